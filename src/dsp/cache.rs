@@ -0,0 +1,322 @@
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bump when the layout of the cached data changes, so older entries are ignored
+const CACHE_VERSION: u32 = 3;
+const ENTRY_EXTENSION: &str = "cache";
+const TMP_EXTENSION: &str = "tmp";
+
+/// On-disk storage of analysis results, reused when the same file is opened
+/// again. Once over the size limit, the least recently used entries are removed
+#[derive(Debug, Clone)]
+pub struct AnalysisCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+/// FNV-1a, stable across builds unlike the std hasher
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl AnalysisCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    /// `$XDG_CACHE_HOME/audeye`, falling back to `~/.cache/audeye`
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+
+        Some(base.join("audeye"))
+    }
+
+    /// Identify the results of an analysis of a file. The key changes whenever
+    /// the file is modified
+    pub fn key(path: &Path, analysis: &str, normalize: bool) -> Option<String> {
+        let path = fs::canonicalize(path).ok()?;
+        let metadata = fs::metadata(&path).ok()?;
+        let mtime = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+
+        Some(format!(
+            "v{} {} {} {} {} normalize={}",
+            CACHE_VERSION,
+            path.display(),
+            metadata.len(),
+            mtime,
+            analysis,
+            normalize
+        ))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.{}", hash(key.as_bytes()), ENTRY_EXTENSION))
+    }
+
+    /// Open the entry stored under this key, positioned on its data
+    pub fn open(&self, key: &str) -> Option<impl Read> {
+        let path = self.entry_path(key);
+        let mut reader = BufReader::new(File::open(&path).ok()?);
+
+        // Entries are named after a hash of their key, check for collisions
+        if read_string(&mut reader).ok()? != key {
+            return None;
+        }
+
+        // Mark the entry as recently used
+        let _ = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+
+        Some(reader)
+    }
+
+    /// Store an entry under this key, then shrink the cache back under its
+    /// size limit
+    pub fn store(
+        &self,
+        key: &str,
+        write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(key);
+
+        // Written aside then renamed, so an incomplete entry is never read
+        let tmp_path = path.with_extension(TMP_EXTENSION);
+        let mut writer = LimitedWriter {
+            inner: BufWriter::new(File::create(&tmp_path)?),
+            written: 0,
+            limit: self.max_size,
+        };
+        let written = write_string(&mut writer, key)
+            .and_then(|_| write(&mut writer))
+            .and_then(|_| writer.flush());
+        let too_large = writer.written > writer.limit;
+        drop(writer);
+        if let Err(err) = written {
+            let _ = fs::remove_file(&tmp_path);
+            // An entry larger than the whole cache is not stored at all
+            return if too_large { Ok(()) } else { Err(err) };
+        }
+        fs::rename(&tmp_path, &path)?;
+
+        self.evict(&path)
+    }
+
+    /// Remove the least recently used entries until the cache fits its limit,
+    /// keeping the one just stored, which fits on its own
+    fn evict(&self, stored: &Path) -> io::Result<()> {
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == ENTRY_EXTENSION))
+            .filter(|path| path != stored)
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), path))
+            })
+            .collect();
+        entries.sort_by_key(|(mtime, _, _)| *mtime);
+
+        let stored_size = fs::metadata(stored)?.len();
+        let mut size: u64 = stored_size + entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        for (_, len, path) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writer failing once more than `limit` bytes would go through it
+struct LimitedWriter<W: Write> {
+    inner: W,
+    written: u64,
+    limit: u64,
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() as u64 > self.limit {
+            self.written += buf.len() as u64;
+            return Err(io::Error::other("Entry larger than the cache"));
+        }
+        let len = self.inner.write(buf)?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn write_u32(writer: &mut dyn Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn write_u64(writer: &mut dyn Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn write_f32(writer: &mut dyn Write, value: f32) -> io::Result<()> {
+    write_u32(writer, value.to_bits())
+}
+
+pub fn read_f32(reader: &mut dyn Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+pub fn write_f64(writer: &mut dyn Write, value: f64) -> io::Result<()> {
+    write_u64(writer, value.to_bits())
+}
+
+pub fn read_f64(reader: &mut dyn Read) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
+
+/// Lengths are read as usize, refusing values too large for this platform
+pub fn read_len(reader: &mut dyn Read) -> io::Result<usize> {
+    usize::try_from(read_u64(reader)?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid length"))
+}
+
+pub fn write_u32_slice(writer: &mut dyn Write, values: &[u32]) -> io::Result<()> {
+    write_u64(writer, values.len() as u64)?;
+    values
+        .iter()
+        .try_for_each(|value| write_u32(writer, *value))
+}
+
+pub fn read_u32_vec(reader: &mut dyn Read) -> io::Result<Vec<u32>> {
+    let len = read_len(reader)?;
+    (0..len).map(|_| read_u32(reader)).collect()
+}
+
+fn write_string(writer: &mut dyn Write, value: &str) -> io::Result<()> {
+    write_u64(writer, value.len() as u64)?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_string(reader: &mut dyn Read) -> io::Result<String> {
+    let len = read_len(reader)?;
+    let mut bytes = vec![];
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_u32_vec, write_u32_slice, AnalysisCache};
+    use std::io::Read;
+
+    fn test_cache(name: &str, max_size: u64) -> AnalysisCache {
+        let dir = std::env::temp_dir().join(format!("audeye_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        AnalysisCache::new(dir, max_size)
+    }
+
+    #[test]
+    fn store_and_open() {
+        let cache = test_cache("store_and_open", 1 << 20);
+        let values: Vec<u32> = (0..1000).collect();
+
+        assert!(cache.open("key").is_none());
+        cache
+            .store("key", |writer| write_u32_slice(writer, &values))
+            .unwrap();
+
+        let mut reader = cache.open("key").unwrap();
+        assert_eq!(read_u32_vec(&mut reader).unwrap(), values);
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+
+        assert!(cache.open("other key").is_none());
+        let _ = std::fs::remove_dir_all(&cache.dir);
+    }
+
+    #[test]
+    fn evict() {
+        // Room for about two entries
+        let cache = test_cache("evict", 9000);
+        let values = vec![0u32; 1000];
+
+        for key in ["first", "second", "third"] {
+            cache
+                .store(key, |writer| write_u32_slice(writer, &values))
+                .unwrap();
+            // Distinct modification times
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        assert!(cache.open("first").is_none());
+        assert!(cache.open("second").is_some());
+        assert!(cache.open("third").is_some());
+        let _ = std::fs::remove_dir_all(&cache.dir);
+    }
+
+    #[test]
+    fn too_large() {
+        let cache = test_cache("too_large", 9000);
+        let values = vec![0u32; 1000];
+        cache
+            .store("small", |writer| write_u32_slice(writer, &values))
+            .unwrap();
+
+        // Neither stored nor evicting the others
+        let values = vec![0u32; 10000];
+        cache
+            .store("large", |writer| write_u32_slice(writer, &values))
+            .unwrap();
+        assert!(cache.open("large").is_none());
+        assert!(cache.open("small").is_some());
+        assert_eq!(std::fs::read_dir(&cache.dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&cache.dir);
+    }
+
+    #[test]
+    fn key() {
+        let path = std::env::current_exe().unwrap();
+        let key = AnalysisCache::key(&path, "waveform", false).unwrap();
+
+        assert_ne!(key, AnalysisCache::key(&path, "waveform", true).unwrap());
+        assert_ne!(
+            key,
+            AnalysisCache::key(&path, "spectrogram", false).unwrap()
+        );
+        assert!(AnalysisCache::key(&path.join("missing"), "waveform", false).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+extern crate sndfile;
+
+use crate::sndfile::SndFile;
+use std::io::{self, Read, Write};
+
+use std::convert::TryFrom;
+
+use super::cache::{read_len, read_u64, write_u64};
+use super::runs::{scan_runs, Run, RunDetector};
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
+use crate::utils::from_dbfs;
+
+/// Run of consecutive samples of a channel at full scale, or close to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipEvent {
+    pub channel: usize,
+    /// First frame of the run
+    pub start: usize,
+    /// Number of samples of the run
+    pub length: usize,
+}
+
+impl ClipEvent {
+    /// Frame after the last one of the run
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+}
+
+/// What counts as clipping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClippingParameters {
+    /// Level a sample has to reach, in dBFS
+    pub threshold: f64,
+    /// Consecutive samples reaching it for a run to count
+    pub min_run: usize,
+}
+
+impl ClippingParameters {
+    /// Detector of the clip events of a signal
+    pub(super) fn detector(&self, channels: usize) -> RunDetector<impl Fn(i32) -> bool> {
+        let level = from_dbfs(self.threshold) * i32::MAX as f64;
+        RunDetector::new(channels, self.min_run, move |sample| {
+            (sample as f64).abs() >= level
+        })
+    }
+}
+
+/// Clip events of every channel, measured on the content of the file as is,
+/// even when normalized for display
+pub struct Clipping {
+    // Sorted by start
+    events: Vec<ClipEvent>,
+    // Number of events of each channel
+    counts: Vec<usize>,
+}
+
+impl DspData<ClippingParameters> for Clipping {
+    fn new(
+        mut sndfile: SndFile,
+        parameters: ClippingParameters,
+        _: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<Clipping, DspErr> {
+        let channels = sndfile.get_channels();
+        let runs = scan_runs(
+            &mut sndfile,
+            parameters.detector(channels),
+            cancel,
+            progress,
+        )?;
+
+        Ok(Clipping::from_runs(channels, &runs))
+    }
+
+    fn cache_key(parameters: &ClippingParameters) -> String {
+        format!("clipping {} {}", parameters.threshold, parameters.min_run)
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.counts.len() as u64)?;
+        write_u64(writer, self.events.len() as u64)?;
+        for event in self.events.iter() {
+            write_u64(writer, event.channel as u64)?;
+            write_u64(writer, event.start as u64)?;
+            write_u64(writer, event.length as u64)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_cache(mut sndfile: SndFile, reader: &mut dyn Read) -> io::Result<Clipping> {
+        let channels = read_len(reader)?;
+        let frames = match sndfile.len().ok().and_then(|len| usize::try_from(len).ok()) {
+            Some(frames) if channels == sndfile.get_channels() => frames,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Cached clipping doesn't match the file",
+                ))
+            }
+        };
+
+        let len = read_len(reader)?;
+        let events = (0..len)
+            .map(|_| {
+                let event = ClipEvent {
+                    channel: read_u64(reader)? as usize,
+                    start: read_u64(reader)? as usize,
+                    length: read_u64(reader)? as usize,
+                };
+                let end = event.start.checked_add(event.length);
+                if event.channel >= channels || end.is_none_or(|end| end > frames) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid event"));
+                }
+                Ok(event)
+            })
+            .collect::<io::Result<Vec<ClipEvent>>>()?;
+
+        let mut counts = vec![0usize; channels];
+        events.iter().for_each(|event| counts[event.channel] += 1);
+
+        Ok(Clipping { events, counts })
+    }
+}
+
+impl Clipping {
+    /// Events of runs of clipped samples, sorted by start
+    pub(super) fn from_runs(channels: usize, runs: &[Run]) -> Clipping {
+        let mut counts = vec![0usize; channels];
+        let events = runs
+            .iter()
+            .map(|run| {
+                counts[run.channel] += 1;
+                ClipEvent {
+                    channel: run.channel,
+                    start: run.start,
+                    length: run.end - run.start,
+                }
+            })
+            .collect();
+
+        Clipping { events, counts }
+    }
+
+    /// Events of all the channels, sorted by start
+    pub fn events(&self) -> &[ClipEvent] {
+        &self.events
+    }
+
+    /// Number of events of a channel
+    pub fn count(&self, channel: usize) -> usize {
+        self.counts[channel]
+    }
+
+    /// Whether each of `count` consecutive blocks of `block_size` frames from
+    /// `start` holds part of an event of the channel
+    pub fn clipped_blocks(
+        &self,
+        channel: usize,
+        start: usize,
+        block_size: usize,
+        count: usize,
+    ) -> Vec<bool> {
+        let mut clipped = vec![false; count];
+        let block_size = usize::max(block_size, 1);
+        let end = start + block_size * count;
+        for event in self
+            .events
+            .iter()
+            .take_while(|event| event.start < end)
+            .filter(|event| event.channel == channel && event.end() > start)
+        {
+            let first = (event.start.max(start) - start) / block_size;
+            let last = (event.end().min(end) - 1 - start) / block_size;
+            clipped[first..=last]
+                .iter_mut()
+                .for_each(|block| *block = true);
+        }
+
+        clipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipEvent, Clipping, ClippingParameters};
+    use crate::dsp::test_files::{compute, open, write_wav};
+    use crate::dsp::DspData;
+
+    #[test]
+    fn runs() {
+        // Full scale runs of 1, 3 and 2 samples on the left channel, and of 4
+        // negative samples at the end of the right one
+        let mut samples = vec![0i16; 2 * 20];
+        for frame in [2usize, 6, 7, 8, 12, 13] {
+            samples[2 * frame] = i16::MAX;
+        }
+        for frame in 16..20 {
+            samples[2 * frame + 1] = i16::MIN;
+        }
+        let path = write_wav("clipping_runs", 2, 44100, &samples);
+
+        let clipping: Clipping = compute(
+            &path,
+            ClippingParameters {
+                threshold: -0.01,
+                min_run: 2,
+            },
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let event = |channel, start, length| ClipEvent {
+            channel,
+            start,
+            length,
+        };
+        assert_eq!(
+            clipping.events(),
+            &[event(0, 6, 3), event(0, 12, 2), event(1, 16, 4)]
+        );
+        assert_eq!(clipping.count(0), 2);
+        assert_eq!(clipping.count(1), 1);
+        assert_eq!(
+            clipping.clipped_blocks(0, 7, 1, 6),
+            vec![true, true, false, false, false, true]
+        );
+        assert_eq!(
+            clipping.clipped_blocks(1, 0, 8, 3),
+            vec![false, false, true]
+        );
+    }
+
+    #[test]
+    fn threshold() {
+        // A full scale run and a run at -4.3 dBFS on a mono channel
+        let mut samples = vec![0i16; 10];
+        samples[2..4].copy_from_slice(&[i16::MAX, i16::MIN]);
+        samples[6..9].copy_from_slice(&[20000, -20000, 20000]);
+        let path = write_wav("clipping_threshold", 1, 44100, &samples);
+
+        let parameters = |threshold| ClippingParameters {
+            threshold,
+            min_run: 1,
+        };
+        let strict: Clipping = compute(&path, parameters(-0.01));
+        let loose: Clipping = compute(&path, parameters(-6f64));
+        std::fs::remove_file(&path).unwrap();
+
+        let event = |start, length| ClipEvent {
+            channel: 0,
+            start,
+            length,
+        };
+        assert_eq!(strict.events(), &[event(2, 2)]);
+        assert_eq!(loose.events(), &[event(2, 2), event(6, 3)]);
+    }
+
+    #[test]
+    fn cache_mismatch() {
+        let mut samples = vec![0i16; 2 * 20];
+        samples[10..14].copy_from_slice(&[i16::MAX; 4]);
+        let path = write_wav("clipping_cache", 2, 44100, &samples);
+        let clipping: Clipping = compute(
+            &path,
+            ClippingParameters {
+                threshold: -0.01,
+                min_run: 1,
+            },
+        );
+
+        let mut bytes = vec![];
+        clipping.write_cache(&mut bytes).unwrap();
+        let cached = Clipping::read_cache(open(&path), &mut bytes.as_slice());
+        // Channel, then end of the first event, out of the file
+        let mut bad_channel = bytes.clone();
+        bad_channel[16..24].copy_from_slice(&2u64.to_le_bytes());
+        let mut bad_end = bytes.clone();
+        bad_end[32..40].copy_from_slice(&16u64.to_le_bytes());
+        // Channels of another file
+        let mut bad_channels = bytes.clone();
+        bad_channels[0..8].copy_from_slice(&1u64.to_le_bytes());
+        let tampered: Vec<bool> = [bad_channel, bad_end, bad_channels]
+            .iter()
+            .map(|bytes| Clipping::read_cache(open(&path), &mut bytes.as_slice()).is_err())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cached.unwrap().events(), clipping.events());
+        assert_eq!(tampered, [true; 3]);
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt::Display;
+
+use colorgrad::{inferno, magma, plasma, turbo, viridis, CustomGradient, Gradient};
+
+/// Number of precomputed colors, dB values are quantized to this resolution
+const LUT_SIZE: usize = 256;
+
+/// A color gradient sampled into a lookup table, used to colorize the dB
+/// magnitudes of a spectrogram
+#[derive(Debug, Clone)]
+pub struct Colormap {
+    name: String,
+    lut: Vec<[u8; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColormapParseError(String);
+
+impl Display for ColormapParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid colormap : {}", self.0)
+    }
+}
+
+/// Range of dB magnitudes mapped onto a colormap. Values under the floor get
+/// the first color, values above the ceiling the last one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DbRange {
+    pub floor: f64,
+    pub ceiling: f64,
+}
+
+/// Smallest span allowed between the floor and the ceiling, in dB
+const MIN_DB_SPAN: f64 = 5f64;
+
+impl DbRange {
+    /// Move the floor by `delta` dB, keeping it under the ceiling
+    pub fn shift_floor(&mut self, delta: f64) {
+        self.floor = (self.floor + delta).min(self.ceiling - MIN_DB_SPAN);
+    }
+
+    /// Move the ceiling by `delta` dB, keeping it above the floor
+    pub fn shift_ceiling(&mut self, delta: f64) {
+        self.ceiling = (self.ceiling + delta).max(self.floor + MIN_DB_SPAN);
+    }
+}
+
+const INFERNO: &str = "inferno";
+const VIRIDIS: &str = "viridis";
+const MAGMA: &str = "magma";
+const PLASMA: &str = "plasma";
+const TURBO: &str = "turbo";
+const GREYSCALE: &str = "greyscale";
+
+pub const COLORMAP_HELP_TEXT: &str = "Spectrogram colors : inferno, viridis, magma, plasma, turbo, greyscale, or a custom gradient given as comma separated hex stops (e.g. \"#000000,#ff0000,#ffffff\")";
+
+impl Colormap {
+    fn from_gradient(name: &str, gradient: &Gradient) -> Self {
+        let lut = (0..LUT_SIZE)
+            .map(|idx| {
+                let color = gradient.at(idx as f64 / (LUT_SIZE - 1) as f64).to_rgba8();
+                [color[0], color[1], color[2]]
+            })
+            .collect();
+
+        Colormap {
+            name: name.to_string(),
+            lut,
+        }
+    }
+
+    fn custom(name: &str, stops: &[&str]) -> Result<Self, ColormapParseError> {
+        if stops.len() < 2 {
+            return Err(ColormapParseError(
+                "a custom gradient needs at least 2 stops".to_string(),
+            ));
+        }
+
+        match CustomGradient::new().html_colors(stops).build() {
+            Ok(gradient) => Ok(Self::from_gradient(name, &gradient)),
+            Err(err) => Err(ColormapParseError(err.to_string())),
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, ColormapParseError> {
+        let gradient = if name == INFERNO {
+            inferno()
+        } else if name == VIRIDIS {
+            viridis()
+        } else if name == MAGMA {
+            magma()
+        } else if name == PLASMA {
+            plasma()
+        } else if name == TURBO {
+            turbo()
+        } else if name == GREYSCALE {
+            return Self::custom(GREYSCALE, &["#000000", "#ffffff"]);
+        } else if name.starts_with('#') {
+            let stops: Vec<&str> = name.split(',').map(|stop| stop.trim()).collect();
+            return Self::custom(name, &stops);
+        } else {
+            return Err(ColormapParseError(name.to_string()));
+        };
+
+        Ok(Self::from_gradient(name, &gradient))
+    }
+
+    pub fn possible_values() -> &'static [&'static str] {
+        &[INFERNO, VIRIDIS, MAGMA, PLASMA, TURBO, GREYSCALE]
+    }
+
+    pub fn default() -> &'static str {
+        INFERNO
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The colormaps to cycle through at runtime : the presets, plus this one
+    /// if it is a custom gradient. Also returns the index of this one
+    pub fn cycle(&self) -> (Vec<Colormap>, usize) {
+        let mut colormaps: Vec<Colormap> = Self::possible_values()
+            .iter()
+            .map(|name| Self::parse(name).unwrap())
+            .collect();
+
+        match colormaps.iter().position(|map| map.name == self.name) {
+            Some(idx) => (colormaps, idx),
+            None => {
+                let idx = colormaps.len();
+                colormaps.push(self.clone());
+                (colormaps, idx)
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn color(&self, db: f32, range: &DbRange) -> [u8; 3] {
+        let floor = range.floor as f32;
+        let ceiling = range.ceiling as f32;
+        let position = ((db - floor) / (ceiling - floor)).clamp(0f32, 1f32);
+        // NaN positions are clamped to the first color
+        let idx = (position * (LUT_SIZE - 1) as f32).round() as usize;
+
+        self.lut[idx.min(LUT_SIZE - 1)]
+    }
+
+    /// Colorize f32 dB magnitudes, given as native endian bytes like the
+    /// buffers of `fast_image_resize`, into rgb pixels
+    pub fn colorize(&self, magnitudes: &[u8], range: &DbRange, dst: &mut Vec<u8>) {
+        dst.clear();
+        dst.reserve(magnitudes.len() / 4 * 3);
+
+        for bytes in magnitudes.chunks_exact(4) {
+            let db = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            dst.extend_from_slice(&self.color(db, range));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Colormap, DbRange};
+
+    #[test]
+    fn parse_all() {
+        Colormap::parse(Colormap::default()).unwrap();
+        for value in Colormap::possible_values() {
+            let colormap = Colormap::parse(value).unwrap();
+            assert_eq!(colormap.name(), *value);
+        }
+    }
+
+    #[test]
+    fn parse_custom() {
+        let colormap = Colormap::parse("#000000, #ff0000,#ffffff").unwrap();
+        let range = DbRange {
+            floor: -100f64,
+            ceiling: 0f64,
+        };
+
+        assert_eq!(colormap.color(-200f32, &range), [0, 0, 0]);
+        let middle = colormap.color(-50f32, &range);
+        assert!(middle[0] == 255 && middle[1] < 5 && middle[2] < 5);
+        assert_eq!(colormap.color(10f32, &range), [255, 255, 255]);
+
+        assert!(Colormap::parse("#000000").is_err());
+        assert!(Colormap::parse("#000000,#nothex").is_err());
+        assert!(Colormap::parse("rainbow").is_err());
+    }
+
+    #[test]
+    fn cycle() {
+        let (colormaps, idx) = Colormap::parse("magma").unwrap().cycle();
+        assert_eq!(colormaps.len(), Colormap::possible_values().len());
+        assert_eq!(colormaps[idx].name(), "magma");
+
+        let (colormaps, idx) = Colormap::parse("#000000,#ffffff").unwrap().cycle();
+        assert_eq!(colormaps.len(), Colormap::possible_values().len() + 1);
+        assert_eq!(colormaps[idx].name(), "#000000,#ffffff");
+    }
+
+    #[test]
+    fn shift_range() {
+        let mut range = DbRange {
+            floor: -100f64,
+            ceiling: 0f64,
+        };
+
+        range.shift_floor(-10f64);
+        range.shift_ceiling(-20f64);
+        assert_eq!(range.floor, -110f64);
+        assert_eq!(range.ceiling, -20f64);
+
+        range.shift_floor(200f64);
+        assert!(range.floor < range.ceiling);
+        range.shift_ceiling(-200f64);
+        assert!(range.floor < range.ceiling);
+    }
+
+    #[test]
+    fn colorize() {
+        let colormap = Colormap::parse("greyscale").unwrap();
+        let range = DbRange {
+            floor: -100f64,
+            ceiling: 0f64,
+        };
+        let magnitudes: Vec<u8> = [-100f32, 0f32, f32::NAN]
+            .iter()
+            .flat_map(|db| db.to_ne_bytes())
+            .collect();
+        let mut dst = vec![];
+        colormap.colorize(&magnitudes, &range, &mut dst);
+
+        assert_eq!(dst, vec![0, 0, 0, 255, 255, 255, 0, 0, 0]);
+    }
+}
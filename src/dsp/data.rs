@@ -1,14 +1,19 @@
 use core::fmt;
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
-use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 extern crate sndfile;
 use crate::sndfile::SndFile;
 
+use super::cache::AnalysisCache;
 use super::normalization::compute_norm;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DspErr {
     msg: String,
 }
@@ -19,6 +24,11 @@ impl DspErr {
             msg: msg.to_string(),
         }
     }
+
+    /// Error returned by the processing aborted through a `CancelToken`
+    pub fn cancelled() -> Self {
+        Self::new("Processing cancelled")
+    }
 }
 
 impl fmt::Display for DspErr {
@@ -27,26 +37,115 @@ impl fmt::Display for DspErr {
     }
 }
 
+/// Shared flag telling a processing to stop as soon as possible
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Receives the fraction of a processing done, from 0 to 1
+#[derive(Default)]
+pub struct ProgressReporter<'a> {
+    callback: Option<Box<dyn FnMut(f64) + 'a>>,
+    last_permille: Option<u32>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(callback: impl FnMut(f64) + 'a) -> Self {
+        Self {
+            callback: Some(Box::new(callback)),
+            last_permille: None,
+        }
+    }
+
+    /// Forward the progress to the callback, only when it changed enough to be
+    /// displayed
+    pub fn report(&mut self, fraction: f64) {
+        let permille = (fraction.clamp(0f64, 1f64) * 1000f64) as u32;
+        if self.last_permille == Some(permille) {
+            return;
+        }
+        self.last_permille = Some(permille);
+
+        if let Some(callback) = self.callback.as_mut() {
+            callback(fraction);
+        }
+    }
+}
+
 pub trait DspData<P> {
-    fn new(file: SndFile, parameter: P, normalize: Option<f64>) -> Result<Self, DspErr>
+    /// Process the file, returning `DspErr::cancelled` early if the token
+    /// gets cancelled, and reporting how much is done along the way
+    fn new(
+        file: SndFile,
+        parameter: P,
+        normalize: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<Self, DspErr>
+    where
+        Self: Sized;
+
+    /// Describe the analysis and its parameters, to identify its results in
+    /// the cache
+    fn cache_key(parameter: &P) -> String;
+
+    /// Serialize the results into the cache
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Load results serialized by `write_cache`. The file is given for the
+    /// data that keeps reading it
+    fn read_cache(file: SndFile, reader: &mut dyn Read) -> io::Result<Self>
     where
         Self: Sized;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+fn open_file(path: &std::path::Path) -> Result<SndFile, DspErr> {
+    let snd = match sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto).from_path(path) {
+        Ok(snd) => snd,
+        Err(_) => return Err(DspErr::new("Could not open wave file")),
+    };
+    if !snd.is_seekable() {
+        return Err(DspErr::new("Input file is not seekable"));
+    }
+
+    Ok(snd)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AsyncDspDataState {
     Created,
     Normalizing,
     Processing,
-    Failed,
+    Failed(DspErr),
+    Cancelled,
     Finished,
 }
 
+/// What the processing thread sends back
+enum AsyncDspMessage {
+    State(AsyncDspDataState),
+    Progress(f64),
+}
+
 pub struct AsyncDspData<T: DspData<P> + Send + 'static, P: Send + 'static> {
     state: AsyncDspDataState,
     pub data: Option<T>,
-    rendered_rx: Receiver<AsyncDspDataState>,
-    process_handle: Option<JoinHandle<T>>,
+    progress: f64,
+    processing_start: Option<Instant>,
+    rendered_rx: Receiver<AsyncDspMessage>,
+    process_handle: Option<JoinHandle<Option<T>>>,
+    cancel_token: CancelToken,
     phantom: PhantomData<P>,
 }
 
@@ -56,29 +155,46 @@ impl<T: DspData<P> + Send + 'static, P: Send + 'static> AsyncDspData<T, P> {
 
         loop {
             match self.rendered_rx.try_recv() {
-                Ok(AsyncDspDataState::Finished) => {
+                Ok(AsyncDspMessage::Progress(progress)) => {
+                    self.progress = progress;
+                    update_needed = true;
+                }
+                Ok(AsyncDspMessage::State(AsyncDspDataState::Finished)) => {
                     // Rendered properly
                     self.load_results();
-                    self.state = AsyncDspDataState::Finished;
                     update_needed = true;
                     break;
                 }
-                Ok(AsyncDspDataState::Failed) => {
-                    // Failed to render, try to join to catch error
-                    let opt_handle = self.process_handle.take();
-                    match opt_handle {
-                        Some(handle) => match handle.join() {
-                            Ok(_) => panic!("Async rendering sent failed signal but succeeded"),
-                            Err(err) => panic!("{:?}", err),
-                        },
-                        None => panic!("Async rendering handle is None"),
-                    }
+                Ok(AsyncDspMessage::State(
+                    new_state @ (AsyncDspDataState::Failed(_) | AsyncDspDataState::Cancelled),
+                )) => {
+                    // Stopped early, nothing to load
+                    self.process_handle.take();
+                    self.state = new_state;
+                    update_needed = true;
+                    break;
                 }
-                Ok(new_state) => {
+                Ok(AsyncDspMessage::State(new_state)) => {
+                    // Both steps report their own progress
+                    if matches!(
+                        new_state,
+                        AsyncDspDataState::Normalizing | AsyncDspDataState::Processing
+                    ) {
+                        self.processing_start = Some(Instant::now());
+                        self.progress = 0f64;
+                    }
                     self.state = new_state;
                     update_needed = true;
                 }
-                _ => {
+                Err(TryRecvError::Disconnected) if self.process_handle.is_some() => {
+                    // The thread is gone without a word, it panicked
+                    self.process_handle.take();
+                    self.state =
+                        AsyncDspDataState::Failed(DspErr::new("Processing stopped unexpectedly"));
+                    update_needed = true;
+                    break;
+                }
+                Err(_) => {
                     break;
                 }
             }
@@ -88,56 +204,160 @@ impl<T: DspData<P> + Send + 'static, P: Send + 'static> AsyncDspData<T, P> {
     }
 
     pub fn state(&mut self) -> AsyncDspDataState {
-        self.state
+        self.state.clone()
     }
 
     pub fn data(&mut self) -> Option<&mut T> {
         self.data.as_mut()
     }
 
-    fn load_results(&mut self) {
-        let opt_handle = self.process_handle.take();
-        match opt_handle {
-            Some(handle) => {
-                self.data = Some(handle.join().expect("Async rendering failed"));
+    /// Fraction of the processing done, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        self.progress
+    }
+
+    /// Estimated time left before the processing is done, extrapolated from
+    /// the progress so far
+    pub fn eta(&self) -> Option<Duration> {
+        const MIN_PROGRESS: f64 = 0.01;
+
+        match self.processing_start {
+            Some(start) if self.progress >= MIN_PROGRESS => {
+                let elapsed = start.elapsed().as_secs_f64();
+                Some(Duration::from_secs_f64(
+                    elapsed * (1f64 - self.progress) / self.progress,
+                ))
             }
-            None => panic!("Async rendering handle is None"),
+            _ => None,
         }
     }
 
-    pub fn new(path: &std::path::PathBuf, parameters: P, normalize: bool) -> Self {
-        let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
-            .from_path(path)
-            .expect("Could not open wave file");
-        if !snd.is_seekable() {
-            panic!("Input file is not seekable");
+    /// Ask the processing to stop. The state becomes `Cancelled` once the
+    /// processing thread noticed it
+    pub fn cancel(&self) {
+        self.cancel_token.cancel();
+    }
+
+    fn load_results(&mut self) {
+        let result = match self.process_handle.take() {
+            Some(handle) => handle.join().ok().flatten(),
+            None => None,
+        };
+
+        self.state = match result {
+            Some(data) => {
+                self.data = Some(data);
+                AsyncDspDataState::Finished
+            }
+            None => AsyncDspDataState::Failed(DspErr::new("Unable to retrieve the results")),
+        };
+    }
+
+    /// A processing that failed before even starting
+    fn failed(err: DspErr) -> Self {
+        // Nothing will ever be sent
+        let (_, rendered_rx) = mpsc::channel();
+
+        Self {
+            state: AsyncDspDataState::Failed(err),
+            data: None,
+            progress: 0f64,
+            processing_start: None,
+            rendered_rx,
+            process_handle: None,
+            cancel_token: CancelToken::default(),
+            phantom: PhantomData,
         }
+    }
+
+    /// Start the processing in the background. With a cache, the results of a
+    /// previous run are loaded instead when available, and new ones are stored
+    pub fn new(
+        path: &std::path::Path,
+        parameters: P,
+        normalize: bool,
+        cache: Option<AnalysisCache>,
+    ) -> Self {
+        let mut snd = match open_file(path) {
+            Ok(snd) => snd,
+            Err(err) => return Self::failed(err),
+        };
+        let cache = cache.and_then(|cache| {
+            AnalysisCache::key(path, &T::cache_key(&parameters), normalize).map(|key| (cache, key))
+        });
 
         let (rendered_tx, rendered_rx) = mpsc::channel();
+        let cancel_token = CancelToken::default();
+        let thread_token = cancel_token.clone();
+        let path = path.to_path_buf();
         let join_handle = thread::spawn(move || {
-            // First, compute the norm if needed
-            let norm = if normalize {
-                let _ = rendered_tx.send(AsyncDspDataState::Normalizing);
-                Some(compute_norm(&mut snd))
-            } else {
-                None
+            // Reuse the results of a previous run if possible
+            if let Some(mut reader) = cache.as_ref().and_then(|(cache, key)| cache.open(key)) {
+                match T::read_cache(snd, &mut reader) {
+                    Ok(data) => {
+                        let _ =
+                            rendered_tx.send(AsyncDspMessage::State(AsyncDspDataState::Finished));
+                        return Some(data);
+                    }
+                    Err(_) => {
+                        // Invalid entry, it will be overwritten
+                        snd = match open_file(&path) {
+                            Ok(snd) => snd,
+                            Err(err) => {
+                                let _ = rendered_tx
+                                    .send(AsyncDspMessage::State(AsyncDspDataState::Failed(err)));
+                                return None;
+                            }
+                        };
+                    }
+                }
+            }
+
+            let progress_reporter = || {
+                let progress_tx = rendered_tx.clone();
+                ProgressReporter::new(move |fraction| {
+                    let _ = progress_tx.send(AsyncDspMessage::Progress(fraction));
+                })
             };
 
-            // Start the processing
-            let _ = rendered_tx.send(AsyncDspDataState::Processing);
-            let res = T::new(snd, parameters, norm);
+            // First, compute the norm if needed, then start the processing
+            let res = if normalize {
+                let _ = rendered_tx.send(AsyncDspMessage::State(AsyncDspDataState::Normalizing));
+                compute_norm(&mut snd, &thread_token, &mut progress_reporter()).map(Some)
+            } else {
+                Ok(None)
+            }
+            .and_then(|norm| {
+                let _ = rendered_tx.send(AsyncDspMessage::State(AsyncDspDataState::Processing));
+                T::new(
+                    snd,
+                    parameters,
+                    norm,
+                    &thread_token,
+                    &mut progress_reporter(),
+                )
+            });
 
             // Check the processing result
             match res {
                 Ok(data) => {
-                    // Success, we update the state and return the data
-                    let _ = rendered_tx.send(AsyncDspDataState::Finished);
-                    data
+                    // Success, we store the data for later runs, update the
+                    // state and return it. The cache is only an optimization
+                    if let Some((cache, key)) = cache.as_ref() {
+                        let _ = cache.store(key, |writer| data.write_cache(writer));
+                    }
+                    let _ = rendered_tx.send(AsyncDspMessage::State(AsyncDspDataState::Finished));
+                    Some(data)
+                }
+                Err(_) if thread_token.is_cancelled() => {
+                    let _ = rendered_tx.send(AsyncDspMessage::State(AsyncDspDataState::Cancelled));
+                    None
                 }
                 Err(dsp_err) => {
-                    // Failure, we stop the program and display the error
-                    let _ = rendered_tx.send(AsyncDspDataState::Failed);
-                    panic!("{}", dsp_err);
+                    // Failure, the error is displayed in place of the data
+                    let _ = rendered_tx
+                        .send(AsyncDspMessage::State(AsyncDspDataState::Failed(dsp_err)));
+                    None
                 }
             }
         });
@@ -145,9 +365,43 @@ impl<T: DspData<P> + Send + 'static, P: Send + 'static> AsyncDspData<T, P> {
         Self {
             state: AsyncDspDataState::Created,
             data: None,
+            progress: 0f64,
+            processing_start: None,
             rendered_rx,
             process_handle: Some(join_handle),
+            cancel_token,
             phantom: PhantomData,
         }
     }
 }
+
+impl<T: DspData<P> + Send + 'static, P: Send + 'static> Drop for AsyncDspData<T, P> {
+    fn drop(&mut self) {
+        // Nobody will read the results anymore
+        self.cancel_token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressReporter;
+
+    #[test]
+    fn progress_reporter() {
+        let mut reported = vec![];
+        {
+            let mut progress = ProgressReporter::new(|fraction| reported.push(fraction));
+            for idx in 0..=10000 {
+                progress.report(idx as f64 / 10000f64);
+            }
+        }
+
+        // At most one report per permille
+        assert_eq!(reported.len(), 1001);
+        assert_eq!(reported.first(), Some(&0f64));
+        assert_eq!(reported.last(), Some(&1f64));
+
+        // Nothing to forward to
+        ProgressReporter::default().report(0.5f64);
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt::Display;
+
+use crate::utils::axis::{format_frequency, ticks};
+
+/// Range [start; end[ of the source bins merged into each output bin
+pub type BinMap = Vec<(usize, usize)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrequencyScale {
+    Linear,
+    Logarithmic,
+    Mel,
+    Bark,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrequencyScaleParseError;
+
+impl Display for FrequencyScaleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid frequency scale")
+    }
+}
+
+const LINEAR: &str = "linear";
+const LOGARITHMIC: &str = "log";
+const MEL: &str = "mel";
+const BARK: &str = "bark";
+
+impl FrequencyScale {
+    pub fn parse(name: &str) -> Result<Self, FrequencyScaleParseError> {
+        if name == LINEAR {
+            Ok(Self::Linear)
+        } else if name == LOGARITHMIC {
+            Ok(Self::Logarithmic)
+        } else if name == MEL {
+            Ok(Self::Mel)
+        } else if name == BARK {
+            Ok(Self::Bark)
+        } else {
+            Err(FrequencyScaleParseError)
+        }
+    }
+
+    pub fn possible_values() -> &'static [&'static str] {
+        &[LINEAR, LOGARITHMIC, MEL, BARK]
+    }
+
+    pub fn default() -> &'static str {
+        LINEAR
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => LINEAR,
+            Self::Logarithmic => LOGARITHMIC,
+            Self::Mel => MEL,
+            Self::Bark => BARK,
+        }
+    }
+
+    /// The scale to switch to when cycling through them
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Linear => Self::Logarithmic,
+            Self::Logarithmic => Self::Mel,
+            Self::Mel => Self::Bark,
+            Self::Bark => Self::Linear,
+        }
+    }
+
+    fn forward(&self, hz: f64) -> f64 {
+        match self {
+            Self::Linear => hz,
+            Self::Logarithmic => hz.ln(),
+            Self::Mel => 2595f64 * f64::log10(1f64 + hz / 700f64),
+            // Traunmüller formula
+            Self::Bark => 26.81f64 * hz / (1960f64 + hz) - 0.53f64,
+        }
+    }
+
+    fn inverse(&self, value: f64) -> f64 {
+        match self {
+            Self::Linear => value,
+            Self::Logarithmic => value.exp(),
+            Self::Mel => 700f64 * (10f64.powf(value / 2595f64) - 1f64),
+            Self::Bark => 1960f64 * (value + 0.53f64) / (26.28f64 - value),
+        }
+    }
+
+    /// Relative position of a frequency along the scale, from 0 (`min_hz`) to
+    /// 1 (`max_hz`)
+    pub fn position(&self, hz: f64, min_hz: f64, max_hz: f64) -> f64 {
+        let (min, max) = (self.forward(min_hz), self.forward(max_hz));
+        (self.forward(hz) - min) / (max - min)
+    }
+
+    /// Frequency at a relative position along the scale, from 0 (`min_hz`) to
+    /// 1 (`max_hz`)
+    pub fn frequency_at(&self, position: f64, min_hz: f64, max_hz: f64) -> f64 {
+        let (min, max) = (self.forward(min_hz), self.forward(max_hz));
+        self.inverse(min + position * (max - min))
+    }
+
+    /// For each of the `dst_bins` output bins, spread along the scale between
+    /// `min_hz` and `max_hz`, the range [start; end[ of the source bins it
+    /// covers, at least one. Source bins are evenly spaced, the first one being
+    /// at `nyquist / num_bins` and the last one at `nyquist`
+    pub fn bin_map(
+        &self,
+        num_bins: usize,
+        nyquist: f64,
+        min_hz: f64,
+        max_hz: f64,
+        dst_bins: usize,
+    ) -> BinMap {
+        let bin_width = nyquist / num_bins as f64;
+        // Number of source bins below a frequency, or up to it included. A
+        // little slack absorbs rounding errors on edges falling on bins
+        let bins_below =
+            |hz: f64| ((hz / bin_width - 1e-9).ceil().max(1f64) as usize - 1).min(num_bins);
+        let bins_up_to = |hz: f64| ((hz / bin_width + 1e-9).floor() as usize).min(num_bins);
+
+        (0..dst_bins)
+            .map(|idx| {
+                let low = self.frequency_at(idx as f64 / dst_bins as f64, min_hz, max_hz);
+                let high = self.frequency_at((idx + 1) as f64 / dst_bins as f64, min_hz, max_hz);
+                let start = bins_below(low);
+                // The last output bin includes `max_hz`
+                let end = if idx + 1 == dst_bins {
+                    bins_up_to(high)
+                } else {
+                    bins_below(high)
+                };
+                if start < end {
+                    return (start, end);
+                }
+
+                // Narrower than a source bin, the closest one is displayed
+                let position = (idx as f64 + 0.5f64) / dst_bins as f64;
+                let hz = self.frequency_at(position, min_hz, max_hz);
+                let bin = ((hz / bin_width).round() as usize).clamp(1, num_bins) - 1;
+                (bin, bin + 1)
+            })
+            .collect()
+    }
+
+    /// Round frequencies to label an axis with, spread evenly along the scale
+    pub fn ticks(&self, min_hz: f64, max_hz: f64, max_count: usize) -> Vec<f64> {
+        if *self == Self::Linear {
+            return ticks(min_hz, max_hz, max_count);
+        }
+        if max_count == 0 {
+            return vec![];
+        }
+
+        // Greedily keep the 1-2-5 values far enough from the previous one
+        let min_spacing = 1f64 / max_count as f64;
+        let mut values: Vec<f64> = vec![];
+        let mut decade = 10f64;
+        while decade <= max_hz {
+            for hz in [decade, 2f64 * decade, 5f64 * decade] {
+                if hz < min_hz || hz > max_hz {
+                    continue;
+                }
+                let far_enough = match values.last() {
+                    Some(prev) => {
+                        self.position(hz, min_hz, max_hz) - self.position(*prev, min_hz, max_hz)
+                            >= min_spacing
+                    }
+                    None => true,
+                };
+                if far_enough {
+                    values.push(hz);
+                }
+            }
+            decade *= 10f64;
+        }
+
+        values
+    }
+
+    /// Labels of a frequency axis along the scale, as (relative position, text)
+    /// pairs
+    pub fn labels(&self, min_hz: f64, max_hz: f64, max_count: usize) -> Vec<(f64, String)> {
+        self.ticks(min_hz, max_hz, max_count)
+            .into_iter()
+            .map(|hz| (self.position(hz, min_hz, max_hz), format_frequency(hz)))
+            .collect()
+    }
+}
+
+/// Rearrange the bins of band-major dB magnitudes, stored as f32 bits,
+/// according to a bin map computed by `FrequencyScale::bin_map`. Merged bins
+/// keep their highest magnitude, so that narrow peaks remain visible
+pub fn remap_bins(src: &[u32], num_bins: usize, map: &[(usize, usize)], dst: &mut Vec<u32>) {
+    let num_bands = src.len() / num_bins;
+    dst.resize(num_bands * map.len(), 0);
+
+    src.chunks_exact(num_bins)
+        .zip(dst.chunks_exact_mut(map.len()))
+        .for_each(|(src_band, dst_band)| {
+            for (dst_value, (start, end)) in dst_band.iter_mut().zip(map.iter()) {
+                let max = src_band[*start..*end]
+                    .iter()
+                    .map(|bits| f32::from_bits(*bits))
+                    .fold(f32::NEG_INFINITY, f32::max);
+                *dst_value = max.to_bits();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remap_bins, FrequencyScale};
+
+    const NYQUIST: f64 = 22050f64;
+
+    #[test]
+    fn parse_all() {
+        FrequencyScale::parse(FrequencyScale::default()).unwrap();
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+            assert_eq!(scale.name(), *value);
+        }
+    }
+
+    #[test]
+    fn relative_roundtrip() {
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+
+            for hz in [20f64, 440f64, 1000f64, 15000f64] {
+                let position = scale.position(hz, 10f64, NYQUIST);
+                assert!((0f64..=1f64).contains(&position));
+                assert!((scale.frequency_at(position, 10f64, NYQUIST) - hz).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn bin_map_is_monotonic() {
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+
+            for (num_bins, dst_bins) in [(2048usize, 2048usize), (256, 1000), (4096, 100)] {
+                let min_hz = NYQUIST / num_bins as f64;
+                let map = scale.bin_map(num_bins, NYQUIST, min_hz, NYQUIST, dst_bins);
+
+                assert_eq!(map.len(), dst_bins);
+                assert!(map.windows(2).all(|pair| pair[0] <= pair[1]));
+                assert!(map
+                    .iter()
+                    .all(|(start, end)| start < end && *end <= num_bins));
+            }
+        }
+    }
+
+    #[test]
+    fn bin_map_range() {
+        let num_bins = 2048usize;
+        let bin_width = NYQUIST / num_bins as f64;
+
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+            let map = scale.bin_map(num_bins, NYQUIST, 100f64, 500f64, 200);
+
+            // Only the bins between 100 and 500 Hz are displayed
+            assert!(map.iter().all(|(start, end)| {
+                (*start + 1) as f64 * bin_width >= 100f64 - bin_width
+                    && *end as f64 * bin_width <= 500f64 + bin_width
+            }));
+        }
+
+        // Linear ranges are contiguous bins
+        let map = FrequencyScale::Linear.bin_map(
+            num_bins,
+            NYQUIST,
+            bin_width * 11f64,
+            bin_width * 20f64,
+            10,
+        );
+        assert_eq!(map, (10..20).map(|bin| (bin, bin + 1)).collect::<Vec<_>>());
+
+        // Downscaled, every source bin is covered exactly once
+        let map = FrequencyScale::Linear.bin_map(num_bins, NYQUIST, bin_width, NYQUIST, 100);
+        assert_eq!(map.first().unwrap().0, 0);
+        assert_eq!(map.last().unwrap().1, num_bins);
+        assert!(map.windows(2).all(|pair| pair[0].1 == pair[1].0));
+    }
+
+    #[test]
+    fn remap_keeps_peaks() {
+        // A single loud bin at high frequencies, squeezed into a few rows
+        let num_bins = 2048usize;
+        let mut src = vec![(-120f32).to_bits(); num_bins];
+        src[1800] = (-6f32).to_bits();
+
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+            let map = scale.bin_map(num_bins, NYQUIST, NYQUIST / num_bins as f64, NYQUIST, 64);
+            let mut dst = vec![];
+            remap_bins(&src, num_bins, &map, &mut dst);
+
+            let values: Vec<f32> = dst.iter().map(|bits| f32::from_bits(*bits)).collect();
+            assert_eq!(values.iter().filter(|value| **value == -6f32).count(), 1);
+            assert!(values
+                .iter()
+                .all(|value| *value == -6f32 || *value == -120f32));
+        }
+    }
+
+    #[test]
+    fn ticks_are_contained() {
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+            let values = scale.ticks(10f64, NYQUIST, 8);
+
+            assert!(!values.is_empty());
+            assert!(values.iter().all(|hz| *hz >= 10f64 && *hz <= NYQUIST));
+        }
+    }
+}
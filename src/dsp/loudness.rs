@@ -0,0 +1,529 @@
+extern crate sndfile;
+use sndfile::SndFileIO;
+
+use crate::sndfile::SndFile;
+use std::io::{self, Read, SeekFrom, Write};
+
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+use std::os::raw::{c_int, c_void};
+
+use super::cache::{read_f64, read_len, write_f64, write_u64};
+use super::true_peak::TruePeakMeter;
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
+use crate::utils::to_dbfs;
+
+/// Frames read at once
+const READ_BLOCK_SIZE: usize = 4096;
+/// Duration of the blocks the signal power is summed over, in seconds. The
+/// loudness curves have one point per block
+const BLOCK_DURATION: f64 = 0.1;
+/// Blocks within the momentary (400ms) and short-term (3s) windows
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Gates of the integrated loudness, in LUFS and LU below the ungated
+/// loudness (BS.1770-4)
+const ABSOLUTE_GATE: f64 = -70f64;
+const RELATIVE_GATE: f64 = -10f64;
+/// Relative gate and percentiles of the loudness range (EBU Tech 3342)
+const RANGE_RELATIVE_GATE: f64 = -20f64;
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+
+/// Position of each channel, when the format of the file stores them
+fn channel_map(sndfile: &SndFile) -> Option<Vec<c_int>> {
+    let channels = sndfile.get_channels();
+    let mut map = vec![sndfile_sys::SF_CHANNEL_MAP_INVALID; channels];
+    // libsndfile fills at most the size given, one position per channel
+    let found = unsafe {
+        sndfile_sys::sf_command(
+            sndfile.get_raw_struct().sndfile_ptr,
+            sndfile_sys::SFC_GET_CHANNEL_MAP_INFO,
+            map.as_mut_ptr() as *mut c_void,
+            (channels * std::mem::size_of::<c_int>()) as c_int,
+        )
+    };
+
+    (found == sndfile_sys::SF_TRUE).then_some(map)
+}
+
+/// Weight of a channel in the sum of the powers, following BS.1770-4 :
+/// surround channels are boosted by 1.5 dB and the LFE is left out
+fn position_weight(position: c_int) -> f64 {
+    const SURROUND: f64 = 1.41;
+
+    match position {
+        sndfile_sys::SF_CHANNEL_MAP_LFE => 0f64,
+        sndfile_sys::SF_CHANNEL_MAP_SIDE_LEFT
+        | sndfile_sys::SF_CHANNEL_MAP_SIDE_RIGHT
+        | sndfile_sys::SF_CHANNEL_MAP_REAR_LEFT
+        | sndfile_sys::SF_CHANNEL_MAP_REAR_RIGHT
+        | sndfile_sys::SF_CHANNEL_MAP_REAR_CENTER => SURROUND,
+        _ => 1f64,
+    }
+}
+
+/// Weight of each channel in the sum of their powers, from the positions
+/// stored in the file. Without them, quad, 5.0, 5.1 and 7.1 files are assumed
+/// in the order of the WAVE format : FL FR (C) (LFE) BL BR (SL SR). Other
+/// channels are all weighted equally, their positions being unknown
+pub fn channel_weights(channels: usize, map: Option<&[c_int]>) -> Vec<f64> {
+    use sndfile_sys::{
+        SF_CHANNEL_MAP_FRONT_CENTER as C, SF_CHANNEL_MAP_FRONT_LEFT as FL,
+        SF_CHANNEL_MAP_FRONT_RIGHT as FR, SF_CHANNEL_MAP_LFE as LFE,
+        SF_CHANNEL_MAP_REAR_LEFT as BL, SF_CHANNEL_MAP_REAR_RIGHT as BR,
+        SF_CHANNEL_MAP_SIDE_LEFT as SL, SF_CHANNEL_MAP_SIDE_RIGHT as SR,
+    };
+
+    let assumed: &[c_int] = match channels {
+        4 => &[FL, FR, BL, BR],
+        5 => &[FL, FR, C, BL, BR],
+        6 => &[FL, FR, C, LFE, BL, BR],
+        8 => &[FL, FR, C, LFE, BL, BR, SL, SR],
+        _ => &[],
+    };
+    match map.filter(|map| map.len() == channels) {
+        Some(map) => map.iter().copied().map(position_weight).collect(),
+        None if assumed.len() == channels => assumed.iter().copied().map(position_weight).collect(),
+        None => vec![1f64; channels],
+    }
+}
+
+/// Loudness of a weighted mean square power, in LUFS
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10f64 * power.log10()
+}
+
+/// Second order IIR filter, as a transposed direct form II
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// K-weighting filter of BS.1770-4 : a high shelf modelling the head, then a
+/// high-pass. Coefficients are derived for any samplerate, matching the ones
+/// given for 48kHz
+fn k_weighting(samplerate: f64) -> [Biquad; 2] {
+    // High shelf
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = f64::tan(PI * f0 / samplerate);
+    let vh = 10f64.powf(gain / 20f64);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1f64 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2f64 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2f64 * (k * k - 1f64) / a0, (1f64 - k / q + k * k) / a0],
+        state: [0f64; 2],
+    };
+
+    // High-pass
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = f64::tan(PI * f0 / samplerate);
+    let a0 = 1f64 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1f64, -2f64, 1f64],
+        a: [2f64 * (k * k - 1f64) / a0, (1f64 - k / q + k * k) / a0],
+        state: [0f64; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Mean of the powers of the blocks of each window of `size` blocks ending on
+/// each block. Windows are shortened at the start of the content
+fn window_powers(powers: &[f64], size: usize) -> Vec<f64> {
+    let mut sum = 0f64;
+    (0..powers.len())
+        .map(|idx| {
+            sum += powers[idx];
+            if idx >= size {
+                // Rounding errors can leave a tiny negative sum once the
+                // window falls silent, where the loudness would be NaN
+                sum = f64::max(sum - powers[idx - size], 0f64);
+            }
+            sum / usize::min(idx + 1, size) as f64
+        })
+        .collect()
+}
+
+/// Loudness of the mean power of the windows above both gates : the absolute
+/// one, and the relative one below the loudness of the windows above the
+/// absolute gate. Also returns the windows above both gates
+fn gated(powers: &[f64], relative_gate: f64) -> (f64, Vec<f64>) {
+    let mean = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+
+    let above_absolute: Vec<f64> = powers
+        .iter()
+        .copied()
+        .filter(|power| to_lufs(*power) > ABSOLUTE_GATE)
+        .collect();
+    if above_absolute.is_empty() {
+        return (f64::NEG_INFINITY, vec![]);
+    }
+
+    let threshold = to_lufs(mean(&above_absolute)) + relative_gate;
+    let above_relative: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|power| to_lufs(*power) > threshold)
+        .collect();
+
+    (to_lufs(mean(&above_relative)), above_relative)
+}
+
+/// Measure the loudness of a signal, fed frame by frame
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    true_peak: TruePeakMeter,
+    block_size: usize,
+    // Sum of the squared filtered samples of each channel, in the current block
+    block_sums: Vec<f64>,
+    block_frames: usize,
+    // Weighted mean square of each block
+    powers: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    /// Channels are summed with the given weights, as given by
+    /// `channel_weights`
+    pub fn new(weights: Vec<f64>, samplerate: usize) -> Self {
+        let channels = weights.len();
+        LoudnessMeter {
+            filters: vec![k_weighting(samplerate as f64); channels],
+            weights,
+            true_peak: TruePeakMeter::new(channels),
+            block_size: usize::max(1, (samplerate as f64 * BLOCK_DURATION).round() as usize),
+            block_sums: vec![0f64; channels],
+            block_frames: 0,
+            powers: vec![],
+        }
+    }
+
+    /// Measure the next frame, with one sample per channel in [-1; 1]
+    pub fn push(&mut self, frame: &[f64]) {
+        for ((filters, sum), sample) in self
+            .filters
+            .iter_mut()
+            .zip(self.block_sums.iter_mut())
+            .zip(frame)
+        {
+            let filtered = filters
+                .iter_mut()
+                .fold(*sample, |value, filter| filter.process(value));
+            *sum += filtered * filtered;
+        }
+        self.true_peak.push(frame);
+
+        self.block_frames += 1;
+        if self.block_frames == self.block_size {
+            let block_size = self.block_size as f64;
+            let power = self
+                .block_sums
+                .iter()
+                .zip(self.weights.iter())
+                .map(|(sum, weight)| weight * sum / block_size)
+                .sum();
+            self.powers.push(power);
+            self.block_sums.iter_mut().for_each(|sum| *sum = 0f64);
+            self.block_frames = 0;
+        }
+    }
+
+    /// Compute the measures over the whole signal fed. The last block is left
+    /// out when incomplete
+    pub fn finish(self) -> Loudness {
+        let momentary = window_powers(&self.powers, MOMENTARY_BLOCKS);
+        let short_term = window_powers(&self.powers, SHORT_TERM_BLOCKS);
+
+        // Gating only considers complete windows
+        let complete = |powers: &[f64], size: usize| -> Vec<f64> {
+            powers.iter().skip(size - 1).copied().collect()
+        };
+        let (integrated, _) = gated(&complete(&momentary, MOMENTARY_BLOCKS), RELATIVE_GATE);
+        let (_, range_powers) = gated(
+            &complete(&short_term, SHORT_TERM_BLOCKS),
+            RANGE_RELATIVE_GATE,
+        );
+
+        let mut range_levels: Vec<f64> = range_powers.into_iter().map(to_lufs).collect();
+        range_levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let range = match range_levels.len() {
+            0 => 0f64,
+            len => {
+                let percentile =
+                    |ratio: f64| range_levels[(ratio * (len - 1) as f64).round() as usize];
+                percentile(RANGE_HIGH_PERCENTILE) - percentile(RANGE_LOW_PERCENTILE)
+            }
+        };
+
+        Loudness {
+            momentary: momentary.into_iter().map(to_lufs).collect(),
+            short_term: short_term.into_iter().map(to_lufs).collect(),
+            integrated,
+            range,
+            true_peak: self.true_peak.peaks().iter().fold(0f64, |a, b| a.max(*b)),
+        }
+    }
+}
+
+pub struct LoudnessParameters;
+
+/// Loudness of the whole content following EBU R128 : integrated loudness,
+/// loudness range and true peak, along with the momentary and short-term
+/// loudness over time. Measured on the content as is, even when normalized for
+/// display
+pub struct Loudness {
+    // One value per block, in LUFS
+    momentary: Vec<f64>,
+    short_term: Vec<f64>,
+    integrated: f64,
+    range: f64,
+    true_peak: f64,
+}
+
+impl DspData<LoudnessParameters> for Loudness {
+    fn new(
+        mut sndfile: SndFile,
+        _: LoudnessParameters,
+        _: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<Loudness, DspErr> {
+        let frames = match sndfile.len() {
+            Ok(frames) => usize::try_from(frames).unwrap(),
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+        };
+        if sndfile.seek(SeekFrom::Start(0)).is_err() {
+            return Err(DspErr::new("Failed to seek 0"));
+        }
+        let channels = sndfile.get_channels();
+
+        let weights = channel_weights(channels, channel_map(&sndfile).as_deref());
+        let mut meter = LoudnessMeter::new(weights, sndfile.get_samplerate());
+        let mut block_data: Vec<f64> = vec![0f64; READ_BLOCK_SIZE * channels];
+        let mut read_frames = 0usize;
+
+        while read_frames < frames {
+            if cancel.is_cancelled() {
+                return Err(DspErr::cancelled());
+            }
+
+            let nb_frames = match sndfile.read_to_slice(block_data.as_mut_slice()) {
+                Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+                Ok(nb_frames) => nb_frames,
+            };
+            block_data[..nb_frames * channels]
+                .chunks_exact(channels)
+                .for_each(|frame| meter.push(frame));
+
+            read_frames += nb_frames;
+            progress.report(read_frames as f64 / frames as f64);
+        }
+
+        Ok(meter.finish())
+    }
+
+    fn cache_key(_: &LoudnessParameters) -> String {
+        format!("loudness {}", BLOCK_DURATION)
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_f64(writer, self.integrated)?;
+        write_f64(writer, self.range)?;
+        write_f64(writer, self.true_peak)?;
+
+        for curve in [&self.momentary, &self.short_term] {
+            write_u64(writer, curve.len() as u64)?;
+            for value in curve.iter() {
+                write_f64(writer, *value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_cache(_: SndFile, reader: &mut dyn Read) -> io::Result<Loudness> {
+        let integrated = read_f64(reader)?;
+        let range = read_f64(reader)?;
+        let true_peak = read_f64(reader)?;
+
+        let mut read_curve = || -> io::Result<Vec<f64>> {
+            let len = read_len(reader)?;
+            (0..len).map(|_| read_f64(reader)).collect()
+        };
+        let momentary = read_curve()?;
+        let short_term = read_curve()?;
+
+        Ok(Loudness {
+            momentary,
+            short_term,
+            integrated,
+            range,
+            true_peak,
+        })
+    }
+}
+
+impl Loudness {
+    /// Duration between two points of the loudness curves, in seconds
+    pub const STEP_DURATION: f64 = BLOCK_DURATION;
+
+    /// Integrated loudness, in LUFS
+    pub fn integrated(&self) -> f64 {
+        self.integrated
+    }
+
+    /// Loudness range, in LU
+    pub fn range(&self) -> f64 {
+        self.range
+    }
+
+    /// Highest true peak of all the channels, in dBTP
+    pub fn true_peak(&self) -> f64 {
+        to_dbfs(self.true_peak)
+    }
+
+    /// Momentary loudness (400ms windows) at each step of `STEP_DURATION`, in
+    /// LUFS
+    pub fn momentary(&self) -> &[f64] {
+        &self.momentary
+    }
+
+    /// Short-term loudness (3s windows) at each step of `STEP_DURATION`, in
+    /// LUFS
+    pub fn short_term(&self) -> &[f64] {
+        &self.short_term
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel_weights, window_powers, LoudnessMeter};
+    use crate::dsp::{CancelToken, DspData, Loudness, LoudnessParameters, ProgressReporter};
+    use std::f64::consts::PI;
+    use std::path::{Path, PathBuf};
+
+    fn get_test_files_location() -> PathBuf {
+        Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+    }
+
+    /// Feed a 1kHz sine, the same on every channel, at a given level in dBFS
+    fn feed_sine(meter: &mut LoudnessMeter, channels: usize, level: f64, seconds: f64) {
+        let samplerate = 48000f64;
+        let amplitude = 10f64.powf(level / 20f64);
+        for idx in 0..(seconds * samplerate) as usize {
+            let value = amplitude * (2f64 * PI * 1000f64 * idx as f64 / samplerate).sin();
+            meter.push(&vec![value; channels]);
+        }
+    }
+
+    #[test]
+    fn integrated() {
+        // EBU Tech 3341, case 1 : a stereo sine at -23 dBFS is -23 LUFS
+        let mut meter = LoudnessMeter::new(channel_weights(2, None), 48000);
+        feed_sine(&mut meter, 2, -23f64, 20f64);
+        let loudness = meter.finish();
+
+        assert!((loudness.integrated() + 23f64).abs() < 0.1f64);
+        assert!(loudness.range() < 0.1f64);
+        assert!(loudness.momentary().len() == 200);
+        assert!((loudness.momentary()[100] + 23f64).abs() < 0.1f64);
+        assert!((loudness.short_term()[100] + 23f64).abs() < 0.1f64);
+        assert!((loudness.true_peak() + 23f64).abs() < 0.1f64);
+    }
+
+    #[test]
+    fn gating() {
+        // Silence is gated out
+        let mut meter = LoudnessMeter::new(channel_weights(2, None), 48000);
+        feed_sine(&mut meter, 2, -23f64, 10f64);
+        feed_sine(&mut meter, 2, -100f64, 10f64);
+        let loudness = meter.finish();
+        assert!((loudness.integrated() + 23f64).abs() < 0.1f64);
+
+        // Nothing above the absolute gate
+        let mut meter = LoudnessMeter::new(channel_weights(1, None), 48000);
+        feed_sine(&mut meter, 1, -90f64, 1f64);
+        assert_eq!(meter.finish().integrated(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn range() {
+        // EBU Tech 3342, case 1 : 20s at -20 dBFS then 20s at -30 dBFS
+        let mut meter = LoudnessMeter::new(channel_weights(2, None), 48000);
+        feed_sine(&mut meter, 2, -20f64, 20f64);
+        feed_sine(&mut meter, 2, -30f64, 20f64);
+        let loudness = meter.finish();
+
+        assert!((loudness.range() - 10f64).abs() < 1f64);
+    }
+
+    #[test]
+    fn silent_windows() {
+        // Summing then removing these leaves a negative rounding error
+        let powers = [0.7f64, 0.2f64, 0.9f64, 0f64, 0f64, 0f64];
+        assert_eq!(window_powers(&powers, 3)[5], 0f64);
+    }
+
+    #[test]
+    fn weights() {
+        assert_eq!(channel_weights(2, None), vec![1f64; 2]);
+        assert_eq!(channel_weights(6, None)[3], 0f64);
+        assert!(channel_weights(6, None)[4] > 1f64);
+        // Unknown positions aren't guessed
+        assert_eq!(channel_weights(7, None), vec![1f64; 7]);
+        // Positions stored in the file come first
+        let map = [
+            sndfile_sys::SF_CHANNEL_MAP_LFE,
+            sndfile_sys::SF_CHANNEL_MAP_SIDE_LEFT,
+            sndfile_sys::SF_CHANNEL_MAP_FRONT_LEFT,
+        ];
+        assert_eq!(channel_weights(3, Some(&map)), vec![0f64, 1.41f64, 1f64]);
+        assert_eq!(channel_weights(3, Some(&map[..2])), vec![1f64; 3]);
+
+        // The LFE doesn't count
+        let mut meter = LoudnessMeter::new(channel_weights(6, None), 48000);
+        feed_sine(&mut meter, 6, -23f64, 5f64);
+        let with_lfe = meter.finish().integrated();
+        let mut meter = LoudnessMeter::new(channel_weights(5, None), 48000);
+        feed_sine(&mut meter, 5, -23f64, 5f64);
+        assert!((with_lfe - meter.finish().integrated()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn file() {
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap();
+        let loudness = Loudness::new(
+            snd,
+            LoudnessParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        assert!(loudness.integrated().is_finite());
+        assert!(loudness.integrated() < 0f64);
+        assert!(loudness.true_peak() <= 0.5f64);
+        assert_eq!(loudness.momentary().len(), loudness.short_term().len());
+    }
+}
@@ -1,10 +1,33 @@
+mod cache;
+mod clipping;
+mod colormap;
 mod data;
+mod frequency_scale;
+mod loudness;
 mod normalization;
+mod overview;
+mod runs;
+mod silence;
 mod spectrogram;
+mod statistics;
 mod time_window;
+mod true_peak;
 mod waveform;
 
-pub use data::{AsyncDspData, AsyncDspDataState, DspData, DspErr};
+#[cfg(test)]
+mod test_files;
+
+pub use cache::AnalysisCache;
+pub use clipping::{ClipEvent, Clipping, ClippingParameters};
+pub use colormap::{Colormap, DbRange, COLORMAP_HELP_TEXT};
+pub use data::{AsyncDspData, AsyncDspDataState, CancelToken, DspData, DspErr, ProgressReporter};
+pub use frequency_scale::FrequencyScale;
+pub use loudness::{Loudness, LoudnessParameters};
+pub use normalization::compute_norm;
+pub use overview::Overview;
+pub use silence::{Silence, SilenceParameters, SilentRegion};
 pub use spectrogram::{Spectrogram, SpectrogramParameters};
+pub use statistics::{Statistics, StatisticsParameters};
 pub use time_window::{SidePaddingType, WindowType, PADDING_HELP_TEXT};
+pub use true_peak::{TruePeak, TruePeakLocation, TruePeakParameters};
 pub use waveform::{Waveform, WaveformParameters, WaveformPoint};
@@ -1,6 +1,12 @@
 use crate::sndfile::SndFile;
-use rayon::prelude::*;
 use sndfile::SndFileIO;
+use std::convert::TryFrom;
+use std::io::SeekFrom;
+
+use super::{CancelToken, DspErr, ProgressReporter};
+
+/// Frames read at once
+const READ_BLOCK_SIZE: usize = 4096;
 
 #[inline(always)]
 fn clamp(val: &i32) -> i32 {
@@ -11,13 +17,95 @@ fn clamp(val: &i32) -> i32 {
     }
 }
 
-pub fn compute_norm(sndfile: &mut SndFile) -> f64 {
-    let data: Vec<i32> = sndfile.read_all_to_vec().unwrap();
+/// Normalization factor of a signal of a given absolute peak. Silence is left
+/// as is, without dividing by zero
+pub fn peak_to_norm(peak: i32) -> f64 {
+    if peak <= 0i32 {
+        return f64::EPSILON;
+    }
+    peak as f64 / i32::MAX as f64
+}
+
+/// Peak of the whole file relative to full scale, read block by block. The
+/// file is left positioned at its start
+pub fn compute_norm(
+    sndfile: &mut SndFile,
+    cancel: &CancelToken,
+    progress: &mut ProgressReporter,
+) -> Result<f64, DspErr> {
+    let frames = match sndfile.len() {
+        Ok(frames) => usize::try_from(frames).unwrap(),
+        Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+    };
+    if sndfile.seek(SeekFrom::Start(0)).is_err() {
+        return Err(DspErr::new("Failed to seek 0"));
+    }
+    let channels = sndfile.get_channels();
 
-    let max = data.par_iter().map(|val| clamp(val).abs()).max().unwrap();
+    let mut max = 0i32;
+    let mut block_data: Vec<i32> = vec![0; READ_BLOCK_SIZE * channels];
+    let mut read_frames = 0usize;
+    while read_frames < frames {
+        if cancel.is_cancelled() {
+            return Err(DspErr::cancelled());
+        }
 
-    if max <= 0i32 {
-        return f64::EPSILON;
+        let nb_frames = match sndfile.read_to_slice(block_data.as_mut_slice()) {
+            Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+            Ok(nb_frames) => nb_frames,
+        };
+        max = block_data[..nb_frames * channels]
+            .iter()
+            .map(|val| clamp(val).abs())
+            .fold(max, i32::max);
+
+        read_frames += nb_frames;
+        progress.report(read_frames as f64 / frames as f64);
+    }
+
+    if sndfile.seek(SeekFrom::Start(0)).is_err() {
+        return Err(DspErr::new("Failed to seek 0"));
+    }
+
+    Ok(peak_to_norm(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_norm;
+    use crate::dsp::{CancelToken, ProgressReporter};
+    use sndfile::SndFileIO;
+    use std::path::{Path, PathBuf};
+
+    fn get_test_files_location() -> PathBuf {
+        Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+    }
+
+    #[test]
+    fn norm() {
+        let open = || {
+            sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(get_test_files_location().join("rock_1s.wav"))
+                .unwrap()
+        };
+        let samples: Vec<i32> = open().read_all_to_vec().unwrap();
+        let peak = samples
+            .iter()
+            .map(|sample| sample.saturating_abs())
+            .max()
+            .unwrap();
+
+        let mut reported = vec![];
+        let mut progress = ProgressReporter::new(|fraction| reported.push(fraction));
+        let norm = compute_norm(&mut open(), &CancelToken::default(), &mut progress).unwrap();
+        drop(progress);
+        assert_eq!(norm, peak as f64 / i32::MAX as f64);
+        assert_eq!(reported.last(), Some(&1f64));
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        assert!(compute_norm(&mut open(), &cancel, &mut ProgressReporter::default()).is_err());
     }
-    max as f64 / i32::MAX as f64
 }
@@ -0,0 +1,161 @@
+extern crate sndfile;
+use sndfile::SndFileIO;
+
+use crate::sndfile::SndFile;
+use std::io::SeekFrom;
+
+use std::convert::TryFrom;
+
+use super::normalization::peak_to_norm;
+use super::statistics::LevelsTracker;
+use super::true_peak::TruePeakTracker;
+use super::{
+    CancelToken, Clipping, ClippingParameters, DspErr, ProgressReporter, Silence,
+    SilenceParameters, Statistics, TruePeak,
+};
+
+/// Frames read at once
+const READ_BLOCK_SIZE: usize = 4096;
+/// Scale of the samples read as floats by libsndfile, to feed the true-peak
+/// measurement the same values from integers
+const FLOAT_SCALE: f64 = 2147483648f64;
+
+/// Every measure of a whole file, read a single time, on its content as is
+pub struct Overview {
+    /// Peak of the whole file relative to full scale, as `compute_norm`
+    /// finds it
+    pub norm: f64,
+    pub levels: Statistics,
+    pub true_peak: TruePeak,
+    pub clipping: Clipping,
+    pub silence: Silence,
+}
+
+impl Overview {
+    pub fn new(
+        mut sndfile: SndFile,
+        clipping: ClippingParameters,
+        silence: SilenceParameters,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<Overview, DspErr> {
+        let frames = match sndfile.len() {
+            Ok(frames) => usize::try_from(frames).unwrap(),
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+        };
+        if sndfile.seek(SeekFrom::Start(0)).is_err() {
+            return Err(DspErr::new("Failed to seek 0"));
+        }
+        let channels = sndfile.get_channels();
+        let samplerate = sndfile.get_samplerate();
+
+        let mut levels = LevelsTracker::new(channels, clipping.threshold);
+        let mut true_peak = TruePeakTracker::new(channels, samplerate);
+        let mut clip_detector = clipping.detector(channels);
+        let mut silence_detector = silence.detector(channels, samplerate);
+        let mut block_data: Vec<i32> = vec![0; READ_BLOCK_SIZE * channels];
+        let mut float_frame = vec![0f64; channels];
+        let mut read_frames = 0usize;
+        while read_frames < frames {
+            if cancel.is_cancelled() {
+                return Err(DspErr::cancelled());
+            }
+
+            let nb_frames = match sndfile.read_to_slice(block_data.as_mut_slice()) {
+                Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+                Ok(nb_frames) => nb_frames,
+            };
+            for frame in block_data[..nb_frames * channels].chunks_exact(channels) {
+                levels.push(frame);
+                clip_detector.push(frame);
+                silence_detector.push(frame);
+                for (float, sample) in float_frame.iter_mut().zip(frame) {
+                    *float = *sample as f64 / FLOAT_SCALE;
+                }
+                true_peak.push(&float_frame);
+            }
+
+            read_frames += nb_frames;
+            progress.report(read_frames as f64 / frames as f64);
+        }
+
+        Ok(Overview {
+            norm: peak_to_norm(levels.peak()),
+            levels: levels.finish(),
+            true_peak: true_peak.finish(),
+            clipping: Clipping::from_runs(channels, &clip_detector.finish()),
+            silence: Silence::from_runs(frames, &silence_detector.finish()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Overview;
+    use crate::dsp::{
+        CancelToken, Clipping, ClippingParameters, DspData, ProgressReporter, Silence,
+        SilenceParameters, Statistics, StatisticsParameters, TruePeak, TruePeakParameters,
+    };
+    use std::path::{Path, PathBuf};
+
+    fn open() -> sndfile::SndFile {
+        let path: PathBuf = Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+            .join("rock_1s.wav");
+        sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(path)
+            .unwrap()
+    }
+
+    #[test]
+    fn matches_separate_analyses() {
+        let clipping = ClippingParameters {
+            threshold: -6f64,
+            min_run: 2,
+        };
+        let silence = SilenceParameters {
+            threshold: -30f64,
+            min_duration: 0.001,
+        };
+        let overview = Overview::new(
+            open(),
+            clipping,
+            silence,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        let cancel = CancelToken::default();
+        let mut progress = ProgressReporter::default();
+        let levels = Statistics::new(
+            open(),
+            StatisticsParameters {
+                start: 0,
+                end: usize::MAX,
+                clip_threshold: clipping.threshold,
+            },
+            None,
+            &cancel,
+            &mut progress,
+        )
+        .unwrap();
+        let true_peak =
+            TruePeak::new(open(), TruePeakParameters, None, &cancel, &mut progress).unwrap();
+        let separate_clipping =
+            Clipping::new(open(), clipping, None, &cancel, &mut progress).unwrap();
+        let separate_silence = Silence::new(open(), silence, None, &cancel, &mut progress).unwrap();
+
+        for ch_idx in 0..open().get_channels() {
+            assert_eq!(overview.levels.channel(ch_idx), levels.channel(ch_idx));
+            assert_eq!(
+                overview.true_peak.channel(ch_idx),
+                true_peak.channel(ch_idx)
+            );
+        }
+        assert_eq!(overview.true_peak.locations(), true_peak.locations());
+        assert_eq!(overview.clipping.events(), separate_clipping.events());
+        assert_eq!(overview.silence.regions(), separate_silence.regions());
+    }
+}
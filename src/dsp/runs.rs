@@ -0,0 +1,146 @@
+extern crate sndfile;
+use sndfile::SndFileIO;
+
+use crate::sndfile::SndFile;
+use std::io::SeekFrom;
+
+use std::convert::TryFrom;
+
+use super::{CancelToken, DspErr, ProgressReporter};
+
+/// Frames read at once
+const READ_BLOCK_SIZE: usize = 4096;
+
+/// Run of consecutive samples of a channel matching a condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Run {
+    pub channel: usize,
+    /// First frame of the run
+    pub start: usize,
+    /// Frame after the last one of the run
+    pub end: usize,
+}
+
+/// Find the runs of samples of each channel matching a condition, fed frame
+/// by frame
+pub struct RunDetector<F: Fn(i32) -> bool> {
+    matches: F,
+    min_length: usize,
+    frames: usize,
+    // Start of the run in progress on each channel
+    current: Vec<Option<usize>>,
+    runs: Vec<Run>,
+}
+
+impl<F: Fn(i32) -> bool> RunDetector<F> {
+    /// Shorter runs than `min_length` are dropped
+    pub fn new(channels: usize, min_length: usize, matches: F) -> Self {
+        RunDetector {
+            matches,
+            min_length: usize::max(min_length, 1),
+            frames: 0,
+            current: vec![None; channels],
+            runs: vec![],
+        }
+    }
+
+    fn end_run(&mut self, channel: usize, start: usize, end: usize) {
+        if end - start >= self.min_length {
+            self.runs.push(Run {
+                channel,
+                start,
+                end,
+            });
+        }
+    }
+
+    /// Check the next frame, with one sample per channel
+    pub fn push(&mut self, frame: &[i32]) {
+        let frame_idx = self.frames;
+        for (channel, sample) in frame.iter().enumerate() {
+            match ((self.matches)(*sample), self.current[channel]) {
+                (true, None) => self.current[channel] = Some(frame_idx),
+                (false, Some(start)) => {
+                    self.end_run(channel, start, frame_idx);
+                    self.current[channel] = None;
+                }
+                _ => {}
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Runs of all the channels sorted by start, the ones still in progress
+    /// ending with the last frame
+    pub fn finish(mut self) -> Vec<Run> {
+        for channel in 0..self.current.len() {
+            if let Some(start) = self.current[channel] {
+                self.end_run(channel, start, self.frames);
+            }
+        }
+
+        self.runs.sort_by_key(|run| (run.start, run.channel));
+        self.runs
+    }
+}
+
+/// Feed a whole file to a run detector, with as many channels as the file
+pub fn scan_runs<F: Fn(i32) -> bool>(
+    sndfile: &mut SndFile,
+    mut detector: RunDetector<F>,
+    cancel: &CancelToken,
+    progress: &mut ProgressReporter,
+) -> Result<Vec<Run>, DspErr> {
+    let frames = match sndfile.len() {
+        Ok(frames) => usize::try_from(frames).unwrap(),
+        Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+    };
+    if sndfile.seek(SeekFrom::Start(0)).is_err() {
+        return Err(DspErr::new("Failed to seek 0"));
+    }
+    let channels = sndfile.get_channels();
+
+    let mut block_data: Vec<i32> = vec![0; READ_BLOCK_SIZE * channels];
+    let mut read_frames = 0usize;
+    while read_frames < frames {
+        if cancel.is_cancelled() {
+            return Err(DspErr::cancelled());
+        }
+
+        let nb_frames = match sndfile.read_to_slice(block_data.as_mut_slice()) {
+            Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+            Ok(nb_frames) => nb_frames,
+        };
+        block_data[..nb_frames * channels]
+            .chunks_exact(channels)
+            .for_each(|frame| detector.push(frame));
+
+        read_frames += nb_frames;
+        progress.report(read_frames as f64 / frames as f64);
+    }
+
+    Ok(detector.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Run, RunDetector};
+
+    #[test]
+    fn detect() {
+        let mut detector = RunDetector::new(2, 2, |sample| sample > 0);
+        let frames = [[1, 0], [1, 1], [0, 1], [1, 0], [1, 1], [1, 0]];
+        frames.iter().for_each(|frame| detector.push(frame));
+
+        let run = |channel, start, end| Run {
+            channel,
+            start,
+            end,
+        };
+        // The single sample run of the right channel is dropped
+        assert_eq!(
+            detector.finish(),
+            vec![run(0, 0, 2), run(1, 1, 3), run(0, 3, 6)]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+extern crate sndfile;
+
+use crate::sndfile::SndFile;
+use std::io::{self, Read, Write};
+
+use std::convert::TryFrom;
+
+use super::cache::{read_len, read_u64, write_u64};
+use super::runs::{scan_runs, Run, RunDetector};
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
+use crate::utils::from_dbfs;
+
+/// Where a silent region stands in its channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SilenceKind {
+    /// From the start of the file, or the whole file
+    Leading,
+    /// Up to the end of the file
+    Trailing,
+    /// Within the content
+    Dropout,
+}
+
+impl SilenceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SilenceKind::Leading => "leading",
+            SilenceKind::Trailing => "trailing",
+            SilenceKind::Dropout => "dropout",
+        }
+    }
+
+    fn to_u64(self) -> u64 {
+        match self {
+            SilenceKind::Leading => 0,
+            SilenceKind::Trailing => 1,
+            SilenceKind::Dropout => 2,
+        }
+    }
+
+    fn from_u64(value: u64) -> io::Result<Self> {
+        match value {
+            0 => Ok(SilenceKind::Leading),
+            1 => Ok(SilenceKind::Trailing),
+            2 => Ok(SilenceKind::Dropout),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid silence kind",
+            )),
+        }
+    }
+}
+
+/// Run of consecutive samples of a channel below the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SilentRegion {
+    pub channel: usize,
+    pub kind: SilenceKind,
+    /// First frame of the region
+    pub start: usize,
+    /// Number of samples of the region
+    pub length: usize,
+}
+
+impl SilentRegion {
+    /// Frame after the last one of the region
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+}
+
+/// What counts as silence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceParameters {
+    /// Level every sample has to stay under, in dBFS
+    pub threshold: f64,
+    /// Shortest duration of a region, in seconds
+    pub min_duration: f64,
+}
+
+impl SilenceParameters {
+    /// Detector of the silent regions of a signal
+    pub(super) fn detector(
+        &self,
+        channels: usize,
+        samplerate: usize,
+    ) -> RunDetector<impl Fn(i32) -> bool> {
+        let level = from_dbfs(self.threshold) * i32::MAX as f64;
+        let min_length = (self.min_duration * samplerate as f64).ceil() as usize;
+        RunDetector::new(channels, min_length, move |sample| {
+            (sample as f64).abs() < level
+        })
+    }
+}
+
+/// Silent regions of every channel, measured on the content of the file as
+/// is, even when normalized for display
+pub struct Silence {
+    frames: usize,
+    // Sorted by start
+    regions: Vec<SilentRegion>,
+}
+
+impl DspData<SilenceParameters> for Silence {
+    fn new(
+        mut sndfile: SndFile,
+        parameters: SilenceParameters,
+        _: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<Silence, DspErr> {
+        let frames = match sndfile.len() {
+            Ok(frames) => usize::try_from(frames).unwrap(),
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+        };
+        let detector = parameters.detector(sndfile.get_channels(), sndfile.get_samplerate());
+        let runs = scan_runs(&mut sndfile, detector, cancel, progress)?;
+
+        Ok(Silence::from_runs(frames, &runs))
+    }
+
+    fn cache_key(parameters: &SilenceParameters) -> String {
+        format!(
+            "silence {} {}",
+            parameters.threshold, parameters.min_duration
+        )
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.frames as u64)?;
+        write_u64(writer, self.regions.len() as u64)?;
+        for region in self.regions.iter() {
+            write_u64(writer, region.channel as u64)?;
+            write_u64(writer, region.kind.to_u64())?;
+            write_u64(writer, region.start as u64)?;
+            write_u64(writer, region.length as u64)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_cache(mut sndfile: SndFile, reader: &mut dyn Read) -> io::Result<Silence> {
+        let frames = read_len(reader)?;
+        let channels = sndfile.get_channels();
+        let file_frames = sndfile.len().ok().and_then(|len| usize::try_from(len).ok());
+        if file_frames != Some(frames) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Cached silences don't match the file",
+            ));
+        }
+
+        let len = read_len(reader)?;
+        let regions = (0..len)
+            .map(|_| {
+                let region = SilentRegion {
+                    channel: read_u64(reader)? as usize,
+                    kind: SilenceKind::from_u64(read_u64(reader)?)?,
+                    start: read_u64(reader)? as usize,
+                    length: read_u64(reader)? as usize,
+                };
+                let end = region.start.checked_add(region.length);
+                if region.channel >= channels || end.is_none_or(|end| end > frames) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid region"));
+                }
+                Ok(region)
+            })
+            .collect::<io::Result<Vec<SilentRegion>>>()?;
+
+        Ok(Silence { frames, regions })
+    }
+}
+
+impl Silence {
+    /// Regions of runs of silent samples of a file of `frames` frames, sorted
+    /// by start
+    pub(super) fn from_runs(frames: usize, runs: &[Run]) -> Silence {
+        let regions = runs
+            .iter()
+            .map(|run| {
+                let kind = if run.start == 0 {
+                    SilenceKind::Leading
+                } else if run.end == frames {
+                    SilenceKind::Trailing
+                } else {
+                    SilenceKind::Dropout
+                };
+                SilentRegion {
+                    channel: run.channel,
+                    kind,
+                    start: run.start,
+                    length: run.end - run.start,
+                }
+            })
+            .collect();
+
+        Silence { frames, regions }
+    }
+
+    /// Regions of all the channels, sorted by start
+    pub fn regions(&self) -> &[SilentRegion] {
+        &self.regions
+    }
+
+    /// Number of frames of the file, to place the regions in time
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Silence, SilenceKind, SilenceParameters, SilentRegion};
+    use crate::dsp::test_files::{compute, open, write_wav};
+    use crate::dsp::DspData;
+
+    fn region(channel: usize, kind: SilenceKind, start: usize, length: usize) -> SilentRegion {
+        SilentRegion {
+            channel,
+            kind,
+            start,
+            length,
+        }
+    }
+
+    #[test]
+    fn regions() {
+        // A tone on the left channel with 100ms of silence
+        // at the start, 50ms and 200ms gaps, and 150ms of silence at the end.
+        // The right channel is silent
+        let mut samples = vec![0i16; 2 * 1000];
+        for frame in (100..300).chain(350..500).chain(700..850) {
+            samples[2 * frame] = if frame % 2 == 0 { 8000 } else { -8000 };
+        }
+        let path = write_wav("silence_regions", 2, 1000, &samples);
+
+        let silence: Silence = compute(
+            &path,
+            SilenceParameters {
+                threshold: -60f64,
+                min_duration: 0.1,
+            },
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(silence.frames(), 1000);
+        assert_eq!(
+            silence.regions(),
+            &[
+                region(0, SilenceKind::Leading, 0, 100),
+                region(1, SilenceKind::Leading, 0, 1000),
+                region(0, SilenceKind::Dropout, 500, 200),
+                region(0, SilenceKind::Trailing, 850, 150),
+            ]
+        );
+    }
+
+    #[test]
+    fn threshold() {
+        // A tone with 100ms at -70 dBFS in the middle
+        let mut samples = vec![8000i16; 500];
+        for sample in samples[200..300].iter_mut() {
+            *sample = 10;
+        }
+        let path = write_wav("silence_threshold", 1, 1000, &samples);
+
+        let parameters = |threshold| SilenceParameters {
+            threshold,
+            min_duration: 0.05,
+        };
+        let strict: Silence = compute(&path, parameters(-90f64));
+        let loose: Silence = compute(&path, parameters(-60f64));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(strict.regions().is_empty());
+        assert_eq!(
+            loose.regions(),
+            &[region(0, SilenceKind::Dropout, 200, 100)]
+        );
+    }
+
+    #[test]
+    fn cache_mismatch() {
+        let mut samples = vec![8000i16; 2 * 500];
+        for sample in samples[400..600].iter_mut() {
+            *sample = 0;
+        }
+        let path = write_wav("silence_cache", 2, 1000, &samples);
+        let silence: Silence = compute(
+            &path,
+            SilenceParameters {
+                threshold: -60f64,
+                min_duration: 0.05,
+            },
+        );
+
+        let mut bytes = vec![];
+        silence.write_cache(&mut bytes).unwrap();
+        let cached = Silence::read_cache(open(&path), &mut bytes.as_slice());
+        // Channel, then end of the first region, out of the file
+        let mut bad_channel = bytes.clone();
+        bad_channel[16..24].copy_from_slice(&2u64.to_le_bytes());
+        let mut bad_end = bytes.clone();
+        bad_end[40..48].copy_from_slice(&501u64.to_le_bytes());
+        // Frames of another file
+        let mut bad_frames = bytes.clone();
+        bad_frames[0..8].copy_from_slice(&1000u64.to_le_bytes());
+        let tampered: Vec<bool> = [bad_channel, bad_end, bad_frames]
+            .iter()
+            .map(|bytes| Silence::read_cache(open(&path), &mut bytes.as_slice()).is_err())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cached.unwrap().regions(), silence.regions());
+        assert_eq!(tampered, [true; 3]);
+    }
+}
@@ -1,56 +1,111 @@
 extern crate sndfile;
 use crate::sndfile::SndFile;
 use realfft::RealFftPlanner;
+use std::io::{self, Read, Write};
 
+use super::cache::{read_len, read_u32_vec, read_u64, write_u32_slice, write_u64};
+use super::frequency_scale::{remap_bins, BinMap, FrequencyScale};
 use super::time_window::{SidePaddingType, TimeWindowBatcher, WindowType};
-use super::{DspData, DspErr};
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
 use crate::utils::Zoom;
 
-use colorgrad::{inferno, Gradient};
-
-#[inline(always)]
-fn db_to_u8x4(db: f64, threshold: f64, gradient: &Gradient) -> [u8; 4] {
-    let grad_pos = if db > 0f64 {
-        1f64
-    } else if db < threshold {
-        0f64
-    } else {
-        (db - threshold) / -threshold
-    };
-
-    gradient.at(grad_pos).to_rgba8()
-}
-
 /// Ordered vertically and by channel. Each channel vector contains contiguous
 /// frequency bins
 pub struct Spectrogram {
     num_bands: usize,
     num_bins: usize,
-    // Ordered by [channel]
-    color_frames: Vec<Vec<u8>>,
+    samplerate: usize,
+    frames: u64,
+    // Ordered by [channel], dB magnitudes stored as f32 bits so they can be
+    // resized without being copied
+    magnitudes: Vec<Vec<u32>>,
+    // Latest bin map of `scaled_data`, along with the scale and the bin range
+    // it was computed for
+    bin_map: Option<((FrequencyScale, usize, usize), BinMap)>,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct SpectrogramParameters {
     pub window_size: usize,
     pub overlap_rate: f64,
     pub db_threshold: f64,
     pub window_type: WindowType,
     pub side_padding_type: SidePaddingType,
+    pub frequency_scale: FrequencyScale,
+}
+
+/// Window sizes to cycle through at runtime
+const WINDOW_SIZES: &[usize] = &[256, 512, 1024, 2048, 4096, 8192, 16384];
+/// Overlap rates to cycle through at runtime
+const OVERLAP_RATES: &[f64] = &[0.25, 0.5, 0.75, 0.875];
+
+impl SpectrogramParameters {
+    /// Switch to the next bigger window size, wrapping to the smallest one
+    pub fn next_window_size(&mut self) {
+        self.window_size = match WINDOW_SIZES.iter().find(|size| **size > self.window_size) {
+            Some(size) => *size,
+            None => WINDOW_SIZES[0],
+        };
+    }
+
+    /// Switch to the next smaller window size, wrapping to the biggest one
+    pub fn previous_window_size(&mut self) {
+        self.window_size = match WINDOW_SIZES
+            .iter()
+            .rev()
+            .find(|size| **size < self.window_size)
+        {
+            Some(size) => *size,
+            None => WINDOW_SIZES[WINDOW_SIZES.len() - 1],
+        };
+    }
+
+    /// Switch to the next bigger overlap rate, wrapping to the smallest one
+    pub fn next_overlap_rate(&mut self) {
+        self.overlap_rate = match OVERLAP_RATES.iter().find(|rate| **rate > self.overlap_rate) {
+            Some(rate) => *rate,
+            None => OVERLAP_RATES[0],
+        };
+    }
+
+    /// Number of time bands a file of `frames` samples is split into
+    pub fn num_bands(&self, frames: u64) -> usize {
+        (frames / (self.window_size as f64 * (1f64 - self.overlap_rate)) as u64) as usize
+    }
+
+    /// Short human readable description of the FFT settings
+    pub fn summary(&self) -> String {
+        format!(
+            "window {} {}, overlap {}, {} padding",
+            self.window_size,
+            self.window_type.name(),
+            self.overlap_rate,
+            self.side_padding_type.name()
+        )
+    }
 }
 
 impl DspData<SpectrogramParameters> for Spectrogram {
     fn new(
-        sndfile: SndFile,
+        mut sndfile: SndFile,
         parameters: SpectrogramParameters,
         norm: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
     ) -> Result<Spectrogram, DspErr> {
         let channels = sndfile.get_channels();
+        let samplerate = sndfile.get_samplerate();
+        let frames = match sndfile.len() {
+            Ok(frames) => frames,
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+        };
         let mut window_batcher = match TimeWindowBatcher::new(
             sndfile,
             parameters.window_size,
             parameters.overlap_rate,
             parameters.window_type,
             parameters.side_padding_type,
+            cancel.clone(),
         ) {
             Ok(batcher) => batcher,
             Err(err) => return Err(err),
@@ -61,9 +116,8 @@ impl DspData<SpectrogramParameters> for Spectrogram {
         let num_bins = parameters.window_size / 2;
         let num_bands = window_batcher.get_num_bands();
 
-        // Allocate the memory for the u8 spectrograms
-        let mut spectrograms_u8x4 = vec![vec![0u8; num_bands * num_bins * 3]; channels];
-        let gradient = inferno();
+        // Allocate the memory for the magnitudes
+        let mut magnitudes = vec![vec![0u32; num_bands * num_bins]; channels];
 
         // Plan the fft
         let mut planner = RealFftPlanner::<f64>::new();
@@ -83,77 +137,217 @@ impl DspData<SpectrogramParameters> for Spectrogram {
                 r2c.process_with_scratch(mono_batch, &mut spectrum, &mut scratch)
                     .unwrap();
 
-                let u8x3_spectrogram_slice = &mut spectrograms_u8x4[ch_idx]
-                    [batch_idx * (num_bins) * 3..(batch_idx + 1) * (num_bins) * 3];
-
-                // Compute the magnitude and reduce it to u8
-                match norm {
-                    Some(fnorm) => {
-                        let fnorm_inv = 1f64 / fnorm;
-                        spectrum[1..num_bins + 1]
-                            .iter()
-                            .enumerate()
-                            .for_each(|(fidx, value)| {
-                                let bin_amp =
-                                    (value * correction_factor * fnorm_inv / fft_len).norm_sqr();
-                                let db_bin_amp = 10f64 * f64::log10(bin_amp + f64::EPSILON);
-                                let color =
-                                    db_to_u8x4(db_bin_amp, parameters.db_threshold, &gradient);
-                                u8x3_spectrogram_slice[fidx * 3] = color[0];
-                                u8x3_spectrogram_slice[fidx * 3 + 1] = color[1];
-                                u8x3_spectrogram_slice[fidx * 3 + 2] = color[2];
-                            });
-                    }
-                    None => {
-                        spectrum[1..num_bins + 1]
-                            .iter()
-                            .enumerate()
-                            .for_each(|(fidx, value)| {
-                                let bin_amp = (value * correction_factor / fft_len).norm_sqr();
-                                let db_bin_amp = 10f64 * f64::log10(bin_amp + f64::EPSILON);
-                                let color =
-                                    db_to_u8x4(db_bin_amp, parameters.db_threshold, &gradient);
-                                u8x3_spectrogram_slice[fidx * 3] = color[0];
-                                u8x3_spectrogram_slice[fidx * 3 + 1] = color[1];
-                                u8x3_spectrogram_slice[fidx * 3 + 2] = color[2];
-                            });
-                    }
-                }
+                let magnitudes_slice =
+                    &mut magnitudes[ch_idx][batch_idx * num_bins..(batch_idx + 1) * num_bins];
+
+                // Compute the magnitude in dB
+                let fnorm_inv = match norm {
+                    Some(fnorm) => 1f64 / fnorm,
+                    None => 1f64,
+                };
+                spectrum[1..num_bins + 1]
+                    .iter()
+                    .zip(magnitudes_slice.iter_mut())
+                    .for_each(|(value, magnitude)| {
+                        let bin_amp = (value * correction_factor * fnorm_inv / fft_len).norm_sqr();
+                        let db_bin_amp = 10f64 * f64::log10(bin_amp + f64::EPSILON);
+                        *magnitude = (db_bin_amp as f32).to_bits();
+                    });
             }
 
             batch_idx += 1;
+            progress.report(window_batcher.progress());
+        }
+
+        // The batcher stops early when cancelled
+        if cancel.is_cancelled() {
+            return Err(DspErr::cancelled());
+        }
+
+        Ok(Spectrogram {
+            num_bands,
+            num_bins,
+            samplerate,
+            frames,
+            magnitudes,
+            bin_map: None,
+        })
+    }
+
+    fn cache_key(parameters: &SpectrogramParameters) -> String {
+        // The frequency scale is only applied when rendering
+        format!(
+            "spectrogram {} {} {} {} {}",
+            parameters.window_size,
+            parameters.overlap_rate,
+            parameters.db_threshold,
+            parameters.window_type.name(),
+            parameters.side_padding_type.name()
+        )
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.num_bands as u64)?;
+        write_u64(writer, self.num_bins as u64)?;
+        write_u64(writer, self.samplerate as u64)?;
+        write_u64(writer, self.frames)?;
+
+        write_u64(writer, self.magnitudes.len() as u64)?;
+        self.magnitudes
+            .iter()
+            .try_for_each(|channel| write_u32_slice(writer, channel))
+    }
+
+    fn read_cache(_: SndFile, reader: &mut dyn Read) -> io::Result<Spectrogram> {
+        let num_bands = read_len(reader)?;
+        let num_bins = read_len(reader)?;
+        let samplerate = read_len(reader)?;
+        let frames = read_u64(reader)?;
+
+        let channels = read_len(reader)?;
+        let magnitudes = (0..channels)
+            .map(|_| read_u32_vec(reader))
+            .collect::<io::Result<Vec<Vec<u32>>>>()?;
+
+        if magnitudes
+            .iter()
+            .any(|channel| channel.len() != num_bands * num_bins)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Cached spectrogram is truncated",
+            ));
         }
 
         Ok(Spectrogram {
             num_bands,
             num_bins,
-            // frames: spectrograms_u8,
-            color_frames: spectrograms_u8x4,
+            samplerate,
+            frames,
+            magnitudes,
+            bin_map: None,
         })
     }
 }
 
 impl Spectrogram {
-    pub fn data(&mut self, channel: usize, zoom: &Zoom) -> (&mut [u8], usize) {
-        let start = (self.num_bands as f64 * zoom.start()) as usize;
-        let end = (self.num_bands as f64 * (zoom.start() + zoom.length())) as usize;
+    /// dB magnitudes of a channel within the zoom window, as f32 bits, with the
+    /// number of bands they span
+    pub fn data(&mut self, channel: usize, zoom: &Zoom) -> (&mut [u32], usize) {
+        let (start, end) = self.band_range(zoom);
 
         (
-            &mut self.color_frames[channel][start * self.num_bins * 3..end * self.num_bins * 3],
+            &mut self.magnitudes[channel][start * self.num_bins..end * self.num_bins],
             end - start,
         )
     }
 
+    /// Range of bands within the zoom window, as [start; end[
+    fn band_range(&self, zoom: &Zoom) -> (usize, usize) {
+        let start = (self.num_bands as f64 * zoom.start()) as usize;
+        let end = (self.num_bands as f64 * (zoom.start() + zoom.length())) as usize;
+
+        (start, end)
+    }
+
+    /// Range of bins within the frequency zoom window, as [first; last[, with
+    /// at least two bins
+    fn bin_range(&self, frequency_zoom: &Zoom) -> (usize, usize) {
+        let num_bins = self.num_bins as f64;
+        let first = ((num_bins * frequency_zoom.start()) as usize).min(self.num_bins - 2);
+        let last = (num_bins * (frequency_zoom.start() + frequency_zoom.length())).ceil() as usize;
+
+        (first, last.clamp(first + 2, self.num_bins))
+    }
+
+    /// Frequencies of the lowest and highest bins within the frequency zoom
+    /// window
+    pub fn frequency_range(&self, frequency_zoom: &Zoom) -> (f64, f64) {
+        let (first, last) = self.bin_range(frequency_zoom);
+        let bin_width = self.min_frequency();
+
+        ((first + 1) as f64 * bin_width, last as f64 * bin_width)
+    }
+
+    /// Same as `data`, restricted to the bins within the frequency zoom window
+    /// and rearranged along the given frequency scale. Also returns the number
+    /// of bins per band. `buffer` holds the rearranged data when needed
+    pub fn scaled_data<'a>(
+        &'a mut self,
+        channel: usize,
+        zoom: &Zoom,
+        frequency_zoom: &Zoom,
+        scale: FrequencyScale,
+        buffer: &'a mut Vec<u32>,
+    ) -> (&'a mut [u32], usize, usize) {
+        let num_bins = self.num_bins;
+        let nyquist = self.nyquist();
+        let (first, last) = self.bin_range(frequency_zoom);
+        let (min_hz, max_hz) = self.frequency_range(frequency_zoom);
+
+        if scale == FrequencyScale::Linear && first == 0 && last == num_bins {
+            let (data, num_bands) = self.data(channel, zoom);
+            return (data, num_bands, num_bins);
+        }
+
+        // Only rebuilt when the scale or the frequency zoom change
+        let key = (scale, first, last);
+        if !matches!(&self.bin_map, Some((cached_key, _)) if *cached_key == key) {
+            let map = scale.bin_map(num_bins, nyquist, min_hz, max_hz, last - first);
+            self.bin_map = Some((key, map));
+        }
+
+        let (start, end) = self.band_range(zoom);
+        let data = &self.magnitudes[channel][start * num_bins..end * num_bins];
+        let map = &self.bin_map.as_ref().unwrap().1;
+        remap_bins(data, num_bins, map, buffer);
+        (buffer.as_mut_slice(), end - start, map.len())
+    }
+
+    /// dB magnitude of a channel at a relative position in time btw 0 and 1,
+    /// in the bin closest to the given frequency
+    pub fn magnitude_at(&self, channel: usize, position: f64, hz: f64) -> Option<f64> {
+        if self.num_bands == 0 {
+            return None;
+        }
+        let band = usize::min(
+            (self.num_bands as f64 * position) as usize,
+            self.num_bands - 1,
+        );
+        // The DC bin is dropped, the first bin stored is at `min_frequency`
+        let bin = ((hz / self.min_frequency()).round() as usize).clamp(1, self.num_bins) - 1;
+
+        Some(f32::from_bits(self.magnitudes[channel][band * self.num_bins + bin]) as f64)
+    }
+
     pub fn num_bins(&self) -> usize {
         self.num_bins
     }
+
+    pub fn nyquist(&self) -> f64 {
+        self.samplerate as f64 / 2f64
+    }
+
+    /// Frequency of the lowest bin stored, the DC bin being dropped
+    pub fn min_frequency(&self) -> f64 {
+        self.nyquist() / self.num_bins as f64
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    /// Duration of the analyzed content, in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames as f64 / self.samplerate as f64
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::dsp::{
-        AsyncDspData, AsyncDspDataState, DspData, SidePaddingType, Spectrogram,
-        SpectrogramParameters, WindowType,
+        AsyncDspData, AsyncDspDataState, CancelToken, DspData, FrequencyScale, ProgressReporter,
+        SidePaddingType, Spectrogram, SpectrogramParameters, WindowType,
     };
     use crate::Zoom;
     use sndfile;
@@ -196,13 +390,21 @@ mod tests {
                                     window_type: wtype,
                                     db_threshold: db_th,
                                     side_padding_type: padding_type,
+                                    frequency_scale: FrequencyScale::Linear,
                                 };
 
                                 let snd =
                                     sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
                                         .from_path(get_test_files_location().join("rock_1s.wav"))
                                         .unwrap();
-                                Spectrogram::new(snd, parameters, norm).unwrap();
+                                Spectrogram::new(
+                                    snd,
+                                    parameters,
+                                    norm,
+                                    &CancelToken::default(),
+                                    &mut ProgressReporter::default(),
+                                )
+                                .unwrap();
                             }
                         }
                     }
@@ -224,11 +426,12 @@ mod tests {
             window_type: WindowType::Hanning,
             db_threshold: DB_THRESHOLD,
             side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
         };
         let path = get_test_files_location().join("rock_1s.wav");
 
         let mut async_data: AsyncDspData<Spectrogram, SpectrogramParameters> =
-            AsyncDspData::new(&path, parameters, false);
+            AsyncDspData::new(&path, parameters, false, None);
         let mut attempts = 0;
 
         loop {
@@ -236,7 +439,7 @@ mod tests {
             async_data.update_status();
             let state = async_data.state();
 
-            assert_ne!(state, AsyncDspDataState::Failed);
+            assert!(!matches!(state, AsyncDspDataState::Failed(_)));
             assert!(attempts < 90);
 
             if state == AsyncDspDataState::Finished {
@@ -259,11 +462,12 @@ mod tests {
             window_type: WindowType::Hanning,
             db_threshold: DB_THRESHOLD,
             side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
         };
         let path = get_test_files_location().join("rock_1s.wav");
 
         let mut async_data: AsyncDspData<Spectrogram, SpectrogramParameters> =
-            AsyncDspData::new(&path, parameters, true);
+            AsyncDspData::new(&path, parameters, true, None);
         let mut attempts = 0;
 
         loop {
@@ -271,7 +475,7 @@ mod tests {
             async_data.update_status();
             let state = async_data.state();
 
-            assert_ne!(state, AsyncDspDataState::Failed);
+            assert!(!matches!(state, AsyncDspDataState::Failed(_)));
             assert!(attempts < 90);
 
             if state == AsyncDspDataState::Finished {
@@ -281,6 +485,96 @@ mod tests {
         }
     }
 
+    #[test]
+    fn async_build_failure() {
+        let sleep_interval = Duration::from_millis(100);
+
+        let parameters = SpectrogramParameters {
+            window_size: 4096,
+            overlap_rate: 0.25,
+            window_type: WindowType::Hanning,
+            db_threshold: 10f64,
+            side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
+        };
+        let path = get_test_files_location().join("rock_1s.wav");
+
+        let mut async_data: AsyncDspData<Spectrogram, SpectrogramParameters> =
+            AsyncDspData::new(&path, parameters, false, None);
+        let mut attempts = 0;
+
+        loop {
+            sleep(sleep_interval);
+            async_data.update_status();
+
+            if let AsyncDspDataState::Failed(err) = async_data.state() {
+                assert_eq!(err.to_string(), "dB threshold should be a negative value");
+                break;
+            }
+            assert_ne!(async_data.state(), AsyncDspDataState::Finished);
+            assert!(attempts < 100);
+            attempts += 1;
+        }
+        assert!(async_data.data().is_none());
+    }
+
+    #[test]
+    fn cancelled() {
+        let parameters = SpectrogramParameters {
+            window_size: 1024,
+            overlap_rate: 0.5,
+            window_type: WindowType::Hanning,
+            db_threshold: -130f64,
+            side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
+        };
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap();
+        let cancel = CancelToken::default();
+        cancel.cancel();
+
+        assert!(Spectrogram::new(
+            snd,
+            parameters,
+            None,
+            &cancel,
+            &mut ProgressReporter::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cycle_parameters() {
+        let mut parameters = SpectrogramParameters {
+            window_size: 4096,
+            overlap_rate: 0.75,
+            window_type: WindowType::Hanning,
+            db_threshold: -130f64,
+            side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
+        };
+
+        parameters.next_window_size();
+        assert_eq!(parameters.window_size, 8192);
+        parameters.previous_window_size();
+        parameters.previous_window_size();
+        assert_eq!(parameters.window_size, 2048);
+
+        // Sizes given on the command line are not necessarily in the cycle
+        parameters.window_size = 333;
+        parameters.next_window_size();
+        assert_eq!(parameters.window_size, 512);
+        parameters.window_size = 100;
+        parameters.previous_window_size();
+        assert_eq!(parameters.window_size, 16384);
+
+        parameters.next_overlap_rate();
+        assert_eq!(parameters.overlap_rate, 0.875);
+        parameters.next_overlap_rate();
+        assert_eq!(parameters.overlap_rate, 0.25);
+    }
+
     #[test]
     fn get_data() {
         const OVERLAP: f64 = 0.25f64;
@@ -293,13 +587,21 @@ mod tests {
             window_type: WindowType::Hanning,
             db_threshold: DB_THRESHOLD,
             side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
         };
 
         let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
             .from_path(get_test_files_location().join("rock_1s.wav"))
             .unwrap();
         let channels = snd.get_channels();
-        let mut spectro = Spectrogram::new(snd, parameters, None).unwrap();
+        let mut spectro = Spectrogram::new(
+            snd,
+            parameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
         let num_bins = spectro.num_bins();
 
         let mut zoom = Zoom::new(0.5f64).unwrap();
@@ -309,7 +611,7 @@ mod tests {
             let (no_zoom_data, num_bands) = spectro.data(ch_idx, &mut zoom);
 
             assert_ne!(no_zoom_data.len(), 0usize);
-            assert_eq!(num_bins * num_bands * 3, no_zoom_data.len());
+            assert_eq!(num_bins * num_bands, no_zoom_data.len());
         }
 
         // Zoom in and move
@@ -321,8 +623,44 @@ mod tests {
                 let (no_zoom_data, num_bands) = spectro.data(ch_idx, &mut zoom);
 
                 assert_ne!(no_zoom_data.len(), 0usize);
-                assert_eq!(num_bins * num_bands * 3, no_zoom_data.len());
+                assert_eq!(num_bins * num_bands, no_zoom_data.len());
             }
         }
+
+        // Other frequency scales
+        let mut buffer = vec![];
+        let no_frequency_zoom = Zoom::new(1f64).unwrap();
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+
+            for ch_idx in 0..channels {
+                let (scaled_data, num_bands, scaled_bins) =
+                    spectro.scaled_data(ch_idx, &zoom, &no_frequency_zoom, scale, &mut buffer);
+
+                assert_eq!(scaled_bins, num_bins);
+                assert_eq!(scaled_bins * num_bands, scaled_data.len());
+            }
+        }
+
+        // Frequency zoom
+        let mut frequency_zoom = Zoom::new(0.01f64).unwrap();
+        let (min_hz, max_hz) = spectro.frequency_range(&frequency_zoom);
+        assert_eq!(min_hz, spectro.min_frequency());
+        assert_eq!(max_hz, spectro.nyquist());
+        for _ in 0..20 {
+            frequency_zoom.zoom_in();
+            frequency_zoom.move_left();
+        }
+        let (min_hz, max_hz) = spectro.frequency_range(&frequency_zoom);
+        assert!(min_hz < max_hz && max_hz < spectro.nyquist() / 2f64);
+
+        for value in FrequencyScale::possible_values() {
+            let scale = FrequencyScale::parse(value).unwrap();
+            let (scaled_data, num_bands, scaled_bins) =
+                spectro.scaled_data(0, &zoom, &frequency_zoom, scale, &mut buffer);
+
+            assert!(scaled_bins < num_bins);
+            assert_eq!(scaled_bins * num_bands, scaled_data.len());
+        }
     }
 }
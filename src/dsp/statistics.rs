@@ -0,0 +1,332 @@
+extern crate sndfile;
+use sndfile::SndFileIO;
+
+use crate::sndfile::SndFile;
+use std::io::{self, Read, SeekFrom, Write};
+
+use std::convert::TryFrom;
+
+use crate::utils::{from_dbfs, to_dbfs};
+
+use super::cache::{read_f64, read_len, write_f64, write_u64};
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
+
+/// Frames read at once
+const READ_BLOCK_SIZE: usize = 4096;
+
+/// Levels of a channel over a range of frames, relative to full scale
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct ChannelStatistics {
+    pub peak: f64,
+    pub rms: f64,
+    pub dc_offset: f64,
+    /// Number of samples reaching the clipping threshold
+    pub clipped: usize,
+}
+
+impl ChannelStatistics {
+    /// Ratio of the peak to the RMS level, in dB. None for digital silence,
+    /// where it is undefined
+    pub fn crest_factor(&self) -> Option<f64> {
+        if self.rms > 0f64 {
+            Some(to_dbfs(self.peak / self.rms))
+        } else {
+            None
+        }
+    }
+}
+
+/// Range of frames to measure, as [start; end[
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StatisticsParameters {
+    pub start: usize,
+    pub end: usize,
+    /// Level a sample has to reach to count as clipped, in dBFS
+    pub clip_threshold: f64,
+}
+
+/// Measure the levels of each channel of a signal fed frame by frame
+pub struct LevelsTracker {
+    clip_level: f64,
+    frames: usize,
+    sums: Vec<f64>,
+    sums_sq: Vec<f64>,
+    peaks: Vec<i32>,
+    clipped: Vec<usize>,
+}
+
+impl LevelsTracker {
+    /// Samples reaching `clip_threshold`, in dBFS, count as clipped
+    pub fn new(channels: usize, clip_threshold: f64) -> Self {
+        LevelsTracker {
+            clip_level: from_dbfs(clip_threshold) * i32::MAX as f64,
+            frames: 0,
+            sums: vec![0f64; channels],
+            sums_sq: vec![0f64; channels],
+            peaks: vec![0i32; channels],
+            clipped: vec![0usize; channels],
+        }
+    }
+
+    /// Measure the next frame, with one sample per channel
+    pub fn push(&mut self, frame: &[i32]) {
+        for (ch_idx, value) in frame.iter().enumerate() {
+            let amplitude = value.saturating_abs();
+            self.sums[ch_idx] += *value as f64;
+            self.sums_sq[ch_idx] += (*value as f64) * (*value as f64);
+            self.peaks[ch_idx] = self.peaks[ch_idx].max(amplitude);
+            if amplitude as f64 >= self.clip_level {
+                self.clipped[ch_idx] += 1;
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Highest absolute sample of all the channels so far
+    pub fn peak(&self) -> i32 {
+        self.peaks.iter().copied().max().unwrap_or(0i32)
+    }
+
+    /// Levels of the whole signal fed
+    pub fn finish(self) -> Statistics {
+        let full_scale = i32::MAX as f64;
+        let count = self.frames.max(1) as f64;
+        let channels = (0..self.peaks.len())
+            .map(|ch_idx| ChannelStatistics {
+                peak: self.peaks[ch_idx] as f64 / full_scale,
+                rms: (self.sums_sq[ch_idx] / count).sqrt() / full_scale,
+                dc_offset: self.sums[ch_idx] / count / full_scale,
+                clipped: self.clipped[ch_idx],
+            })
+            .collect();
+
+        Statistics { channels }
+    }
+}
+
+/// Levels measured on each channel of a range of frames. They are measured on
+/// the content of the file as is, even when normalized for display
+pub struct Statistics {
+    channels: Vec<ChannelStatistics>,
+}
+
+impl DspData<StatisticsParameters> for Statistics {
+    fn new(
+        mut sndfile: SndFile,
+        parameters: StatisticsParameters,
+        _: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<Statistics, DspErr> {
+        let file_frames = match sndfile.len() {
+            Ok(frames) => usize::try_from(frames).unwrap(),
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+        };
+        let end = usize::min(parameters.end, file_frames);
+        let start = usize::min(parameters.start, end);
+        if sndfile.seek(SeekFrom::Start(start as u64)).is_err() {
+            return Err(DspErr::new("Failed to seek the start of the range"));
+        }
+        let channels = sndfile.get_channels();
+        let frames = end - start;
+
+        let mut tracker = LevelsTracker::new(channels, parameters.clip_threshold);
+        let mut block_data: Vec<i32> = vec![0; READ_BLOCK_SIZE * channels];
+        let mut read_frames = 0usize;
+
+        while read_frames < frames {
+            if cancel.is_cancelled() {
+                return Err(DspErr::cancelled());
+            }
+
+            let to_read = usize::min(READ_BLOCK_SIZE, frames - read_frames);
+            let nb_frames = match sndfile.read_to_slice(&mut block_data[..to_read * channels]) {
+                Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+                Ok(nb_frames) => nb_frames,
+            };
+            block_data[..nb_frames * channels]
+                .chunks_exact(channels)
+                .for_each(|frame| tracker.push(frame));
+
+            read_frames += nb_frames;
+            progress.report(read_frames as f64 / frames as f64);
+        }
+
+        Ok(tracker.finish())
+    }
+
+    fn cache_key(parameters: &StatisticsParameters) -> String {
+        format!(
+            "statistics {} {} {}",
+            parameters.start, parameters.end, parameters.clip_threshold
+        )
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.channels.len() as u64)?;
+        for channel in self.channels.iter() {
+            write_f64(writer, channel.peak)?;
+            write_f64(writer, channel.rms)?;
+            write_f64(writer, channel.dc_offset)?;
+            write_u64(writer, channel.clipped as u64)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_cache(sndfile: SndFile, reader: &mut dyn Read) -> io::Result<Statistics> {
+        let num_channels = read_len(reader)?;
+        let channels = (0..num_channels)
+            .map(|_| {
+                Ok(ChannelStatistics {
+                    peak: read_f64(reader)?,
+                    rms: read_f64(reader)?,
+                    dc_offset: read_f64(reader)?,
+                    clipped: read_len(reader)?,
+                })
+            })
+            .collect::<io::Result<Vec<ChannelStatistics>>>()?;
+
+        if num_channels != sndfile.get_channels() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Cached statistics don't match the file",
+            ));
+        }
+
+        Ok(Statistics { channels })
+    }
+}
+
+impl Statistics {
+    pub fn channel(&self, channel: usize) -> &ChannelStatistics {
+        &self.channels[channel]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dsp::{
+        CancelToken, DspData, ProgressReporter, Statistics, StatisticsParameters, Waveform,
+        WaveformParameters,
+    };
+    use crate::utils::from_dbfs;
+    use sndfile::SndFileIO;
+    use std::path::{Path, PathBuf};
+
+    fn get_test_files_location() -> PathBuf {
+        Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+    }
+
+    fn open() -> sndfile::SndFile {
+        sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap()
+    }
+
+    /// Level counted as clipped by `compute`, low enough to be reached
+    const CLIP_THRESHOLD: f64 = -6f64;
+
+    fn compute(start: usize, end: usize) -> Statistics {
+        Statistics::new(
+            open(),
+            StatisticsParameters {
+                start,
+                end,
+                clip_threshold: CLIP_THRESHOLD,
+            },
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn whole_file() {
+        let snd = open();
+        let channels = snd.get_channels();
+        let waveform = Waveform::new(
+            snd,
+            WaveformParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+        let statistics = compute(0, usize::MAX);
+
+        for ch_idx in 0..channels {
+            let point = waveform.compute_channel_point(ch_idx);
+            let peak = i32::max(point.peak_max, point.peak_min.saturating_neg());
+            let channel = statistics.channel(ch_idx);
+
+            assert!((channel.peak - peak as f64 / i32::MAX as f64).abs() < 1e-9);
+            assert!((channel.rms - point.rms as f64 / i32::MAX as f64).abs() < 1e-6);
+            assert!(channel.peak >= channel.rms);
+            assert!(channel.dc_offset.abs() <= channel.rms);
+        }
+    }
+
+    #[test]
+    fn range() {
+        let mut snd = open();
+        let channels = snd.get_channels();
+        let frames = snd.len().unwrap() as usize;
+        let mut interleaved = vec![0i32; frames * channels];
+        snd.read_to_slice(interleaved.as_mut_slice()).unwrap();
+
+        let (start, end) = (frames / 4, frames / 2);
+        let statistics = compute(start, end);
+
+        for ch_idx in 0..channels {
+            let samples: Vec<f64> = interleaved[start * channels..end * channels]
+                .iter()
+                .skip(ch_idx)
+                .step_by(channels)
+                .map(|value| *value as f64 / i32::MAX as f64)
+                .collect();
+            let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+            let peak = samples
+                .iter()
+                .fold(0f64, |peak, value| peak.max(value.abs()));
+
+            let clip_level = from_dbfs(CLIP_THRESHOLD) * i32::MAX as f64;
+            let clipped = interleaved[start * channels..end * channels]
+                .iter()
+                .skip(ch_idx)
+                .step_by(channels)
+                .filter(|value| value.saturating_abs() as f64 >= clip_level)
+                .count();
+
+            let channel = statistics.channel(ch_idx);
+            assert!((channel.dc_offset - mean).abs() < 1e-9);
+            assert!((channel.peak - peak).abs() < 1e-9);
+            assert_eq!(channel.clipped, clipped);
+        }
+
+        // Nothing to measure
+        let empty = compute(start, start);
+        assert_eq!(empty.channel(0).peak, 0f64);
+        assert_eq!(empty.channel(0).clipped, 0);
+        assert_eq!(empty.channel(0).crest_factor(), None);
+    }
+
+    #[test]
+    fn cache_roundtrip() {
+        let statistics = compute(1000, 20000);
+
+        let mut bytes = vec![];
+        statistics.write_cache(&mut bytes).unwrap();
+        let cached = Statistics::read_cache(open(), &mut bytes.as_slice()).unwrap();
+        assert!(Statistics::read_cache(open(), &mut &bytes[..bytes.len() - 1]).is_err());
+        // Channels of another file
+        let mut tampered = bytes.clone();
+        tampered[0..8].copy_from_slice(&(statistics.channels.len() as u64 + 1).to_le_bytes());
+        assert!(Statistics::read_cache(open(), &mut tampered.as_slice()).is_err());
+
+        assert_eq!(cached.channels, statistics.channels);
+    }
+}
@@ -0,0 +1,44 @@
+extern crate sndfile;
+
+use crate::sndfile::SndFile;
+
+use super::{CancelToken, DspData, ProgressReporter};
+use sndfile::SndFileIO;
+use std::path::{Path, PathBuf};
+
+/// Write interleaved 16 bits samples to a WAV file of the temporary
+/// directory, unique to the test process
+pub fn write_wav(name: &str, channels: usize, samplerate: usize, samples: &[i16]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("audeye_{}_{}.wav", name, std::process::id()));
+    let options = sndfile::WriteOptions::new(
+        sndfile::MajorFormat::WAV,
+        sndfile::SubtypeFormat::PCM_16,
+        sndfile::Endian::File,
+        samplerate,
+        channels,
+    );
+    let mut snd = sndfile::OpenOptions::WriteOnly(options)
+        .from_path(&path)
+        .unwrap();
+    snd.write_from_slice(samples).unwrap();
+
+    path
+}
+
+pub fn open(path: &Path) -> SndFile {
+    sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+        .from_path(path)
+        .unwrap()
+}
+
+/// Run an analysis on a file, without normalization
+pub fn compute<P, T: DspData<P>>(path: &Path, parameters: P) -> T {
+    T::new(
+        open(path),
+        parameters,
+        None,
+        &CancelToken::default(),
+        &mut ProgressReporter::default(),
+    )
+    .unwrap()
+}
@@ -4,7 +4,7 @@ use apodize::{blackman_iter, hamming_iter, hanning_iter};
 use sndfile::SndFileIO;
 use std::{cmp::min, convert::TryFrom, fmt::Display, io::SeekFrom};
 
-use super::DspErr;
+use super::{CancelToken, DspErr};
 use crate::utils::deinterleave_vec;
 
 #[derive(Debug, Clone, Copy)]
@@ -69,6 +69,25 @@ impl WindowType {
     pub fn default() -> &'static str {
         HANNING
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hanning => HANNING,
+            Self::Hamming => HAMMING,
+            Self::Blackman => BLACKMAN,
+            Self::Uniform => UNIFORM,
+        }
+    }
+
+    /// The window type to switch to when cycling through them
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Hamming => Self::Hanning,
+            Self::Hanning => Self::Blackman,
+            Self::Blackman => Self::Uniform,
+            Self::Uniform => Self::Hamming,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -254,6 +273,23 @@ impl SidePaddingType {
     pub fn default() -> &'static str {
         ZEROS
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Zeros => ZEROS,
+            Self::SmoothRamp => RAMP,
+            Self::Loop => LOOP,
+        }
+    }
+
+    /// The padding type to switch to when cycling through them
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Zeros => Self::SmoothRamp,
+            Self::SmoothRamp => Self::Loop,
+            Self::Loop => Self::Zeros,
+        }
+    }
 }
 
 pub struct TimeWindowBatcher {
@@ -267,6 +303,7 @@ pub struct TimeWindowBatcher {
     window: Vec<f64>,
     tmp_interleaved_block: Vec<f64>,
     side_padding: SidePadding,
+    cancel: CancelToken,
 }
 
 impl TimeWindowBatcher {
@@ -276,6 +313,7 @@ impl TimeWindowBatcher {
         overlap: f64,
         window_type: WindowType,
         side_padding: SidePaddingType,
+        cancel: CancelToken,
     ) -> Result<TimeWindowBatcher, DspErr> {
         if 0f64 >= overlap || overlap >= 1f64 {
             return Err(DspErr::new(
@@ -308,16 +346,24 @@ impl TimeWindowBatcher {
             window: window_type.build_window(window_size),
             tmp_interleaved_block: vec![0f64; window_size * channels],
             side_padding,
+            cancel,
         })
     }
 
+    /// Fraction of the bands already batched, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        self.crt_band_idx as f64 / self.num_bands as f64
+    }
+
     pub fn get_num_bands(&self) -> usize {
         self.num_bands
     }
 
+    /// Next windowed batch of samples, or `None` at the end of the file or if
+    /// the processing got cancelled
     pub fn get_next_batch(&mut self) -> Option<Vec<&mut [f64]>> {
-        // We reached the end
-        if self.crt_band_idx >= self.num_bands {
+        // We reached the end, or nobody wants the rest
+        if self.crt_band_idx >= self.num_bands || self.cancel.is_cancelled() {
             return None;
         }
 
@@ -436,7 +482,8 @@ mod tests {
         #[test]
         fn parse_all() {
             for value in WindowType::possible_values() {
-                WindowType::parse(value).unwrap();
+                let window_type = WindowType::parse(value).unwrap();
+                assert_eq!(window_type.name(), *value);
             }
         }
 
@@ -496,6 +543,7 @@ mod tests {
 
             for padding_type_str in SidePaddingType::possible_values() {
                 let padding_type = SidePaddingType::parse(padding_type_str).unwrap();
+                assert_eq!(padding_type.name(), *padding_type_str);
                 let mut padder =
                     SidePadding::new(padding_type, &mut snd, PADDING_LEFT, WINDOW_SIZE);
 
@@ -621,7 +669,9 @@ mod tests {
 
     mod time_window {
         use super::get_test_files_location;
-        use crate::dsp::{time_window::TimeWindowBatcher, SidePaddingType, WindowType};
+        use crate::dsp::{
+            time_window::TimeWindowBatcher, CancelToken, SidePaddingType, WindowType,
+        };
         use std::convert::TryFrom;
 
         #[test]
@@ -640,6 +690,7 @@ mod tests {
                         *overlap,
                         WindowType::Hanning,
                         SidePaddingType::Zeros,
+                        CancelToken::default(),
                     )
                     .unwrap();
                 }
@@ -661,6 +712,7 @@ mod tests {
                 OVERLAP,
                 WindowType::Hanning,
                 SidePaddingType::Zeros,
+                CancelToken::default(),
             )
             .unwrap();
         }
@@ -680,6 +732,7 @@ mod tests {
                 OVERLAP,
                 WindowType::Hanning,
                 SidePaddingType::Zeros,
+                CancelToken::default(),
             )
             .unwrap();
         }
@@ -706,6 +759,7 @@ mod tests {
                         *overlap,
                         WindowType::Hanning,
                         SidePaddingType::Zeros,
+                        CancelToken::default(),
                     )
                     .unwrap();
                     let num_batch = batcher.get_num_bands();
@@ -734,5 +788,26 @@ mod tests {
                 }
             }
         }
+
+        #[test]
+        fn cancel() {
+            let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(get_test_files_location().join("rock_1s.wav"))
+                .unwrap();
+            let cancel = CancelToken::default();
+            let mut batcher = TimeWindowBatcher::new(
+                snd,
+                1024,
+                0.5,
+                WindowType::Hanning,
+                SidePaddingType::Zeros,
+                cancel.clone(),
+            )
+            .unwrap();
+
+            assert!(batcher.get_next_batch().is_some());
+            cancel.cancel();
+            assert!(batcher.get_next_batch().is_none());
+        }
     }
 }
@@ -0,0 +1,478 @@
+extern crate sndfile;
+use sndfile::SndFileIO;
+
+use crate::sndfile::SndFile;
+use std::io::{self, Read, SeekFrom, Write};
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use super::cache::{read_f64, read_len, read_u64, write_f64, write_u64};
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
+
+/// Frames read at once
+const READ_BLOCK_SIZE: usize = 4096;
+/// Highest true peaks located, at least this duration apart in seconds
+const MAX_LOCATIONS: usize = 8;
+const LOCATION_SPACING: f64 = 0.1;
+
+/// Oversampling factor of the true-peak measurement
+const OVERSAMPLING: usize = 4;
+/// Taps of each phase of the interpolation filter
+const TAPS: usize = 12;
+/// Interpolation filter of ITU-R BS.1770-4 annex 2, one row per phase
+const PHASES: [[f64; TAPS]; OVERSAMPLING] = [
+    [
+        0.0017089843750,
+        0.0109863281250,
+        -0.0196533203125,
+        0.0332031250000,
+        -0.0594482421875,
+        0.1373291015625,
+        0.9721679687500,
+        -0.1022949218750,
+        0.0476074218750,
+        -0.0266113281250,
+        0.0148925781250,
+        -0.0083007812500,
+    ],
+    [
+        -0.0291748046875,
+        0.0292968750000,
+        -0.0517578125000,
+        0.0891113281250,
+        -0.1665039062500,
+        0.4650878906250,
+        0.7797851562500,
+        -0.2003173828125,
+        0.1015625000000,
+        -0.0582275390625,
+        0.0330810546875,
+        -0.0189208984375,
+    ],
+    [
+        -0.0189208984375,
+        0.0330810546875,
+        -0.0582275390625,
+        0.1015625000000,
+        -0.2003173828125,
+        0.7797851562500,
+        0.4650878906250,
+        -0.1665039062500,
+        0.0891113281250,
+        -0.0517578125000,
+        0.0292968750000,
+        -0.0291748046875,
+    ],
+    [
+        -0.0083007812500,
+        0.0148925781250,
+        -0.0266113281250,
+        0.0476074218750,
+        -0.1022949218750,
+        0.9721679687500,
+        0.1373291015625,
+        -0.0594482421875,
+        0.0332031250000,
+        -0.0196533203125,
+        0.0109863281250,
+        0.0017089843750,
+    ],
+];
+
+/// Frames between a sample and the interpolation around it
+const LATENCY: usize = TAPS / 2;
+
+/// Peak of each channel once reconstructed between its samples, as after a
+/// D/A conversion, estimated by oversampling. Never below the sample peak
+pub struct TruePeakMeter {
+    // Latest samples of each channel, newest first, stored twice so they can
+    // be read as a single slice
+    history: Vec<[f64; 2 * TAPS]>,
+    position: usize,
+    peaks: Vec<f64>,
+    frame_peaks: Vec<f64>,
+}
+
+impl TruePeakMeter {
+    pub fn new(channels: usize) -> Self {
+        TruePeakMeter {
+            history: vec![[0f64; 2 * TAPS]; channels],
+            position: 0,
+            peaks: vec![0f64; channels],
+            frame_peaks: vec![0f64; channels],
+        }
+    }
+
+    /// Measure the next frame, with one sample per channel in [-1; 1]
+    pub fn push(&mut self, frame: &[f64]) {
+        self.position = (self.position + TAPS - 1) % TAPS;
+        let position = self.position;
+
+        for (((history, peak), frame_peak), sample) in self
+            .history
+            .iter_mut()
+            .zip(self.peaks.iter_mut())
+            .zip(self.frame_peaks.iter_mut())
+            .zip(frame)
+        {
+            history[position] = *sample;
+            history[position + TAPS] = *sample;
+            let samples = &history[position..position + TAPS];
+
+            *frame_peak = samples[LATENCY].abs();
+            for phase in PHASES.iter() {
+                let value: f64 = phase.iter().zip(samples).map(|(h, x)| h * x).sum();
+                *frame_peak = frame_peak.max(value.abs());
+            }
+            *peak = peak.max(*frame_peak).max(sample.abs());
+        }
+    }
+
+    /// True peak of each channel, relative to full scale
+    pub fn peaks(&self) -> &[f64] {
+        &self.peaks
+    }
+
+    /// True peak of each channel around the frame pushed `LATENCY` frames
+    /// before the last one, relative to full scale
+    fn frame_peaks(&self) -> &[f64] {
+        &self.frame_peaks
+    }
+}
+
+/// Where a true peak reaches its level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TruePeakLocation {
+    pub frame: usize,
+    pub channel: usize,
+    /// Relative to full scale
+    pub peak: f64,
+}
+
+/// Measure the true peak of each channel of a signal fed frame by frame,
+/// along with the locations of the highest ones
+pub struct TruePeakTracker {
+    meter: TruePeakMeter,
+    // Minimum distance between two locations, in frames
+    spacing: usize,
+    pushed: usize,
+    // The highest true peak of each span of `spacing` frames
+    candidates: Vec<TruePeakLocation>,
+}
+
+impl TruePeakTracker {
+    pub fn new(channels: usize, samplerate: usize) -> Self {
+        TruePeakTracker {
+            meter: TruePeakMeter::new(channels),
+            spacing: usize::max(1, (samplerate as f64 * LOCATION_SPACING) as usize),
+            pushed: 0,
+            candidates: vec![],
+        }
+    }
+
+    /// Measure the next frame, with one sample per channel in [-1; 1]
+    pub fn push(&mut self, frame: &[f64]) {
+        self.meter.push(frame);
+        self.pushed += 1;
+        if self.pushed <= LATENCY {
+            return;
+        }
+
+        let frame_idx = self.pushed - 1 - LATENCY;
+        for (channel, peak) in self.meter.frame_peaks().iter().enumerate() {
+            let location = TruePeakLocation {
+                frame: frame_idx,
+                channel,
+                peak: *peak,
+            };
+            match self.candidates.last_mut() {
+                Some(best) if best.frame / self.spacing == frame_idx / self.spacing => {
+                    if *peak > best.peak {
+                        *best = location;
+                    }
+                }
+                _ => self.candidates.push(location),
+            }
+        }
+    }
+
+    /// True peaks of the whole signal fed
+    pub fn finish(mut self) -> TruePeak {
+        // Silence after the end lets the interpolation reach the last frames
+        let silence = vec![0f64; self.meter.peaks().len()];
+        for _ in 0..usize::min(LATENCY, self.pushed) {
+            self.push(&silence);
+        }
+
+        // Candidates of neighbouring spans can be a few frames apart, only the
+        // highest of them is kept
+        let mut candidates = self.candidates;
+        candidates.sort_by(|a, b| b.peak.partial_cmp(&a.peak).unwrap_or(Ordering::Equal));
+        let mut locations: Vec<TruePeakLocation> = vec![];
+        for candidate in candidates {
+            if locations.len() == MAX_LOCATIONS {
+                break;
+            }
+            let spacing = self.spacing;
+            if locations
+                .iter()
+                .all(|location| location.frame.abs_diff(candidate.frame) >= spacing)
+            {
+                locations.push(candidate);
+            }
+        }
+
+        TruePeak {
+            peaks: self.meter.peaks().to_vec(),
+            locations,
+        }
+    }
+}
+
+pub struct TruePeakParameters;
+
+/// True peak of each channel, measured by oversampling 4 times following
+/// ITU-R BS.1770-4, along with the locations of the highest ones
+pub struct TruePeak {
+    peaks: Vec<f64>,
+    // Highest first
+    locations: Vec<TruePeakLocation>,
+}
+
+impl DspData<TruePeakParameters> for TruePeak {
+    fn new(
+        mut sndfile: SndFile,
+        _: TruePeakParameters,
+        _: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
+    ) -> Result<TruePeak, DspErr> {
+        let frames = match sndfile.len() {
+            Ok(frames) => usize::try_from(frames).unwrap(),
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
+        };
+        if sndfile.seek(SeekFrom::Start(0)).is_err() {
+            return Err(DspErr::new("Failed to seek 0"));
+        }
+        let channels = sndfile.get_channels();
+
+        let mut tracker = TruePeakTracker::new(channels, sndfile.get_samplerate());
+        let mut block_data: Vec<f64> = vec![0f64; READ_BLOCK_SIZE * channels];
+        let mut read_frames = 0usize;
+        while read_frames < frames {
+            if cancel.is_cancelled() {
+                return Err(DspErr::cancelled());
+            }
+
+            let nb_frames = match sndfile.read_to_slice(block_data.as_mut_slice()) {
+                Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+                Ok(nb_frames) => nb_frames,
+            };
+            block_data[..nb_frames * channels]
+                .chunks_exact(channels)
+                .for_each(|frame| tracker.push(frame));
+
+            read_frames += nb_frames;
+            progress.report(read_frames as f64 / frames as f64);
+        }
+
+        Ok(tracker.finish())
+    }
+
+    fn cache_key(_: &TruePeakParameters) -> String {
+        format!("true peak {} {}", MAX_LOCATIONS, LOCATION_SPACING)
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.peaks.len() as u64)?;
+        for peak in self.peaks.iter() {
+            write_f64(writer, *peak)?;
+        }
+
+        write_u64(writer, self.locations.len() as u64)?;
+        for location in self.locations.iter() {
+            write_u64(writer, location.frame as u64)?;
+            write_u64(writer, location.channel as u64)?;
+            write_f64(writer, location.peak)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_cache(mut sndfile: SndFile, reader: &mut dyn Read) -> io::Result<TruePeak> {
+        let len = read_len(reader)?;
+        let channels = sndfile.get_channels();
+        let frames = match sndfile.len().ok().and_then(|len| usize::try_from(len).ok()) {
+            Some(frames) if len == channels => frames,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Cached true peaks don't match the file",
+                ))
+            }
+        };
+        let peaks = (0..len)
+            .map(|_| read_f64(reader))
+            .collect::<io::Result<Vec<f64>>>()?;
+
+        let len = read_len(reader)?;
+        let locations = (0..len)
+            .map(|_| {
+                let location = TruePeakLocation {
+                    frame: read_u64(reader)? as usize,
+                    channel: read_u64(reader)? as usize,
+                    peak: read_f64(reader)?,
+                };
+                if location.channel >= channels || location.frame >= frames {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid location",
+                    ));
+                }
+                Ok(location)
+            })
+            .collect::<io::Result<Vec<TruePeakLocation>>>()?;
+
+        Ok(TruePeak { peaks, locations })
+    }
+}
+
+impl TruePeak {
+    /// True peak of a channel, relative to full scale
+    pub fn channel(&self, channel: usize) -> f64 {
+        self.peaks[channel]
+    }
+
+    /// Locations of the highest true peaks, highest first. At least 100ms
+    /// apart
+    pub fn locations(&self) -> &[TruePeakLocation] {
+        &self.locations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TruePeak, TruePeakMeter, TruePeakParameters, TruePeakTracker};
+    use crate::dsp::{CancelToken, DspData, ProgressReporter};
+    use crate::utils::to_dbfs;
+    use std::f64::consts::PI;
+    use std::path::{Path, PathBuf};
+
+    fn get_test_files_location() -> PathBuf {
+        Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+    }
+
+    #[test]
+    fn inter_sample_peak() {
+        // A quarter of the samplerate, shifted so that every sample falls
+        // halfway between the peaks
+        let mut meter = TruePeakMeter::new(2);
+        let mut sample_peak = 0f64;
+        for idx in 0..4800 {
+            let value = (PI / 2f64 * idx as f64 + PI / 4f64).sin();
+            sample_peak = f64::max(sample_peak, value.abs());
+            meter.push(&[value, value / 2f64]);
+        }
+
+        assert!((to_dbfs(sample_peak) + 3.01f64).abs() < 0.01f64);
+        assert!(to_dbfs(meter.peaks()[0]).abs() < 0.5f64);
+        assert!((to_dbfs(meter.peaks()[1]) + 6.02f64).abs() < 0.5f64);
+    }
+
+    #[test]
+    fn never_below_samples() {
+        let mut meter = TruePeakMeter::new(1);
+        meter.push(&[0.5f64]);
+        assert!(meter.peaks()[0] >= 0.5f64);
+
+        meter.push(&[-0.75f64]);
+        assert!(meter.peaks()[0] >= 0.75f64);
+    }
+
+    #[test]
+    fn file() {
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap();
+        let channels = snd.get_channels();
+        let spacing = snd.get_samplerate() / 10;
+        let true_peak = TruePeak::new(
+            snd,
+            TruePeakParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        let highest = true_peak.locations()[0];
+        assert!(highest.channel < channels);
+        assert_eq!(highest.peak, true_peak.channel(highest.channel));
+        for pair in true_peak.locations().windows(2) {
+            assert!(pair[0].peak >= pair[1].peak);
+        }
+        for (idx, location) in true_peak.locations().iter().enumerate() {
+            for other in true_peak.locations()[idx + 1..].iter() {
+                assert!(location.frame.abs_diff(other.frame) >= spacing);
+            }
+        }
+    }
+
+    #[test]
+    fn spaced_locations() {
+        // 100 frames spans at 1kHz : the first two peaks straddle the end of
+        // the first span, only the highest of them is located
+        let mut tracker = TruePeakTracker::new(1, 1000);
+        for idx in 0..1000 {
+            let value = match idx {
+                98 => 0.9f64,
+                102 => 0.8f64,
+                300 => 0.5f64,
+                _ => 0f64,
+            };
+            tracker.push(&[value]);
+        }
+        let true_peak = tracker.finish();
+
+        let locations = true_peak.locations();
+        assert_eq!(locations.len(), 2);
+        assert!(locations[0].frame.abs_diff(98) <= 1);
+        assert!(locations[1].frame.abs_diff(300) <= 1);
+    }
+
+    #[test]
+    fn cache_mismatch() {
+        let open = || {
+            sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(get_test_files_location().join("rock_1s.wav"))
+                .unwrap()
+        };
+        let true_peak = TruePeak::new(
+            open(),
+            TruePeakParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        let mut bytes = vec![];
+        true_peak.write_cache(&mut bytes).unwrap();
+        let cached = TruePeak::read_cache(open(), &mut bytes.as_slice()).unwrap();
+        assert_eq!(cached.locations(), true_peak.locations());
+
+        // Channel of the first location, out of the file
+        let offset = 8 * (true_peak.peaks.len() + 2) + 8;
+        let mut tampered = bytes.clone();
+        tampered[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(TruePeak::read_cache(open(), &mut tampered.as_slice()).is_err());
+        // Frame of the first location, after the end of the file
+        let offset = offset - 8;
+        let mut tampered = bytes.clone();
+        tampered[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(TruePeak::read_cache(open(), &mut tampered.as_slice()).is_err());
+    }
+}
@@ -2,22 +2,37 @@ extern crate sndfile;
 use sndfile::SndFileIO;
 
 use crate::sndfile::SndFile;
-use std::io::SeekFrom;
+use std::io::{self, Read, SeekFrom, Write};
+use std::sync::Mutex;
 
 use std::convert::TryFrom;
 
 use rayon::prelude::*;
 
-use super::{DspData, DspErr};
+use super::cache::{
+    read_f32, read_f64, read_len, read_u32, write_f32, write_f64, write_u32, write_u64,
+};
+use super::{CancelToken, DspData, DspErr, ProgressReporter};
 use crate::utils::Zoom;
 
+/// Frames summarized by each point of the finest level of the pyramid. Below
+/// this resolution, samples are read back from the file, so it can stay coarse
+/// enough to keep the pyramid small
+const BASE_BLOCK_SIZE: usize = 1024;
+/// Number of points of a level merged into one point of the next level
+const LEVEL_FACTOR: usize = 4;
+/// Frames read at once while building the pyramid
+const READ_BLOCK_SIZE: usize = 4096;
+
 fn compute_point(frames: &[i32]) -> WaveformPoint<i32> {
     let mut min = 0i32;
     let mut max = 0i32;
     let mut sum = 0f64;
+    let mut sum_sq = 0f64;
 
     for elm in frames {
-        sum += (*elm as f64) * (*elm as f64);
+        sum += *elm as f64;
+        sum_sq += (*elm as f64) * (*elm as f64);
         if *elm > max {
             max = *elm;
         } else if *elm < min {
@@ -26,19 +41,96 @@ fn compute_point(frames: &[i32]) -> WaveformPoint<i32> {
     }
 
     WaveformPoint {
-        rms: (sum / frames.len() as f64).sqrt() as i32,
+        rms: (sum_sq / frames.len() as f64).sqrt() as i32,
+        mean: (sum / frames.len() as f64) as i32,
         peak_min: min,
         peak_max: max,
     }
 }
 
+/// Peaks, mean and energy of a block of frames, mergeable with its neighbours.
+/// Accumulated in double precision, then packed to be stored
+#[derive(Default, Debug, Copy, Clone)]
+struct Summary {
+    min: i32,
+    max: i32,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl Summary {
+    #[inline(always)]
+    fn add_sample(&mut self, value: i32) {
+        self.sum += value as f64;
+        self.sum_sq += (value as f64) * (value as f64);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn merge(mut self, other: &Summary) -> Self {
+        self.sum += other.sum;
+        self.sum_sq += other.sum_sq;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self
+    }
+
+    fn merge_packed(self, other: &PackedSummary) -> Self {
+        self.merge(&Summary {
+            min: other.min,
+            max: other.max,
+            sum: other.sum as f64,
+            sum_sq: other.sum_sq as f64,
+        })
+    }
+
+    fn pack(&self) -> PackedSummary {
+        PackedSummary {
+            min: self.min,
+            max: self.max,
+            sum: self.sum as f32,
+            sum_sq: self.sum_sq as f32,
+        }
+    }
+
+    fn point(&self, frames: usize) -> WaveformPoint<i32> {
+        WaveformPoint {
+            rms: (self.sum_sq / frames as f64).sqrt() as i32,
+            mean: (self.sum / frames as f64) as i32,
+            peak_min: self.min,
+            peak_max: self.max,
+        }
+    }
+}
+
+/// Summary as stored in the pyramid, in single precision to halve its size
+#[derive(Default, Debug, Copy, Clone)]
+struct PackedSummary {
+    min: i32,
+    max: i32,
+    sum: f32,
+    sum_sq: f32,
+}
+
+/// Multi-resolution summary of the samples, like a peak file. Only the
+/// summaries are kept in memory, samples are read from the file on demand when
+/// zoomed near sample level
 pub struct Waveform {
-    frames: Vec<Vec<i32>>,
+    // Ordered by [level][channel], each level being LEVEL_FACTOR times coarser
+    // than the previous one, the first level summarizing BASE_BLOCK_SIZE frames
+    pyramid: Vec<Vec<Vec<PackedSummary>>>,
+    frames: usize,
+    channels: usize,
+    samplerate: usize,
+    norm: Option<f64>,
+    sndfile: Mutex<SndFile>,
 }
 
 #[derive(Default, Debug, Copy, Clone)]
 pub struct WaveformPoint<T> {
     pub rms: T,
+    /// DC offset of the block
+    pub mean: T,
     pub peak_min: T,
     pub peak_max: T,
 }
@@ -46,89 +138,304 @@ pub struct WaveformPoint<T> {
 #[derive(Default)]
 pub struct WaveformParameters;
 
+#[inline(always)]
+fn normalize(value: i32, norm: Option<f64>) -> i32 {
+    match norm {
+        Some(fnorm) => (value as f64 / fnorm) as i32,
+        None => value,
+    }
+}
+
 impl DspData<WaveformParameters> for Waveform {
     fn new(
         mut sndfile: SndFile,
         _: WaveformParameters,
         norm: Option<f64>,
+        cancel: &CancelToken,
+        progress: &mut ProgressReporter,
     ) -> Result<Waveform, DspErr> {
-        // Compute block size
-        let frames = sndfile.len().expect("Unable to retrieve number of frames");
-        sndfile.seek(SeekFrom::Start(0)).expect("Failed to seek 0");
-        let block_size = 4096usize;
-        let block_count = if frames % block_size as u64 == 0 {
-            usize::try_from(frames / block_size as u64).unwrap()
-        } else {
-            usize::try_from(frames / block_size as u64 + 1).unwrap()
+        let frames = match sndfile.len() {
+            Ok(frames) => usize::try_from(frames).unwrap(),
+            Err(_) => return Err(DspErr::new("Unable to retrieve number of frames")),
         };
+        if sndfile.seek(SeekFrom::Start(0)).is_err() {
+            return Err(DspErr::new("Failed to seek 0"));
+        }
         let channels = sndfile.get_channels();
 
-        // Create data vectors
-        let mut data = Waveform {
-            frames: vec![vec![0i32; usize::try_from(frames).unwrap()]; channels],
-        };
-        let mut block_data: Vec<i32> = vec![0; block_size * channels];
-
-        // Find min and max for each block
-        for block_idx in 0..block_count {
-            // Read block from file
-            // let mut nb_frames: usize = 0;
-            let read = sndfile.read_to_slice(block_data.as_mut_slice());
-            let nb_frames = match read {
-                Ok(frames) => {
-                    if frames == 0 {
-                        panic!("0 frames read")
-                    }
-                    frames
-                }
-                Err(err) => panic!("{:?}", err),
+        // Summarize the finest level in a single pass over the file
+        let mut level: Vec<Vec<Summary>> =
+            vec![Vec::with_capacity(frames.div_ceil(BASE_BLOCK_SIZE)); channels];
+        let mut current = vec![Summary::default(); channels];
+        let mut current_frames = 0usize;
+        let mut block_data: Vec<i32> = vec![0; READ_BLOCK_SIZE * channels];
+        let mut read_frames = 0usize;
+
+        while read_frames < frames {
+            if cancel.is_cancelled() {
+                return Err(DspErr::cancelled());
+            }
+
+            let nb_frames = match sndfile.read_to_slice(block_data.as_mut_slice()) {
+                Ok(0) | Err(_) => return Err(DspErr::new("Unable to read the file")),
+                Ok(nb_frames) => nb_frames,
             };
 
-            // Load into frames vector
-            let frame_offset = block_idx * block_size;
-            {
-                let interleaved_slice = &block_data[..nb_frames * channels];
-                // let mono_slices = data.frames.iter_mut().map(|channel| {
-                //     channel.as_mut_slice()[frame_idx..frame_idx + interleaved_slice.len()]
-                // }).collect();
-
-                // We could use dynamic dispatch to automatically switch btw
-                // the different evaluation method (norm / no-norm) but it would
-                // surely slow it down.
-                // TODO: benchmark ?
-                match norm {
-                    Some(fnorm) => {
-                        let fnorm_inv = 1f64 / fnorm;
-
-                        interleaved_slice
-                            .chunks_exact(channels)
-                            .enumerate()
-                            .for_each(|(frame_idx, samples)| {
-                                for (channel, value) in samples.iter().enumerate() {
-                                    data.frames[channel][frame_offset + frame_idx] =
-                                        (*value as f64 * fnorm_inv) as i32;
-                                }
-                            });
-                    }
-                    None => {
-                        interleaved_slice
-                            .chunks_exact(channels)
-                            .enumerate()
-                            .for_each(|(frame_idx, samples)| {
-                                for (channel, value) in samples.iter().enumerate() {
-                                    data.frames[channel][frame_offset + frame_idx] = *value;
-                                }
-                            });
+            for samples in block_data[..nb_frames * channels].chunks_exact(channels) {
+                for (summary, value) in current.iter_mut().zip(samples) {
+                    summary.add_sample(normalize(*value, norm));
+                }
+
+                current_frames += 1;
+                if current_frames == BASE_BLOCK_SIZE {
+                    for (summaries, summary) in level.iter_mut().zip(current.iter_mut()) {
+                        summaries.push(*summary);
+                        *summary = Summary::default();
                     }
+                    current_frames = 0;
                 }
             }
+
+            read_frames += nb_frames;
+            progress.report(read_frames as f64 / frames as f64);
+        }
+        if current_frames > 0 {
+            for (summaries, summary) in level.iter_mut().zip(current.iter()) {
+                summaries.push(*summary);
+            }
         }
 
-        Ok(data)
+        // Merge it into coarser levels, up to a single point per channel.
+        // Levels are merged before being packed, not to pile up rounding errors
+        let mut pyramid = vec![];
+        loop {
+            let coarser: Option<Vec<Vec<Summary>>> = (level[0].len() > 1).then(|| {
+                level
+                    .iter()
+                    .map(|summaries| {
+                        summaries
+                            .chunks(LEVEL_FACTOR)
+                            .map(|chunk| chunk.iter().fold(Summary::default(), Summary::merge))
+                            .collect()
+                    })
+                    .collect()
+            });
+            pyramid.push(
+                level
+                    .iter()
+                    .map(|summaries| summaries.iter().map(Summary::pack).collect())
+                    .collect(),
+            );
+
+            match coarser {
+                Some(coarser) => level = coarser,
+                None => break,
+            }
+        }
+
+        Ok(Waveform {
+            pyramid,
+            frames,
+            channels,
+            samplerate: sndfile.get_samplerate(),
+            norm,
+            sndfile: Mutex::new(sndfile),
+        })
+    }
+
+    fn cache_key(_: &WaveformParameters) -> String {
+        format!("waveform {} {}", BASE_BLOCK_SIZE, LEVEL_FACTOR)
+    }
+
+    fn write_cache(&self, writer: &mut dyn Write) -> io::Result<()> {
+        // NaN when not normalized
+        write_f64(writer, self.norm.unwrap_or(f64::NAN))?;
+        write_u64(writer, self.frames as u64)?;
+        write_u64(writer, self.channels as u64)?;
+        write_u64(writer, self.samplerate as u64)?;
+
+        write_u64(writer, self.pyramid.len() as u64)?;
+        for summaries in self.pyramid.iter().flatten() {
+            write_u64(writer, summaries.len() as u64)?;
+            for summary in summaries {
+                write_u32(writer, summary.min as u32)?;
+                write_u32(writer, summary.max as u32)?;
+                write_f32(writer, summary.sum)?;
+                write_f32(writer, summary.sum_sq)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_cache(mut sndfile: SndFile, reader: &mut dyn Read) -> io::Result<Waveform> {
+        let norm = Some(read_f64(reader)?).filter(|norm| !norm.is_nan());
+        let frames = read_len(reader)?;
+        let channels = read_len(reader)?;
+        let samplerate = read_len(reader)?;
+
+        let levels = read_len(reader)?;
+        let pyramid = (0..levels)
+            .map(|_| {
+                (0..channels)
+                    .map(|_| {
+                        let len = read_len(reader)?;
+                        (0..len)
+                            .map(|_| {
+                                Ok(PackedSummary {
+                                    min: read_u32(reader)? as i32,
+                                    max: read_u32(reader)? as i32,
+                                    sum: read_f32(reader)?,
+                                    sum_sq: read_f32(reader)?,
+                                })
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect::<io::Result<Vec<Vec<Vec<PackedSummary>>>>>()?;
+
+        // Every level has to be as long as the one computed from the file,
+        // as points are read by slicing them
+        let file_frames = sndfile.len().ok().and_then(|len| usize::try_from(len).ok());
+        let level_lens = pyramid_lens(frames);
+        let matching_levels = pyramid.len() == level_lens.len()
+            && pyramid
+                .iter()
+                .zip(level_lens.iter())
+                .all(|(level, len)| level.iter().all(|summaries| summaries.len() == *len));
+        if !matching_levels || file_frames != Some(frames) || channels != sndfile.get_channels() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Cached waveform doesn't match the file",
+            ));
+        }
+
+        Ok(Waveform {
+            pyramid,
+            frames,
+            channels,
+            samplerate,
+            norm,
+            sndfile: Mutex::new(sndfile),
+        })
+    }
+}
+
+/// Number of summaries of each level of the pyramid of a file of `frames`
+/// frames
+fn pyramid_lens(frames: usize) -> Vec<usize> {
+    let mut lens = vec![frames.div_ceil(BASE_BLOCK_SIZE)];
+    while *lens.last().unwrap() > 1 {
+        lens.push(lens.last().unwrap().div_ceil(LEVEL_FACTOR));
     }
+    lens
 }
 
 impl Waveform {
+    pub fn num_channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Duration of the content, in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames as f64 / self.samplerate as f64
+    }
+
+    /// Compute the peak, RMS and mean values over the whole content of a
+    /// channel
+    pub fn compute_channel_point(&self, channel: usize) -> WaveformPoint<i32> {
+        match self.pyramid.last().unwrap()[channel].first() {
+            Some(summary) => Summary::default().merge_packed(summary).point(self.frames),
+            None => WaveformPoint::default(),
+        }
+    }
+
+    /// DC offset of a channel relative to full scale, as in the file even when
+    /// normalized
+    pub fn dc_offset(&self, channel: usize) -> f64 {
+        let mean = self.compute_channel_point(channel).mean as f64 / i32::MAX as f64;
+        mean * self.norm.unwrap_or(1f64)
+    }
+
+    /// Read the samples of a channel back from the file
+    fn read_samples(&self, channel: usize, start: usize, end: usize) -> Vec<i32> {
+        let mut sndfile = self.sndfile.lock().unwrap();
+        if sndfile.seek(SeekFrom::Start(start as u64)).is_err() {
+            return vec![];
+        }
+
+        let mut interleaved = vec![0i32; (end - start) * self.channels];
+        let nb_frames = sndfile
+            .read_to_slice(interleaved.as_mut_slice())
+            .unwrap_or(0);
+
+        interleaved
+            .chunks_exact(self.channels)
+            .take(nb_frames)
+            .map(|samples| normalize(samples[channel], self.norm))
+            .collect()
+    }
+
+    /// Frame at a relative position in time btw 0 and 1
+    pub fn frame_at(&self, position: f64) -> usize {
+        usize::min(
+            (self.frames as f64 * position) as usize,
+            self.frames.saturating_sub(1),
+        )
+    }
+
+    /// Time of a frame, in seconds
+    pub fn frame_time(&self, frame: usize) -> f64 {
+        frame as f64 / self.samplerate as f64
+    }
+
+    /// Value of a single sample, read back from the file
+    pub fn sample(&self, channel: usize, frame: usize) -> Option<i32> {
+        if frame >= self.frames {
+            return None;
+        }
+        self.read_samples(channel, frame, frame + 1)
+            .first()
+            .copied()
+    }
+
+    /// Number of frames within the zoom window
+    pub fn visible_frames(&self, zoom: &Zoom) -> f64 {
+        self.frames as f64 * zoom.length()
+    }
+
+    /// Samples of a channel within the zoom window, to be drawn individually.
+    /// Also returns the position of the first one, in frames from the start of
+    /// the window
+    pub fn compute_samples(&self, channel: usize, zoom: &Zoom) -> (f64, Vec<i32>) {
+        let start = self.frames as f64 * zoom.start();
+        let end = self.frames as f64 * (zoom.start() + zoom.length());
+        let first = self.first_visible_frame(zoom);
+        let last = usize::min(end.floor() as usize + 1, self.frames);
+        if first >= last {
+            return (0f64, vec![]);
+        }
+
+        (
+            first as f64 - start,
+            self.read_samples(channel, first, last),
+        )
+    }
+
+    /// First frame of the samples computed by `compute_samples`
+    pub fn first_visible_frame(&self, zoom: &Zoom) -> usize {
+        (self.frames as f64 * zoom.start()).ceil() as usize
+    }
+
+    /// First frame of the points computed by `compute_points`, and the number
+    /// of frames each of them covers
+    pub fn points_layout(&self, block_count: usize, zoom: &Zoom) -> (usize, usize) {
+        let start = (self.frames as f64 * zoom.start()) as usize;
+        let end = (self.frames as f64 * (zoom.start() + zoom.length())) as usize;
+        (start, (end - start).div_ceil(usize::max(block_count, 1)))
+    }
+
     pub fn compute_points(
         &self,
         channel: usize,
@@ -139,29 +446,51 @@ impl Waveform {
         let mut points = vec![WaveformPoint::default(); block_count];
 
         // Compute block size and count
-        let total_frames = self.frames[0].len();
-        let start = (total_frames as f64 * zoom.start()) as usize;
-        let end = (total_frames as f64 * (zoom.start() + zoom.length())) as usize;
+        let (start, block_size) = self.points_layout(block_count, zoom);
+        let end = (self.frames as f64 * (zoom.start() + zoom.length())) as usize;
         let rendered_frames = end - start;
-        let block_size = if rendered_frames % block_count == 0 {
-            rendered_frames / block_count
+        if rendered_frames == 0 || block_count == 0 {
+            return points;
+        }
+        let used_points = rendered_frames.div_ceil(block_size);
+
+        if block_size < BASE_BLOCK_SIZE {
+            // Near sample level, the pyramid is too coarse
+            let samples = self.read_samples(channel, start, end);
+
+            points[..used_points]
+                .par_iter_mut()
+                .zip(samples.par_chunks(block_size))
+                .for_each(|(point, chunk)| {
+                    *point = compute_point(chunk);
+                });
         } else {
-            rendered_frames / block_count + 1
-        };
-
-        let samples_chunks = self.frames[channel][start..end].par_chunks_exact(block_size);
-        let remains = samples_chunks.remainder();
-
-        points[..block_count]
-            .par_iter_mut()
-            .zip(samples_chunks.into_par_iter())
-            .for_each(|(point, chunk)| {
-                *point = compute_point(chunk);
-            });
-
-        if !remains.is_empty() {
-            // Consume the end
-            points[block_count - 1] = compute_point(remains);
+            // Coarsest level still finer than the blocks
+            let mut level = 0usize;
+            while level + 1 < self.pyramid.len()
+                && BASE_BLOCK_SIZE * LEVEL_FACTOR.pow(level as u32 + 1) <= block_size
+            {
+                level += 1;
+            }
+            let level_size = BASE_BLOCK_SIZE * LEVEL_FACTOR.pow(level as u32);
+            let summaries = &self.pyramid[level][channel];
+            let frames = self.frames;
+
+            points[..used_points]
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(idx, point)| {
+                    let first_frame = start + idx * block_size;
+                    let last_frame = usize::min(first_frame + block_size, end);
+                    let first = first_frame / level_size;
+                    let last = usize::min(last_frame.div_ceil(level_size), summaries.len());
+
+                    let summary = summaries[first..last]
+                        .iter()
+                        .fold(Summary::default(), Summary::merge_packed);
+                    let covered_frames = usize::min(last * level_size, frames) - first * level_size;
+                    *point = summary.point(covered_frames);
+                });
         }
 
         points
@@ -170,9 +499,13 @@ impl Waveform {
 
 #[cfg(test)]
 mod tests {
-    use crate::dsp::{AsyncDspData, AsyncDspDataState, DspData, Waveform, WaveformParameters};
+    use crate::dsp::{
+        AsyncDspData, AsyncDspDataState, CancelToken, DspData, ProgressReporter, Waveform,
+        WaveformParameters,
+    };
     use crate::Zoom;
     use sndfile;
+    use sndfile::SndFileIO;
     use std::path::{Path, PathBuf};
     use std::thread::sleep;
     use std::time::Duration;
@@ -190,7 +523,75 @@ mod tests {
                 .from_path(get_test_files_location().join("rock_1s.wav"))
                 .unwrap();
 
-            Waveform::new(snd, WaveformParameters::default(), norm).unwrap();
+            Waveform::new(
+                snd,
+                WaveformParameters::default(),
+                norm,
+                &CancelToken::default(),
+                &mut ProgressReporter::default(),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn cancelled() {
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap();
+        let cancel = CancelToken::default();
+        cancel.cancel();
+
+        assert!(Waveform::new(
+            snd,
+            WaveformParameters,
+            None,
+            &cancel,
+            &mut ProgressReporter::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cache_roundtrip() {
+        let open = || {
+            sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(get_test_files_location().join("rock_1s.wav"))
+                .unwrap()
+        };
+        let waveform = Waveform::new(
+            open(),
+            WaveformParameters,
+            Some(0.5f64),
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        let mut bytes = vec![];
+        waveform.write_cache(&mut bytes).unwrap();
+        let cached = Waveform::read_cache(open(), &mut bytes.as_slice()).unwrap();
+        assert!(Waveform::read_cache(open(), &mut &bytes[..bytes.len() / 2]).is_err());
+        // Levels too short for the number of frames
+        let mut tampered = bytes.clone();
+        let frames = (waveform.frames as u64 * 2).to_le_bytes();
+        tampered[8..16].copy_from_slice(&frames);
+        assert!(Waveform::read_cache(open(), &mut tampered.as_slice()).is_err());
+
+        assert_eq!(cached.norm, waveform.norm);
+        assert_eq!(cached.duration(), waveform.duration());
+        let zoom = Zoom::new(0.0001f64).unwrap();
+        for ch_idx in 0..waveform.num_channels() {
+            for block_count in [100usize, 10000] {
+                let points = waveform.compute_points(ch_idx, block_count, &zoom);
+                let cached_points = cached.compute_points(ch_idx, block_count, &zoom);
+                for (point, cached_point) in points.iter().zip(cached_points.iter()) {
+                    assert_eq!(point.peak_min, cached_point.peak_min);
+                    assert_eq!(point.peak_max, cached_point.peak_max);
+                    assert_eq!(point.rms, cached_point.rms);
+                    assert_eq!(point.mean, cached_point.mean);
+                }
+            }
         }
     }
 
@@ -200,7 +601,7 @@ mod tests {
         let path = get_test_files_location().join("rock_1s.wav");
 
         let mut async_data: AsyncDspData<Waveform, WaveformParameters> =
-            AsyncDspData::new(&path, WaveformParameters::default(), false);
+            AsyncDspData::new(&path, WaveformParameters::default(), false, None);
         let mut attempts = 0;
 
         loop {
@@ -208,7 +609,7 @@ mod tests {
             async_data.update_status();
             let state = async_data.state();
 
-            assert_ne!(state, AsyncDspDataState::Failed);
+            assert!(!matches!(state, AsyncDspDataState::Failed(_)));
             assert!(attempts < 30);
 
             if state == AsyncDspDataState::Finished {
@@ -224,7 +625,7 @@ mod tests {
         let path = get_test_files_location().join("rock_1s.wav");
 
         let mut async_data: AsyncDspData<Waveform, WaveformParameters> =
-            AsyncDspData::new(&path, WaveformParameters::default(), true);
+            AsyncDspData::new(&path, WaveformParameters::default(), true, None);
         let mut attempts = 0;
 
         loop {
@@ -232,7 +633,7 @@ mod tests {
             async_data.update_status();
             let state = async_data.state();
 
-            assert_ne!(state, AsyncDspDataState::Failed);
+            assert!(!matches!(state, AsyncDspDataState::Failed(_)));
             assert!(attempts < 30);
 
             if state == AsyncDspDataState::Finished {
@@ -242,6 +643,130 @@ mod tests {
         }
     }
 
+    #[test]
+    fn pyramid_matches_samples() {
+        let open = || {
+            sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(get_test_files_location().join("rock_1s.wav"))
+                .unwrap()
+        };
+        let mut snd = open();
+        let channels = snd.get_channels();
+        let frames = snd.len().unwrap() as usize;
+        let mut interleaved = vec![0i32; frames * channels];
+        snd.read_to_slice(interleaved.as_mut_slice()).unwrap();
+
+        let waveform = Waveform::new(
+            open(),
+            WaveformParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+        let zoom = Zoom::new(0.001f64).unwrap();
+
+        for ch_idx in 0..channels {
+            let samples: Vec<i32> = interleaved
+                .chunks_exact(channels)
+                .map(|frame| frame[ch_idx])
+                .collect();
+
+            // Whole channel, from the top of the pyramid
+            let point = waveform.compute_channel_point(ch_idx);
+            assert_eq!(point.peak_max, *samples.iter().max().unwrap());
+            assert_eq!(point.peak_min, *samples.iter().min().unwrap());
+            let mean = samples.iter().map(|sample| *sample as f64).sum::<f64>() / frames as f64;
+            assert!((point.mean as f64 - mean).abs() <= 1f64);
+            assert!((waveform.dc_offset(ch_idx) - mean / i32::MAX as f64).abs() < 1e-6);
+
+            // Coarse points cover at least the samples of their block
+            for block_count in [10usize, 100] {
+                let block_size = frames.div_ceil(block_count);
+                let points = waveform.compute_points(ch_idx, block_count, &zoom);
+
+                for (point, chunk) in points.iter().zip(samples.chunks(block_size)) {
+                    assert!(point.peak_max >= *chunk.iter().max().unwrap());
+                    assert!(point.peak_min <= *chunk.iter().min().unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compute_samples() {
+        let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap();
+        let channels = snd.get_channels();
+        let frames = snd.len().unwrap() as usize;
+        let mut interleaved = vec![0i32; frames * channels];
+        snd.read_to_slice(interleaved.as_mut_slice()).unwrap();
+
+        let waveform = Waveform::new(
+            snd,
+            WaveformParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        // Zoom in until a few dozen samples are visible
+        let mut zoom = Zoom::new(10f64 / frames as f64).unwrap();
+        while waveform.visible_frames(&zoom) > 50f64 {
+            zoom.zoom_in();
+        }
+        for _ in 0..5 {
+            zoom.move_right();
+        }
+
+        for ch_idx in 0..channels {
+            let (offset, samples) = waveform.compute_samples(ch_idx, &zoom);
+            assert!((0f64..1f64).contains(&offset));
+            assert!(!samples.is_empty());
+            assert!(samples.len() as f64 <= waveform.visible_frames(&zoom) + 1f64);
+
+            let first = (frames as f64 * zoom.start() + offset).round() as usize;
+            for (idx, sample) in samples.iter().enumerate() {
+                assert_eq!(*sample, interleaved[(first + idx) * channels + ch_idx]);
+            }
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(get_test_files_location().join("rock_1s.wav"))
+            .unwrap();
+        let channels = snd.get_channels();
+        let frames = snd.len().unwrap() as usize;
+        let mut interleaved = vec![0i32; frames * channels];
+        snd.read_to_slice(interleaved.as_mut_slice()).unwrap();
+
+        let waveform = Waveform::new(
+            snd,
+            WaveformParameters,
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+
+        assert_eq!(waveform.frame_at(0f64), 0);
+        assert_eq!(waveform.frame_at(1f64), frames - 1);
+        let frame = waveform.frame_at(0.5f64);
+        assert_eq!(frame, frames / 2);
+
+        for ch_idx in 0..channels {
+            assert_eq!(
+                waveform.sample(ch_idx, frame),
+                Some(interleaved[frame * channels + ch_idx])
+            );
+        }
+        assert_eq!(waveform.sample(0, frames), None);
+    }
+
     #[test]
     fn compute_points() {
         let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
@@ -251,7 +776,14 @@ mod tests {
         let mut zoom = Zoom::new(0.5f64).unwrap();
         let blocks_counts = [50usize, 100, 128, 150, 1024];
 
-        let waveform = Waveform::new(snd, WaveformParameters::default(), None).unwrap();
+        let waveform = Waveform::new(
+            snd,
+            WaveformParameters::default(),
+            None,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
 
         // No zoom
         for ch_idx in 0..channels {
@@ -0,0 +1,82 @@
+use sndfile::{SndFile, SndFileIO, SubtypeFormat};
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::open;
+
+/// Frames copied at once
+const COPY_BLOCK_SIZE: usize = 4096;
+
+/// Build the default path of an audio export, next to the working directory,
+/// keeping the format of the input : <audio file name>_<suffix>.<extension>
+pub fn default_audio_export_path(input: &Path, suffix: &str) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "audeye".to_string());
+    let extension = input
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "wav".to_string());
+
+    PathBuf::from(format!("{}_{}.{}", stem, suffix, extension))
+}
+
+/// Copy the frames [start; end[ of a file to a new file, with the same format
+pub fn export_audio_range(input: &Path, start: usize, end: usize, output: &Path) -> io::Result<()> {
+    let mut src = open(input)?;
+    let subtype = src.get_subtype_format();
+    let options = sndfile::WriteOptions::new(
+        src.get_major_format(),
+        subtype,
+        src.get_endian(),
+        src.get_samplerate(),
+        src.get_channels(),
+    );
+    let mut dst = sndfile::OpenOptions::WriteOnly(options)
+        .from_path(output)
+        .map_err(|err| io::Error::other(format!("{:?}", err)))?;
+
+    src.seek(SeekFrom::Start(start as u64))
+        .map_err(|_| io::Error::other("Failed to seek the start of the range"))?;
+
+    // Integers copy integer formats bit for bit, floats keep the values of
+    // float formats out of [-1; 1]
+    match subtype {
+        SubtypeFormat::FLOAT | SubtypeFormat::DOUBLE | SubtypeFormat::VORBIS => {
+            copy_frames::<f64>(&mut src, &mut dst, start, end)
+        }
+        _ => copy_frames::<i32>(&mut src, &mut dst, start, end),
+    }
+}
+
+fn copy_frames<T>(src: &mut SndFile, dst: &mut SndFile, start: usize, end: usize) -> io::Result<()>
+where
+    T: 'static + Default + Copy,
+    SndFile: SndFileIO<T>,
+{
+    let channels = src.get_channels();
+    let mut block: Vec<T> = vec![T::default(); COPY_BLOCK_SIZE * channels];
+    let mut copied = start;
+    while copied < end {
+        let to_read = usize::min(COPY_BLOCK_SIZE, end - copied);
+        let nb_frames = match src.read_to_slice(&mut block[..to_read * channels]) {
+            Ok(0) | Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "Only {} of {} frames could be read",
+                        copied - start,
+                        end - start
+                    ),
+                ))
+            }
+            Ok(nb_frames) => nb_frames,
+        };
+        dst.write_from_slice(&block[..nb_frames * channels])
+            .map_err(|_| io::Error::other("Failed to write the audio file"))?;
+        copied += nb_frames;
+    }
+
+    Ok(())
+}
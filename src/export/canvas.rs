@@ -0,0 +1,18 @@
+use std::io;
+use std::path::Path;
+
+pub type Rgb = [u8; 3];
+
+/// Drawing primitives shared by the exported image formats. Coordinates are
+/// expressed in pixels, from the top left corner
+pub trait Canvas {
+    /// Straight line between two points, both ends included
+    fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgb);
+
+    fn fill_rect(&mut self, x: i64, y: i64, width: i64, height: i64, color: Rgb);
+
+    /// Draw a text with its top left corner at (x, y)
+    fn text(&mut self, x: i64, y: i64, text: &str, color: Rgb, scale: usize);
+
+    fn save(&self, path: &Path) -> io::Result<()>;
+}
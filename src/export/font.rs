@@ -0,0 +1,45 @@
+/// Width of a glyph, in font pixels
+pub const GLYPH_WIDTH: usize = 3;
+/// Height of a glyph, in font pixels
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// Width in pixels of a text drawn with the given scale
+pub fn text_width(text: &str, scale: usize) -> usize {
+    text.chars().count() * (GLYPH_WIDTH + 1) * scale
+}
+
+/// Height in pixels of a text drawn with the given scale
+pub fn text_height(scale: usize) -> usize {
+    GLYPH_HEIGHT * scale
+}
+
+/// A tiny 3x5 bitmap font, covering only what axis labels need. Each row is
+/// stored on the 3 lowest bits, the most significant one being the leftmost
+pub fn glyph(c: char) -> Option<[u8; GLYPH_HEIGHT]> {
+    let rows = match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        'k' => [0b100, 0b101, 0b110, 0b101, 0b101],
+        's' => [0b000, 0b011, 0b110, 0b011, 0b110],
+        'd' => [0b001, 0b001, 0b111, 0b101, 0b111],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'z' => [0b000, 0b111, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        _ => return None,
+    };
+
+    Some(rows)
+}
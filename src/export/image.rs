@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use super::canvas::{Canvas, Rgb};
+use super::font::{glyph, GLYPH_WIDTH};
+
+/// A plain RGB canvas to draw the exported views into
+pub struct RgbImage {
+    width: usize,
+    height: usize,
+    buffer: Vec<u8>,
+}
+
+impl RgbImage {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            buffer: background.repeat(width * height),
+        }
+    }
+
+    /// Set a single pixel, silently ignoring out of bounds coordinates
+    pub fn put(&mut self, x: i64, y: i64, color: Rgb) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let idx = (y as usize * self.width + x as usize) * 3;
+        self.buffer[idx..idx + 3].copy_from_slice(&color);
+    }
+
+    /// Copy a transposed image (as produced for the terminal rendering) with
+    /// its top left corner at (x, y). Each row of the source is a column of
+    /// the destination, and goes from the bottom to the top
+    pub fn blit_transposed(&mut self, x: usize, y: usize, src: &[u8], src_width: usize) {
+        for (col_idx, column) in src.chunks_exact(src_width * 3).enumerate() {
+            for (row_idx, pixel) in column.chunks_exact(3).enumerate() {
+                self.put(
+                    (x + col_idx) as i64,
+                    (y + src_width - 1 - row_idx) as i64,
+                    [pixel[0], pixel[1], pixel[2]],
+                );
+            }
+        }
+    }
+}
+
+impl Canvas for RgbImage {
+    /// Bresenham line between two points, both ends included
+    fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgb) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let (mut x, mut y) = (x0, y0);
+        let mut err = dx + dy;
+
+        loop {
+            self.put(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let err2 = 2 * err;
+            if err2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if err2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, x: i64, y: i64, width: i64, height: i64, color: Rgb) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.put(px, py, color);
+            }
+        }
+    }
+
+    /// Unknown characters are skipped
+    fn text(&mut self, x: i64, y: i64, text: &str, color: Rgb, scale: usize) {
+        let scale = scale as i64;
+        let advance = (GLYPH_WIDTH as i64 + 1) * scale;
+
+        for (char_idx, c) in text.chars().enumerate() {
+            let rows = match glyph(c) {
+                Some(rows) => rows,
+                None => continue,
+            };
+            let char_x = x + char_idx as i64 * advance;
+
+            for (row_idx, row) in rows.iter().enumerate() {
+                for col_idx in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - col_idx)) != 0 {
+                        self.fill_rect(
+                            char_x + col_idx as i64 * scale,
+                            y + row_idx as i64 * scale,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer
+            .write_image_data(&self.buffer)
+            .map_err(io::Error::other)
+    }
+}
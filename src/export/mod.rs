@@ -0,0 +1,324 @@
+mod audio;
+mod canvas;
+mod font;
+mod image;
+mod plot;
+mod spectrogram;
+mod svg;
+mod waveform;
+
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::dsp::{
+    compute_norm, CancelToken, Colormap, DbRange, DspData, ProgressReporter, Spectrogram,
+    SpectrogramParameters, Waveform, WaveformParameters,
+};
+use crate::utils::{AmplitudeMapping, Zoom};
+
+pub use audio::{default_audio_export_path, export_audio_range};
+pub use spectrogram::{export_spectrogram, SpectrogramStyle};
+pub use waveform::export_waveform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExportFormatParseError;
+
+impl Display for ExportFormatParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid export format")
+    }
+}
+
+const PNG: &str = "png";
+const SVG: &str = "svg";
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<Self, ExportFormatParseError> {
+        if name == PNG {
+            Ok(Self::Png)
+        } else if name == SVG {
+            Ok(Self::Svg)
+        } else {
+            Err(ExportFormatParseError)
+        }
+    }
+
+    pub fn possible_values() -> &'static [&'static str] {
+        &[PNG, SVG]
+    }
+
+    pub fn default() -> &'static str {
+        PNG
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => PNG,
+            Self::Svg => SVG,
+        }
+    }
+
+    /// Deduce the format from the extension of a path, defaults to png
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case(SVG) => Self::Svg,
+            _ => Self::Png,
+        }
+    }
+}
+
+/// Dimensions, decorations and format of an exported image
+#[derive(Debug, Clone, Copy)]
+pub struct ExportOptions {
+    pub width: usize,
+    pub height: usize,
+    pub axes: bool,
+    /// Format of the views exported from the interface, when it supports it
+    pub format: ExportFormat,
+}
+
+/// Build the default path of an export, next to the working directory :
+/// <audio file name>_<suffix>.<format extension>
+pub fn default_export_path(input: &Path, suffix: &str, format: ExportFormat) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "audeye".to_string());
+
+    PathBuf::from(format!("{}_{}.{}", stem, suffix, format.extension()))
+}
+
+fn open(input: &Path) -> io::Result<sndfile::SndFile> {
+    sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+        .from_path(input)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err)))
+}
+
+fn compute_file_norm(input: &Path, normalize: bool) -> io::Result<Option<f64>> {
+    Ok(if normalize {
+        let norm = compute_norm(
+            &mut open(input)?,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Some(norm)
+    } else {
+        None
+    })
+}
+
+/// Compute the spectrogram of a whole file and export all of its channels
+pub fn export_spectrogram_file(
+    input: &Path,
+    parameters: SpectrogramParameters,
+    colormap: &Colormap,
+    normalize: bool,
+    options: &ExportOptions,
+    output: &Path,
+) -> io::Result<()> {
+    let frequency_zoom = Zoom::new(1f64).unwrap();
+    let style = SpectrogramStyle {
+        scale: parameters.frequency_scale,
+        frequency_zoom: &frequency_zoom,
+        colormap,
+        db_range: DbRange {
+            floor: parameters.db_threshold,
+            ceiling: 0f64,
+        },
+    };
+    let norm = compute_file_norm(input, normalize)?;
+    let mut spectrogram = Spectrogram::new(
+        open(input)?,
+        parameters,
+        norm,
+        &CancelToken::default(),
+        &mut ProgressReporter::default(),
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let channels: Vec<usize> = (0..spectrogram.num_channels()).collect();
+    let zoom = Zoom::new(1f64).unwrap();
+
+    export_spectrogram(&mut spectrogram, &channels, &zoom, &style, options, output)
+}
+
+/// Compute the waveform of a whole file and export all of its channels
+pub fn export_waveform_file(
+    input: &Path,
+    normalize: bool,
+    options: &ExportOptions,
+    output: &Path,
+) -> io::Result<()> {
+    let norm = compute_file_norm(input, normalize)?;
+    let waveform = Waveform::new(
+        open(input)?,
+        WaveformParameters,
+        norm,
+        &CancelToken::default(),
+        &mut ProgressReporter::default(),
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let channels: Vec<usize> = (0..waveform.num_channels()).collect();
+    let mappings = vec![AmplitudeMapping::full_scale(); channels.len()];
+    let zoom = Zoom::new(1f64).unwrap();
+
+    export_waveform(&waveform, &channels, &mappings, &zoom, options, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        default_audio_export_path, default_export_path, export_audio_range,
+        export_spectrogram_file, export_waveform_file, ExportFormat, ExportOptions,
+    };
+    use crate::dsp::{
+        Colormap, FrequencyScale, SidePaddingType, SpectrogramParameters, WindowType,
+    };
+    use sndfile::SndFileIO;
+    use std::path::{Path, PathBuf};
+
+    fn get_test_files_location() -> PathBuf {
+        Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+    }
+
+    #[test]
+    fn spectrogram() {
+        let output = std::env::temp_dir().join("audeye_test_spectrogram.png");
+
+        for axes in [false, true] {
+            let parameters = SpectrogramParameters {
+                window_size: 1024,
+                overlap_rate: 0.5,
+                db_threshold: -130f64,
+                window_type: WindowType::Hanning,
+                side_padding_type: SidePaddingType::Zeros,
+                frequency_scale: FrequencyScale::Mel,
+            };
+            let options = ExportOptions {
+                width: 640,
+                height: 480,
+                axes,
+                format: ExportFormat::Png,
+            };
+
+            export_spectrogram_file(
+                &get_test_files_location().join("rock_1s.wav"),
+                parameters,
+                &Colormap::parse("viridis").unwrap(),
+                false,
+                &options,
+                &output,
+            )
+            .unwrap();
+            assert!(output.exists());
+        }
+    }
+
+    #[test]
+    fn waveform() {
+        for extension in ["png", "svg"] {
+            let output = std::env::temp_dir().join(format!("audeye_test_waveform.{}", extension));
+
+            for axes in [false, true] {
+                let options = ExportOptions {
+                    width: 640,
+                    height: 480,
+                    axes,
+                    format: ExportFormat::from_path(&output),
+                };
+
+                export_waveform_file(
+                    &get_test_files_location().join("rock_1s.wav"),
+                    false,
+                    &options,
+                    &output,
+                )
+                .unwrap();
+                assert!(output.exists());
+            }
+        }
+    }
+
+    #[test]
+    fn default_path() {
+        let input = get_test_files_location().join("rock_1s.wav");
+        for value in ExportFormat::possible_values() {
+            let format = ExportFormat::parse(value).unwrap();
+            let path = default_export_path(&input, "waveform", format);
+            assert_eq!(path, PathBuf::from(format!("rock_1s_waveform.{}", value)));
+            assert_eq!(ExportFormat::from_path(&path), format);
+        }
+    }
+
+    #[test]
+    fn too_small() {
+        let parameters = SpectrogramParameters {
+            window_size: 1024,
+            overlap_rate: 0.5,
+            db_threshold: -130f64,
+            window_type: WindowType::Hanning,
+            side_padding_type: SidePaddingType::Zeros,
+            frequency_scale: FrequencyScale::Linear,
+        };
+        let options = ExportOptions {
+            width: 10,
+            height: 10,
+            axes: true,
+            format: ExportFormat::Png,
+        };
+
+        assert!(export_spectrogram_file(
+            &get_test_files_location().join("rock_1s.wav"),
+            parameters,
+            &Colormap::parse(Colormap::default()).unwrap(),
+            false,
+            &options,
+            &std::env::temp_dir().join("audeye_test_too_small.png"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn audio_range() {
+        let input = get_test_files_location().join("rock_1s.wav");
+        let output = std::env::temp_dir().join("audeye_test_range.wav");
+        let open = |path: &Path| {
+            sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(path)
+                .unwrap()
+        };
+
+        let mut src = open(&input);
+        let channels = src.get_channels();
+        let frames = src.len().unwrap() as usize;
+        let mut expected = vec![0i32; frames * channels];
+        src.read_to_slice(expected.as_mut_slice()).unwrap();
+
+        let (start, end) = (frames / 4, frames / 2);
+        export_audio_range(&input, start, end, &output).unwrap();
+
+        let mut dst = open(&output);
+        assert_eq!(dst.get_channels(), channels);
+        assert_eq!(dst.get_samplerate(), src.get_samplerate());
+        assert_eq!(dst.len().unwrap() as usize, end - start);
+        let mut copied = vec![0i32; (end - start) * channels];
+        dst.read_to_slice(copied.as_mut_slice()).unwrap();
+        assert_eq!(copied, expected[start * channels..end * channels]);
+        // Past the end of the file
+        assert!(export_audio_range(&input, start, frames + 1, &output).is_err());
+
+        assert_eq!(
+            default_audio_export_path(&input, "selection"),
+            PathBuf::from("rock_1s_selection.wav")
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::io;
+
+use super::canvas::{Canvas, Rgb};
+use super::font::{text_height, text_width};
+use super::ExportOptions;
+
+pub const BACKGROUND_COLOR: Rgb = [16, 16, 16];
+pub const AXIS_COLOR: Rgb = [200, 200, 200];
+
+const LABEL_SCALE: usize = 2;
+const LABEL_MAX_CHARS: usize = 6;
+const TICK_LENGTH: usize = 5;
+const MARGIN: usize = 4;
+const CHANNEL_SEPARATOR: usize = 4;
+
+/// Height in pixels of an axis label
+pub fn label_height() -> usize {
+    text_height(LABEL_SCALE)
+}
+
+/// Width in pixels of an axis label
+pub fn label_width(label: &str) -> usize {
+    text_width(label, LABEL_SCALE)
+}
+
+/// A rectangle of the exported image a single channel is drawn into
+#[derive(Debug, Clone, Copy)]
+pub struct PlotArea {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Split the image into one area per channel, stacked vertically, leaving
+/// room for the axes labels if required
+pub fn compute_layout(options: &ExportOptions, channel_count: usize) -> io::Result<Vec<PlotArea>> {
+    let (left, bottom, right, top) = if options.axes {
+        (
+            text_width(&"0".repeat(LABEL_MAX_CHARS), LABEL_SCALE) + TICK_LENGTH + MARGIN,
+            text_height(LABEL_SCALE) + TICK_LENGTH + 2 * MARGIN,
+            // Leave room for the last time label to overflow
+            text_width(&"0".repeat(LABEL_MAX_CHARS / 2), LABEL_SCALE),
+            text_height(LABEL_SCALE) / 2 + MARGIN,
+        )
+    } else {
+        (0, 0, 0, 0)
+    };
+
+    let separators = CHANNEL_SEPARATOR * channel_count.saturating_sub(1);
+    let width = options.width.saturating_sub(left + right);
+    let height = options
+        .height
+        .saturating_sub(top + bottom + separators)
+        .checked_div(channel_count)
+        .unwrap_or(0);
+
+    if width == 0 || height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Export size is too small",
+        ));
+    }
+
+    Ok((0..channel_count)
+        .map(|idx| PlotArea {
+            x: left,
+            y: top + idx * (height + CHANNEL_SEPARATOR),
+            width,
+            height,
+        })
+        .collect())
+}
+
+/// Draw ticks and labels on the left side of an area. Positions go from 0
+/// (bottom) to 1 (top)
+pub fn draw_vertical_axis<C: Canvas>(img: &mut C, area: &PlotArea, labels: &[(f64, String)]) {
+    let x_tick_end = area.x as i64 - 1;
+    let x_tick_start = x_tick_end - TICK_LENGTH as i64 + 1;
+    let half_height = label_height() as i64 / 2;
+
+    img.line(
+        x_tick_end,
+        area.y as i64,
+        x_tick_end,
+        (area.y + area.height) as i64 - 1,
+        AXIS_COLOR,
+    );
+
+    for (position, label) in labels {
+        let y = (area.y + area.height - 1) as i64
+            - (position * (area.height - 1) as f64).round() as i64;
+        img.line(x_tick_start, y, x_tick_end, y, AXIS_COLOR);
+
+        let text_x = x_tick_start - 1 - text_width(label, LABEL_SCALE) as i64;
+        img.text(text_x, y - half_height, label, AXIS_COLOR, LABEL_SCALE);
+    }
+}
+
+/// Draw ticks and labels under an area. Positions go from 0 (left) to 1
+/// (right)
+pub fn draw_horizontal_axis<C: Canvas>(img: &mut C, area: &PlotArea, labels: &[(f64, String)]) {
+    let y_tick_start = (area.y + area.height) as i64;
+    let y_tick_end = y_tick_start + TICK_LENGTH as i64 - 1;
+
+    img.line(
+        area.x as i64,
+        y_tick_start,
+        (area.x + area.width) as i64 - 1,
+        y_tick_start,
+        AXIS_COLOR,
+    );
+
+    for (position, label) in labels {
+        let x = area.x as i64 + (position * (area.width - 1) as f64).round() as i64;
+        img.line(x, y_tick_start, x, y_tick_end, AXIS_COLOR);
+
+        let text_x = x - text_width(label, LABEL_SCALE) as i64 / 2;
+        img.text(
+            text_x,
+            y_tick_end + MARGIN as i64,
+            label,
+            AXIS_COLOR,
+            LABEL_SCALE,
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use fast_image_resize as fr;
+use std::convert::TryFrom;
+use std::io;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+use super::canvas::Canvas;
+use super::image::RgbImage;
+use super::plot::{
+    compute_layout, draw_horizontal_axis, draw_vertical_axis, label_height, label_width, PlotArea,
+    BACKGROUND_COLOR,
+};
+use super::ExportOptions;
+use crate::dsp::{Colormap, DbRange, FrequencyScale, Spectrogram};
+use crate::utils::axis::time_labels;
+use crate::utils::Zoom;
+
+fn non_zero(value: usize) -> io::Result<NonZeroU32> {
+    u32::try_from(value)
+        .ok()
+        .and_then(NonZeroU32::new)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid image dimension"))
+}
+
+/// How the magnitudes of a spectrogram are laid out and colored
+pub struct SpectrogramStyle<'a> {
+    pub scale: FrequencyScale,
+    // Range of frequencies shown
+    pub frequency_zoom: &'a Zoom,
+    pub colormap: &'a Colormap,
+    pub db_range: DbRange,
+}
+
+fn draw_channel(
+    img: &mut RgbImage,
+    spectrogram: &mut Spectrogram,
+    channel: usize,
+    area: &PlotArea,
+    zoom: &Zoom,
+    style: &SpectrogramStyle,
+    buffers: &mut (Vec<u32>, Vec<u8>),
+) -> io::Result<()> {
+    let (buffer, colors) = buffers;
+    let (data_slice, num_bands, num_bins) =
+        spectrogram.scaled_data(channel, zoom, style.frequency_zoom, style.scale, buffer);
+    let src_image = fr::Image::from_slice_u32(
+        non_zero(num_bins)?,
+        non_zero(num_bands)?,
+        data_slice,
+        fr::PixelType::F32,
+    )
+    .map_err(|err| io::Error::other(format!("{:?}", err)))?;
+
+    // Like the terminal rendering, the image is transposed
+    let mut dst_image = fr::Image::new(
+        non_zero(area.height)?,
+        non_zero(area.width)?,
+        fr::PixelType::F32,
+    );
+    let mut resizer = fr::Resizer::new(fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3));
+    resizer
+        .resize(&src_image.view(), &mut dst_image.view_mut())
+        .map_err(|err| io::Error::other(format!("{:?}", err)))?;
+
+    style
+        .colormap
+        .colorize(dst_image.buffer(), &style.db_range, colors);
+    img.blit_transposed(area.x, area.y, colors, area.height);
+
+    Ok(())
+}
+
+/// Write the given channels of a spectrogram, restricted to the zoom window and
+/// laid out and colored according to the given style, into a png file
+pub fn export_spectrogram(
+    spectrogram: &mut Spectrogram,
+    channels: &[usize],
+    zoom: &Zoom,
+    style: &SpectrogramStyle,
+    options: &ExportOptions,
+    path: &Path,
+) -> io::Result<()> {
+    let layout = compute_layout(options, channels.len())?;
+    let mut img = RgbImage::new(options.width, options.height, BACKGROUND_COLOR);
+
+    let mut buffers = (vec![], vec![]);
+    for (channel, area) in channels.iter().zip(layout.iter()) {
+        draw_channel(
+            &mut img,
+            spectrogram,
+            *channel,
+            area,
+            zoom,
+            style,
+            &mut buffers,
+        )?;
+    }
+
+    if options.axes {
+        let (min_hz, max_hz) = spectrogram.frequency_range(style.frequency_zoom);
+        for area in layout.iter() {
+            let max_count = area.height / (label_height() * 3);
+            let labels = style.scale.labels(min_hz, max_hz, max_count);
+            draw_vertical_axis(&mut img, area, &labels);
+        }
+
+        if let Some(area) = layout.last() {
+            let duration = spectrogram.duration();
+            let max_count = area.width / (label_width("000000") * 2);
+            let labels = time_labels(
+                zoom.start() * duration,
+                (zoom.start() + zoom.length()) * duration,
+                max_count,
+            );
+            draw_horizontal_axis(&mut img, area, &labels);
+        }
+    }
+
+    img.save(path)
+}
@@ -0,0 +1,85 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::canvas::{Canvas, Rgb};
+use super::font::text_height;
+
+fn to_svg_color(color: Rgb) -> String {
+    format!("rgb({},{},{})", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A vector canvas, written as an svg document
+pub struct SvgImage {
+    width: usize,
+    height: usize,
+    elements: String,
+}
+
+impl SvgImage {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        let mut img = Self {
+            width,
+            height,
+            elements: String::new(),
+        };
+        img.fill_rect(0, 0, width as i64, height as i64, background);
+        img
+    }
+}
+
+impl Canvas for SvgImage {
+    fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgb) {
+        // Shift to the pixel centers to get crisp 1px lines
+        let _ = writeln!(
+            self.elements,
+            r#"<line x1="{}.5" y1="{}.5" x2="{}.5" y2="{}.5" stroke="{}" stroke-linecap="square"/>"#,
+            x0,
+            y0,
+            x1,
+            y1,
+            to_svg_color(color)
+        );
+    }
+
+    fn fill_rect(&mut self, x: i64, y: i64, width: i64, height: i64, color: Rgb) {
+        let _ = writeln!(
+            self.elements,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            x,
+            y,
+            width,
+            height,
+            to_svg_color(color)
+        );
+    }
+
+    fn text(&mut self, x: i64, y: i64, text: &str, color: Rgb, scale: usize) {
+        // Match the metrics of the bitmap font, the y coordinate is the baseline
+        let height = text_height(scale);
+        let _ = writeln!(
+            self.elements,
+            r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" fill="{}">{}</text>"#,
+            x,
+            y + height as i64,
+            height * 7 / 5,
+            to_svg_color(color),
+            escape(text)
+        );
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let document = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n{}</svg>\n",
+            self.width, self.height, self.width, self.height, self.elements
+        );
+        fs::write(path, document)
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::path::Path;
+
+use super::canvas::{Canvas, Rgb};
+use super::image::RgbImage;
+use super::plot::{
+    compute_layout, draw_horizontal_axis, draw_vertical_axis, label_height, label_width, PlotArea,
+    BACKGROUND_COLOR,
+};
+use super::svg::SvgImage;
+use super::{ExportFormat, ExportOptions};
+use crate::dsp::Waveform;
+use crate::utils::axis::time_labels;
+use crate::utils::{AmplitudeMapping, Zoom};
+
+const PEAK_COLOR: Rgb = [140, 140, 140];
+const RMS_COLOR: Rgb = [255, 255, 255];
+
+/// Vertical pixel coordinate of an amplitude within an area
+fn to_y(area: &PlotArea, mapping: &AmplitudeMapping, value: i32) -> i64 {
+    let relative = (mapping.map(value as f64) + 1f64) / 2f64;
+    (area.y + area.height - 1) as i64 - (relative * (area.height - 1) as f64).round() as i64
+}
+
+fn draw_channel<C: Canvas>(
+    canvas: &mut C,
+    waveform: &Waveform,
+    channel: usize,
+    mapping: &AmplitudeMapping,
+    area: &PlotArea,
+    zoom: &Zoom,
+) {
+    // Fewer samples than pixels, joined by lines
+    let visible_frames = waveform.visible_frames(zoom);
+    if visible_frames <= area.width as f64 {
+        let (offset, samples) = waveform.compute_samples(channel, zoom);
+        let spacing = area.width as f64 / visible_frames;
+        let last_x = (area.x + area.width - 1) as i64;
+
+        let mut previous = None;
+        for (idx, sample) in samples.iter().enumerate() {
+            let x = area.x as i64 + ((offset + idx as f64) * spacing).round() as i64;
+            let (x, y) = (x.min(last_x), to_y(area, mapping, *sample));
+            if let Some((previous_x, previous_y)) = previous {
+                canvas.line(previous_x, previous_y, x, y, RMS_COLOR);
+            }
+            previous = Some((x, y));
+        }
+        return;
+    }
+
+    // One point per horizontal pixel
+    let points = waveform.compute_points(channel, area.width, zoom);
+
+    for (idx, point) in points.iter().enumerate() {
+        let x = (area.x + idx) as i64;
+
+        canvas.line(
+            x,
+            to_y(area, mapping, point.peak_max),
+            x,
+            to_y(area, mapping, point.peak_min),
+            PEAK_COLOR,
+        );
+        canvas.line(
+            x,
+            to_y(area, mapping, point.rms),
+            x,
+            to_y(area, mapping, -point.rms),
+            RMS_COLOR,
+        );
+    }
+}
+
+fn draw<C: Canvas>(
+    canvas: &mut C,
+    waveform: &Waveform,
+    channels: &[usize],
+    mappings: &[AmplitudeMapping],
+    layout: &[PlotArea],
+    zoom: &Zoom,
+    options: &ExportOptions,
+) {
+    for ((channel, mapping), area) in channels.iter().zip(mappings).zip(layout.iter()) {
+        draw_channel(canvas, waveform, *channel, mapping, area, zoom);
+    }
+
+    if options.axes {
+        for (mapping, area) in mappings.iter().zip(layout.iter()) {
+            let max_count = area.height / (label_height() * 3);
+            draw_vertical_axis(canvas, area, &mapping.labels(max_count));
+        }
+
+        if let Some(area) = layout.last() {
+            let duration = waveform.duration();
+            let max_count = area.width / (label_width("000000") * 2);
+            let labels = time_labels(
+                zoom.start() * duration,
+                (zoom.start() + zoom.length()) * duration,
+                max_count,
+            );
+            draw_horizontal_axis(canvas, area, &labels);
+        }
+    }
+}
+
+/// Write the given channels of a waveform, restricted to the zoom window and
+/// scaled vertically by their mapping, into a png or svg file depending on the
+/// extension of the path
+pub fn export_waveform(
+    waveform: &Waveform,
+    channels: &[usize],
+    mappings: &[AmplitudeMapping],
+    zoom: &Zoom,
+    options: &ExportOptions,
+    path: &Path,
+) -> io::Result<()> {
+    let layout = compute_layout(options, channels.len())?;
+
+    match ExportFormat::from_path(path) {
+        ExportFormat::Png => {
+            let mut img = RgbImage::new(options.width, options.height, BACKGROUND_COLOR);
+            draw(
+                &mut img, waveform, channels, mappings, &layout, zoom, options,
+            );
+            img.save(path)
+        }
+        ExportFormat::Svg => {
+            let mut img = SvgImage::new(options.width, options.height, BACKGROUND_COLOR);
+            draw(
+                &mut img, waveform, channels, mappings, &layout, zoom, options,
+            );
+            img.save(path)
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use sndfile::SndFileError;
 use std::convert::From;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
 use std::time::Duration;
 use structopt::StructOpt;
 use tui::backend::Backend;
@@ -16,24 +17,41 @@ use crate::render::MetadataRenderer;
 use std::io::{Error, ErrorKind};
 
 mod utils;
+use utils::axis::{format_frequency, format_time};
 use utils::bindings;
 use utils::event::{Config, Event, Events};
-use utils::TabsState;
-use utils::Zoom;
+use utils::{from_dbfs, to_dbfs, TabsState};
+use utils::{Cursor, Selection, Zoom};
 
 mod render;
 use render::ChannelsTabs;
 use render::HelperPopup;
+use render::LoudnessRenderer;
 use render::Renderer;
 use render::RendererType;
 use render::RenderingInfo;
+use render::SilencePanel;
 use render::SpectralRenderer;
+use render::StatisticsPopup;
 use render::WaveformRenderer;
 
 mod dsp;
-use dsp::{SidePaddingType, WindowType, PADDING_HELP_TEXT};
+use dsp::{
+    AnalysisCache, ClipEvent, ClippingParameters, Colormap, FrequencyScale, SidePaddingType,
+    SilenceParameters, TruePeakLocation, WindowType, COLORMAP_HELP_TEXT, PADDING_HELP_TEXT,
+};
+
+mod report;
+use report::{Report, ReportFormat};
+
+mod export;
+use export::{
+    default_audio_export_path, default_export_path, export_audio_range, export_spectrogram_file,
+    export_waveform_file, ExportFormat, ExportOptions,
+};
 
 use std::io;
+use termion::event::{Key, MouseButton, MouseEvent};
 use termion::{input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
 use tui::{
     backend::CrosstermBackend,
@@ -42,7 +60,7 @@ use tui::{
     style::Style,
     text::{Span, Spans},
     widgets::{
-        canvas::{Canvas, Rectangle},
+        canvas::{Canvas, Line, Rectangle},
         Block, Borders, Tabs,
     },
     Terminal,
@@ -50,7 +68,13 @@ use tui::{
 
 const WAVEFORM_TAB_IDX: usize = 0;
 const SPECTRAL_TAB_IDX: usize = 1;
-const METADATA_TAB_IDX: usize = 2;
+const LOUDNESS_TAB_IDX: usize = 2;
+const METADATA_TAB_IDX: usize = 3;
+
+/// Step of the spectrogram dB range adjustments
+const DB_STEP: f64 = 5f64;
+/// Width of the silences panel, in characters
+const SILENCES_PANEL_WIDTH: u16 = 56;
 
 struct App<'a> {
     tabs: TabsState<'a>,
@@ -59,7 +83,41 @@ struct App<'a> {
     repaint: bool,
     should_stop: bool,
     zoom: Zoom,
+    // Zoom limit of the current tab, as given to the zoom
+    max_zoom: f64,
     helper: HelperPopup,
+    status: Option<String>,
+    // Text typed in the channel selection prompt, while it is open
+    channel_prompt: Option<String>,
+    // Where things were last drawn, to map the mouse onto them
+    layout: MouseLayout,
+    drag: Option<Drag>,
+    cursor: Option<Cursor>,
+    selection: Option<Selection>,
+    statistics: StatisticsPopup,
+    silences: SilencePanel,
+    // Status of the audio export running in the background, once it is over
+    audio_export: Option<Receiver<String>>,
+}
+
+#[derive(Default)]
+struct MouseLayout {
+    zoom_head: Rect,
+    channels: Rect,
+    // Areas spanning the zoom window along their width
+    time_areas: Vec<Rect>,
+    // Inside of each channel
+    channel_areas: Vec<Rect>,
+}
+
+/// Mouse dragging in progress
+enum Drag {
+    // Panning from the zoom head
+    ZoomHead,
+    // Selecting a region to zoom on, from a column and its relative position
+    Region(u16, f64),
+    // Marking the selection, from its relative position
+    Selection(f64),
 }
 
 #[derive(StructOpt)]
@@ -86,10 +144,159 @@ struct CliArgs {
         possible_values=SidePaddingType::possible_values(),
         help=PADDING_HELP_TEXT)]
     fft_padding_type: SidePaddingType,
+    #[structopt(long = "frequency-scale",
+        parse(try_from_str = FrequencyScale::parse),
+        default_value=FrequencyScale::default(),
+        possible_values=FrequencyScale::possible_values(),)]
+    frequency_scale: FrequencyScale,
+    #[structopt(long = "colormap",
+        parse(try_from_str = Colormap::parse),
+        default_value=Colormap::default(),
+        help=COLORMAP_HELP_TEXT)]
+    colormap: Colormap,
 
     // Normalize option
     #[structopt(short = "n", long = "normalize")]
     normalize: bool,
+
+    // Clipping options
+    #[structopt(
+        long = "clip-threshold",
+        default_value = "-0.01",
+        help = "Level, in dBFS, from which samples count as clipped"
+    )]
+    clip_threshold: f64,
+    #[structopt(
+        long = "clip-run",
+        default_value = "3",
+        help = "Number of consecutive clipped samples making a clip event"
+    )]
+    clip_min_run: usize,
+
+    // Silence options
+    #[structopt(
+        long = "silence-threshold",
+        default_value = "-60",
+        help = "Level, in dBFS, under which samples count as silent"
+    )]
+    silence_threshold: f64,
+    #[structopt(
+        long = "silence-duration",
+        default_value = "0.1",
+        help = "Shortest silence to report, in seconds"
+    )]
+    silence_min_duration: f64,
+
+    // DC offset options
+    #[structopt(
+        long = "dc-threshold",
+        default_value = "-40",
+        help = "DC offset, in dBFS, from which channels are flagged"
+    )]
+    dc_threshold: f64,
+
+    // Headless options
+    #[structopt(
+        short = "a",
+        long = "analyze",
+        help = "Print a report about the file on stdout instead of starting the interface"
+    )]
+    analyze: bool,
+    #[structopt(long = "report-format",
+        parse(try_from_str = ReportFormat::parse),
+        default_value=ReportFormat::default(),
+        possible_values=ReportFormat::possible_values(),)]
+    report_format: ReportFormat,
+
+    // Export options
+    #[structopt(
+        long = "export-spectrogram",
+        parse(from_os_str),
+        help = "Write the spectrogram of the whole file to a png image and exit"
+    )]
+    export_spectrogram: Option<std::path::PathBuf>,
+    #[structopt(
+        long = "export-waveform",
+        parse(from_os_str),
+        help = "Write the waveform of the whole file to a png or svg image and exit"
+    )]
+    export_waveform: Option<std::path::PathBuf>,
+    #[structopt(long = "export-width", default_value = "1920")]
+    export_width: usize,
+    #[structopt(long = "export-height", default_value = "1080")]
+    export_height: usize,
+    #[structopt(long = "export-axes", help = "Draw labelled axes on exported images")]
+    export_axes: bool,
+    #[structopt(long = "export-format",
+        parse(try_from_str = ExportFormat::parse),
+        default_value=ExportFormat::default(),
+        possible_values=ExportFormat::possible_values(),
+        help="Format of the waveform views exported from the interface")]
+    export_format: ExportFormat,
+
+    // Cache options
+    #[structopt(
+        long = "no-cache",
+        help = "Always recompute the analysis instead of loading and storing it in the cache"
+    )]
+    no_cache: bool,
+    #[structopt(
+        long = "cache-size",
+        default_value = "1024",
+        help = "Size limit of the analysis cache, in MiB"
+    )]
+    cache_size: u64,
+}
+
+impl CliArgs {
+    /// The analysis cache, under the XDG cache directory
+    fn cache(&self) -> Option<AnalysisCache> {
+        if self.no_cache {
+            return None;
+        }
+
+        AnalysisCache::default_dir()
+            .map(|dir| AnalysisCache::new(dir, self.cache_size.saturating_mul(1024 * 1024)))
+    }
+
+    fn spectrogram_parameters(&self) -> SpectrogramParameters {
+        SpectrogramParameters {
+            window_size: self.fft_window_size,
+            overlap_rate: self.fft_overlap,
+            db_threshold: self.fft_db_threshold,
+            window_type: self.fft_window_type,
+            side_padding_type: self.fft_padding_type,
+            frequency_scale: self.frequency_scale,
+        }
+    }
+
+    fn clipping_parameters(&self) -> ClippingParameters {
+        ClippingParameters {
+            threshold: self.clip_threshold,
+            min_run: self.clip_min_run,
+        }
+    }
+
+    fn silence_parameters(&self) -> SilenceParameters {
+        SilenceParameters {
+            threshold: self.silence_threshold,
+            min_duration: self.silence_min_duration,
+        }
+    }
+
+    /// DC offset relative to full scale from which channels are flagged
+    fn dc_level(&self) -> f64 {
+        from_dbfs(self.dc_threshold)
+    }
+
+    fn export_options(&self) -> ExportOptions {
+        ExportOptions {
+            width: self.export_width,
+            height: self.export_height,
+            axes: self.export_axes,
+            format: self.export_format,
+        }
+    }
 }
 
 fn draw_tabs<B: Backend>(frame: &mut Frame<'_, B>, area: Rect, app: &App) {
@@ -125,20 +332,34 @@ fn draw_tabs<B: Backend>(frame: &mut Frame<'_, B>, area: Rect, app: &App) {
 fn draw_zoom_head<B: Backend>(
     frame: &mut Frame<'_, B>,
     area: Rect,
-    zoom_start: f64,
-    zoom_len: f64,
+    zoom: &Zoom,
+    selection: Option<&Selection>,
+    status: Option<&str>,
 ) {
     let canva = Canvas::default()
         .background_color(Color::Rgb(16, 16, 16))
-        .block(Block::default().borders(Borders::TOP | Borders::BOTTOM))
+        .block(
+            Block::default()
+                .borders(Borders::TOP | Borders::BOTTOM)
+                .title(status.unwrap_or_default()),
+        )
         .paint(|ctx| {
             ctx.draw(&Rectangle {
-                x: zoom_start,
+                x: zoom.start(),
                 y: 0f64,
-                width: zoom_len,
+                width: zoom.length(),
                 height: 1f64,
                 color: Color::White,
-            })
+            });
+            if let Some(selection) = selection {
+                ctx.draw(&Line {
+                    x1: selection.start(),
+                    y1: 0.5f64,
+                    x2: selection.end(),
+                    y2: 0.5f64,
+                    color: Color::Blue,
+                });
+            }
         })
         .x_bounds([0f64, 1f64])
         .y_bounds([0f64, 1f64]);
@@ -146,6 +367,315 @@ fn draw_zoom_head<B: Backend>(
     frame.render_widget(canva, area);
 }
 
+fn contains(area: &Rect, x: u16, y: u16) -> bool {
+    x >= area.x && x < area.x + area.width && y >= area.y && y < area.y + area.height
+}
+
+/// Relative position of the content under the pointer, within the zoom head
+fn zoom_head_position(area: &Rect, x: u16) -> f64 {
+    (x.saturating_sub(area.x) as f64 + 0.5f64) / area.width as f64
+}
+
+/// Relative position of the content under the pointer, when over the content
+fn content_position(app: &App, x: u16, y: u16) -> Option<f64> {
+    app.layout
+        .time_areas
+        .iter()
+        .find(|area| contains(area, x, y))
+        .map(|area| {
+            let ratio = ((x - area.x) as f64 + 0.5f64) / area.width as f64;
+            app.zoom.start() + ratio * app.zoom.length()
+        })
+}
+
+/// Cursor at the pointer, when over the content. The height is kept when
+/// pointing outside of the channels
+fn cursor_at(app: &App, x: u16, y: u16) -> Option<Cursor> {
+    let position = content_position(app, x, y)?;
+    let height = match app
+        .layout
+        .channel_areas
+        .iter()
+        .find(|area| contains(area, x, y))
+    {
+        Some(area) => ((area.y + area.height - y) as f64 - 0.5f64) / area.height as f64,
+        None => app.cursor.map_or(0.5f64, |cursor| cursor.height()),
+    };
+
+    Some(Cursor::new(position, height))
+}
+
+/// Move the cursor by a number of columns and rows of the view, showing it
+/// first if needed. The view follows the cursor
+fn move_cursor(app: &mut App, columns: f64, rows: f64) {
+    let width = app
+        .layout
+        .time_areas
+        .first()
+        .map_or(1, |area| area.width.max(1));
+    let height = app
+        .layout
+        .channel_areas
+        .first()
+        .map_or(1, |area| area.height.max(1));
+    let center = app.zoom.start() + app.zoom.length() / 2f64;
+    let cursor = app
+        .cursor
+        .get_or_insert_with(|| Cursor::new(center, 0.5f64));
+    cursor.move_by(
+        columns * app.zoom.length() / width as f64,
+        rows / height as f64,
+    );
+
+    let position = cursor.position();
+    if position < app.zoom.start() || position >= app.zoom.start() + app.zoom.length() {
+        app.zoom.center_on(position);
+    }
+    app.status = None;
+}
+
+/// Index of the closest of some frames after the cursor, or before it. The
+/// center of the view stands for the cursor while hidden
+fn closest_frame(
+    app: &App,
+    candidates: impl Iterator<Item = usize>,
+    frames: usize,
+    forward: bool,
+) -> Option<usize> {
+    let current = app
+        .cursor
+        .map_or(app.zoom.start() + app.zoom.length() / 2f64, |c| {
+            c.position()
+        });
+    let current = (current * frames as f64) as usize;
+    let candidates = candidates.enumerate().filter(|(_, frame)| {
+        if forward {
+            *frame > current
+        } else {
+            *frame < current
+        }
+    });
+    let closest = if forward {
+        candidates.min_by_key(|(_, frame)| *frame)
+    } else {
+        candidates.max_by_key(|(_, frame)| *frame)
+    };
+
+    closest.map(|(idx, _)| idx)
+}
+
+/// Show a frame in the waveform view : the cursor goes to it and the view
+/// follows
+fn show_frame(app: &mut App, frame: usize, frames: usize) {
+    // The middle of the frame, safe from rounding errors
+    let position = (frame as f64 + 0.5f64) / frames as f64;
+    let height = app.cursor.map_or(0.5f64, |c| c.height());
+    app.cursor = Some(Cursor::new(position, height));
+    app.zoom.center_on(position);
+    app.tabs.index = WAVEFORM_TAB_IDX;
+}
+
+/// Show the closest of the highest true peaks after the cursor, or before it
+fn jump_to_true_peak(
+    app: &mut App,
+    locations: &[TruePeakLocation],
+    frames: usize,
+    samplerate: f64,
+    forward: bool,
+) {
+    if locations.is_empty() {
+        app.status = Some("No true peak measured yet".to_string());
+        return;
+    }
+
+    let closest = closest_frame(
+        app,
+        locations.iter().map(|location| location.frame),
+        frames,
+        forward,
+    );
+    app.status = Some(match closest {
+        Some(rank) => {
+            let location = locations[rank];
+            show_frame(app, location.frame, frames);
+            format!(
+                "True peak #{} : {:.2} dBTP on {} at {}",
+                rank + 1,
+                to_dbfs(location.peak),
+                app.channels.title(location.channel),
+                format_time(location.frame as f64 / samplerate)
+            )
+        }
+        None if forward => "No true peak after the cursor".to_string(),
+        None => "No true peak before the cursor".to_string(),
+    });
+}
+
+/// Show the closest clip event of the displayed channels after the cursor, or
+/// before it
+fn jump_to_clip(
+    app: &mut App,
+    events: &[ClipEvent],
+    frames: usize,
+    samplerate: f64,
+    forward: bool,
+) {
+    let activated: Vec<usize> = app
+        .channels
+        .activated()
+        .iter()
+        .map(|(idx, _)| *idx)
+        .collect();
+    let events: Vec<&ClipEvent> = events
+        .iter()
+        .filter(|event| activated.contains(&event.channel))
+        .collect();
+    if events.is_empty() {
+        app.status = Some("No clip event on the displayed channels".to_string());
+        return;
+    }
+
+    let closest = closest_frame(app, events.iter().map(|event| event.start), frames, forward);
+    app.status = Some(match closest {
+        Some(idx) => {
+            let event = events[idx];
+            show_frame(app, event.start, frames);
+            format!(
+                "Clip event {}/{} : {} samples on {} at {}",
+                idx + 1,
+                events.len(),
+                event.length,
+                app.channels.title(event.channel),
+                format_time(event.start as f64 / samplerate)
+            )
+        }
+        None if forward => "No clip event after the cursor".to_string(),
+        None => "No clip event before the cursor".to_string(),
+    });
+}
+
+/// Zoom with the wheel, pan by dragging the zoom head, toggle channels by
+/// clicking their name, place the cursor by clicking the content, zoom on a
+/// region by dragging over it and select a region by right-dragging over it
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    match mouse {
+        // Terminal coordinates start at 1
+        MouseEvent::Press(MouseButton::WheelUp, x, y) => {
+            match content_position(app, x - 1, y - 1) {
+                Some(position) => app.zoom.zoom_in_at(position),
+                None => app.zoom.zoom_in(),
+            }
+        }
+        MouseEvent::Press(MouseButton::WheelDown, x, y) => {
+            match content_position(app, x - 1, y - 1) {
+                Some(position) => app.zoom.zoom_out_at(position),
+                None => app.zoom.zoom_out(),
+            }
+        }
+        MouseEvent::Press(MouseButton::Left, x, y) => {
+            let (x, y) = (x - 1, y - 1);
+            if contains(&app.layout.zoom_head, x, y) {
+                app.zoom
+                    .center_on(zoom_head_position(&app.layout.zoom_head, x));
+                app.drag = Some(Drag::ZoomHead);
+            } else if let Some(channel) = app.channels.channel_at(app.layout.channels, x, y) {
+                app.channels.update(channel);
+            } else if let Some(position) = content_position(app, x, y) {
+                app.drag = Some(Drag::Region(x, position));
+            }
+        }
+        MouseEvent::Press(MouseButton::Right, x, y) => {
+            if let Some(position) = content_position(app, x - 1, y - 1) {
+                app.drag = Some(Drag::Selection(position));
+            }
+        }
+        MouseEvent::Hold(x, y) => match app.drag {
+            Some(Drag::ZoomHead) => app
+                .zoom
+                .center_on(zoom_head_position(&app.layout.zoom_head, x - 1)),
+            Some(Drag::Selection(start)) => {
+                if let Some(end) = content_position(app, x - 1, y - 1) {
+                    app.selection = Some(Selection::new(start, end));
+                }
+            }
+            _ => return,
+        },
+        MouseEvent::Release(x, y) => {
+            let (x, y) = (x - 1, y - 1);
+            match (app.drag.take(), content_position(app, x, y)) {
+                // A simple click places the cursor there
+                (Some(Drag::Region(start_x, _)), Some(_)) if x == start_x => {
+                    app.cursor = cursor_at(app, x, y);
+                    app.status = None;
+                }
+                (Some(Drag::Region(_, start)), Some(end)) => app.zoom.zoom_on(start, end),
+                (Some(Drag::Selection(start)), Some(end)) if end != start => {
+                    app.selection = Some(Selection::new(start, end));
+                }
+                _ => {}
+            }
+        }
+        _ => return,
+    }
+
+    app.repaint = true;
+}
+
+/// Edit the channel selection prompt, and apply it when validated
+fn handle_channel_prompt(app: &mut App, input: Key) {
+    let text = match app.channel_prompt.as_mut() {
+        Some(text) => text,
+        None => return,
+    };
+
+    match input {
+        Key::Char('\n') => {
+            if let Err(err) = app.channels.select(text) {
+                app.status = Some(err);
+            } else {
+                app.status = None;
+            }
+            app.channel_prompt = None;
+        }
+        Key::Esc => app.channel_prompt = None,
+        Key::Backspace => {
+            text.pop();
+        }
+        Key::Char(c) => text.push(c),
+        _ => {}
+    }
+    app.repaint = true;
+}
+
+/// Bottom of the view, one line per channel
+fn statistics_layout(area: Rect, channels: usize) -> Rect {
+    let height = u16::min(channels as u16 + 2, area.height / 2).max(3);
+
+    Rect {
+        x: area.x + area.width / 8,
+        y: area.y + area.height.saturating_sub(height),
+        width: area.width - area.width / 4,
+        height: u16::min(height, area.height),
+    }
+}
+
+/// View and silences panel, side by side
+fn silences_layout(area: Rect) -> (Rect, Rect) {
+    let width = u16::min(SILENCES_PANEL_WIDTH, area.width / 3);
+
+    (
+        Rect {
+            width: area.width - width,
+            ..area
+        },
+        Rect {
+            x: area.x + area.width - width,
+            width,
+            ..area
+        },
+    )
+}
+
 fn helper_layout(area: Rect) -> Rect {
     let x_offset = area.width / 4;
     let y_offset = area.height / 4;
@@ -162,17 +692,6 @@ fn main() -> Result<(), io::Error> {
     // Get cli args
     let args = CliArgs::from_args();
 
-    let stdout = io::stdout().into_raw_mode()?;
-    let stdout = MouseTerminal::from(stdout);
-    let stdout = AlternateScreen::from(stdout);
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    const TAB_SIZE: u16 = 3;
-
-    let events = Events::with_config(Config {
-        tick_rate: Duration::from_millis(100),
-    });
-
     // Check file
     let snd_res = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto).from_path(&args.path);
     if let Err(err) = snd_res {
@@ -187,46 +706,104 @@ fn main() -> Result<(), io::Error> {
         });
     }
 
-    let snd = snd_res.unwrap();
-    let channels = snd.get_channels();
-    if channels > 9usize {
-        let err = Error::new(
-            ErrorKind::InvalidInput,
-            "Audeye does not support configuration with more than 9 channels",
-        );
-        return Err(err);
+    // Headless mode, no need for a terminal
+    if args.analyze {
+        let report = Report::new(
+            &args.path,
+            args.clipping_parameters(),
+            args.silence_parameters(),
+            args.dc_level(),
+        )
+        .map_err(|err| Error::other(err.to_string()))?;
+        println!("{}", report.render(args.report_format));
+    }
+
+    if let Some(export_path) = &args.export_spectrogram {
+        export_spectrogram_file(
+            &args.path,
+            args.spectrogram_parameters(),
+            &args.colormap,
+            args.normalize,
+            &args.export_options(),
+            export_path,
+        )?;
     }
 
+    if let Some(export_path) = &args.export_waveform {
+        export_waveform_file(
+            &args.path,
+            args.normalize,
+            &args.export_options(),
+            export_path,
+        )?;
+    }
+
+    if args.analyze || args.export_spectrogram.is_some() || args.export_waveform.is_some() {
+        return Ok(());
+    }
+
+    let mut snd = snd_res.unwrap();
+    let channels = snd.get_channels();
+    let frames = snd.len().unwrap() as usize;
+    let samplerate = snd.get_samplerate() as f64;
+
+    let stdout = io::stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(stdout);
+    let stdout = AlternateScreen::from(stdout);
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    const TAB_SIZE: u16 = 3;
+
+    let events = Events::with_config(Config {
+        tick_rate: Duration::from_millis(100),
+    });
+
     // Create the renderers
-    let mut waveform = RendererType::Waveform(WaveformRenderer::new(&args.path, args.normalize));
-    let mut spectral = RendererType::Spectral(SpectralRenderer::new(
+    let cache = args.cache();
+    let mut waveform = RendererType::Waveform(Box::new(WaveformRenderer::new(
         &args.path,
-        SpectrogramParameters {
-            window_size: args.fft_window_size,
-            overlap_rate: args.fft_overlap,
-            db_threshold: args.fft_db_threshold,
-            window_type: args.fft_window_type,
-            side_padding_type: args.fft_padding_type,
-        },
         args.normalize,
-    ));
-    let mut metadata_render = RendererType::Metadata(Box::new(MetadataRenderer::new(&args.path)));
+        args.clipping_parameters(),
+        args.dc_level(),
+        cache.clone(),
+    )));
+    let mut spectral = RendererType::Spectral(Box::new(SpectralRenderer::new(
+        &args.path,
+        args.spectrogram_parameters(),
+        &args.colormap,
+        args.normalize,
+        cache.clone(),
+    )));
+    let mut loudness =
+        RendererType::Loudness(Box::new(LoudnessRenderer::new(&args.path, cache.clone())));
+    let mut metadata_render =
+        RendererType::Metadata(Box::new(MetadataRenderer::new(&args.path, cache.clone())));
 
-    // Build the app
-    // Compute the max zoom allowed
-    let res_max = usize::min(
-        waveform.max_width_resolution(),
-        spectral.max_width_resolution(),
-    ) as f64;
+    // Build the app, the zoom limit follows the current tab
+    let max_zoom = terminal.size()?.width as f64 / waveform.max_width_resolution() as f64;
 
     let mut app = App {
-        tabs: TabsState::new(vec!["Waveform", "Spectral", "Metadata"]),
+        tabs: TabsState::new(vec!["Waveform", "Spectral", "Loudness", "Metadata"]),
         channels: ChannelsTabs::new(channels),
         previous_frame: Rect::default(),
         repaint: true,
         should_stop: false,
-        zoom: Zoom::new(terminal.size()?.width as f64 / res_max).unwrap(),
+        zoom: Zoom::new(max_zoom).unwrap(),
+        max_zoom,
         helper: HelperPopup::default(),
+        status: None,
+        channel_prompt: None,
+        layout: MouseLayout::default(),
+        drag: None,
+        cursor: None,
+        selection: None,
+        statistics: StatisticsPopup::new(
+            &args.path,
+            args.clipping_parameters().threshold,
+            cache.clone(),
+        ),
+        silences: SilencePanel::new(&args.path, args.silence_parameters(), cache),
+        audio_export: None,
     };
 
     // let mut zoom_head = ZoomHead::new(&mut app.zoom);
@@ -238,33 +815,67 @@ fn main() -> Result<(), io::Error> {
         let renderer = match app.tabs.index {
             WAVEFORM_TAB_IDX => &mut waveform,
             SPECTRAL_TAB_IDX => &mut spectral,
+            LOUDNESS_TAB_IDX => &mut loudness,
             METADATA_TAB_IDX => &mut metadata_render,
             _ => unreachable!(),
         };
 
         if tsize != app.previous_frame {
             app.repaint = true;
-            let new_max_zoom = terminal.size()?.width as f64 / res_max;
-            app.zoom.update_zoom_max(new_max_zoom);
         }
 
-        if app.repaint || renderer.needs_redraw() {
+        // Each tab has its own zoom limit, which can also change with the size
+        // or the content of the tab
+        let max_zoom = tsize.width as f64 / renderer.max_width_resolution() as f64;
+        if max_zoom != app.max_zoom {
+            app.max_zoom = max_zoom;
+            app.zoom.update_zoom_max(max_zoom);
+            app.repaint = true;
+        }
+
+        app.statistics.update(app.selection);
+
+        if app.repaint
+            | renderer.needs_redraw()
+            | app.statistics.needs_redraw()
+            | app.silences.needs_redraw()
+        {
             terminal.draw(|f| {
                 // Chunks settings
                 let size = f.size();
 
-                // Build rendering info structure for the renderers
-                let rendering_info = RenderingInfo {
-                    activated_channels: app.channels.activated(),
-                    zoom: &app.zoom,
-                };
-
                 // Setup headers and view layout
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
                     .constraints([Constraint::Length(TAB_SIZE), Constraint::Min(3)])
                     .split(size);
 
+                // Silences panel, on the right of the view. Drawn first, as the
+                // renderers borrow its regions to shade them
+                let view_rect = if app.silences.is_visible() {
+                    let (view_rect, silences_rect) = silences_layout(chunks[1]);
+                    let panel_info = RenderingInfo {
+                        activated_channels: app.channels.activated(),
+                        zoom: &app.zoom,
+                        cursor: None,
+                        selection: None,
+                        silence: None,
+                    };
+                    app.silences.draw(f, &panel_info, silences_rect);
+                    view_rect
+                } else {
+                    chunks[1]
+                };
+
+                // Build rendering info structure for the renderers
+                let rendering_info = RenderingInfo {
+                    activated_channels: app.channels.activated(),
+                    zoom: &app.zoom,
+                    cursor: app.cursor.as_ref(),
+                    selection: app.selection.as_ref(),
+                    silence: app.silences.silence(),
+                };
+
                 let header_chunks = Layout::default()
                     .direction(Direction::Horizontal)
                     .constraints([
@@ -280,11 +891,39 @@ fn main() -> Result<(), io::Error> {
                 // Channel tabs
                 app.channels.render(f, header_chunks[2]);
 
-                // Zoom head
-                draw_zoom_head(f, header_chunks[1], app.zoom.start(), app.zoom.length());
+                // Zoom head, titled by the prompt while it is open, then by the
+                // latest status or the values under the cursor
+                let status = match (&app.channel_prompt, &app.status, &app.cursor) {
+                    (Some(text), _, _) => Some(format!("Channels (e.g. 1-4,9) : {}_", text)),
+                    (None, Some(status), _) => Some(status.clone()),
+                    (None, None, Some(cursor)) => renderer.cursor_readout(&rendering_info, cursor),
+                    (None, None, None) => None,
+                };
+                draw_zoom_head(
+                    f,
+                    header_chunks[1],
+                    &app.zoom,
+                    app.selection.as_ref(),
+                    status.as_deref(),
+                );
 
                 // Renderer view drawing
-                renderer.draw(f, &rendering_info, chunks[1]);
+                renderer.draw(f, &rendering_info, view_rect);
+
+                app.layout = MouseLayout {
+                    zoom_head: header_chunks[1],
+                    channels: header_chunks[2],
+                    time_areas: renderer.time_areas(&rendering_info, view_rect),
+                    channel_areas: renderer.channel_areas(&rendering_info, view_rect),
+                };
+
+                // Statistics of the selection
+                if app.statistics.is_visible() {
+                    let statistics_rect =
+                        statistics_layout(view_rect, rendering_info.activated_channels.len());
+                    f.render_widget(Clear, statistics_rect);
+                    app.statistics.draw(f, &rendering_info, statistics_rect);
+                }
 
                 // Helper menu
                 if app.helper.is_visible() {
@@ -299,10 +938,33 @@ fn main() -> Result<(), io::Error> {
         app.previous_frame = tsize;
         app.repaint = false;
 
+        // Replace the "Computing" status once the new spectrogram is over
+        if let RendererType::Spectral(renderer) = &mut spectral {
+            if let Some(status) = renderer.take_recompute_status() {
+                app.status = Some(status);
+                app.repaint = true;
+            }
+        }
+
+        // Show the result of the audio export once it is over
+        let export_status = app.audio_export.as_ref().map(|rx| rx.try_recv());
+        match export_status {
+            Some(Ok(status)) => {
+                app.status = Some(status);
+                app.audio_export = None;
+                app.repaint = true;
+            }
+            Some(Err(TryRecvError::Disconnected)) => app.audio_export = None,
+            Some(Err(TryRecvError::Empty)) | None => {}
+        }
+
         loop {
             let event = events.next().unwrap();
 
             match event {
+                Event::Input(input) if app.channel_prompt.is_some() => {
+                    handle_channel_prompt(&mut app, input);
+                }
                 Event::Input(input) => match input {
                     bindings::QUIT => {
                         app.should_stop = true;
@@ -316,45 +978,57 @@ fn main() -> Result<(), io::Error> {
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_1 => {
-                        app.channels.update(0);
+                        app.channels.update_in_page(0);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_2 => {
-                        app.channels.update(1);
+                        app.channels.update_in_page(1);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_3 => {
-                        app.channels.update(2);
+                        app.channels.update_in_page(2);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_4 => {
-                        app.channels.update(3);
+                        app.channels.update_in_page(3);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_5 => {
-                        app.channels.update(4);
+                        app.channels.update_in_page(4);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_6 => {
-                        app.channels.update(5);
+                        app.channels.update_in_page(5);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_7 => {
-                        app.channels.update(6);
+                        app.channels.update_in_page(6);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_8 => {
-                        app.channels.update(7);
+                        app.channels.update_in_page(7);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_SELECTOR_9 => {
-                        app.channels.update(8);
+                        app.channels.update_in_page(8);
                         app.repaint = true;
                     }
                     bindings::CHANNEL_RESET => {
                         app.channels.reset();
                         app.repaint = true;
                     }
+                    bindings::NEXT_CHANNEL_PAGE => {
+                        app.channels.next_page();
+                        app.repaint = true;
+                    }
+                    bindings::PREVIOUS_CHANNEL_PAGE => {
+                        app.channels.previous_page();
+                        app.repaint = true;
+                    }
+                    bindings::CHANNEL_PROMPT => {
+                        app.channel_prompt = Some(String::new());
+                        app.repaint = true;
+                    }
                     bindings::MOVE_LEFT => {
                         app.zoom.move_left();
                         app.repaint = true;
@@ -371,12 +1045,283 @@ fn main() -> Result<(), io::Error> {
                         app.zoom.zoom_in();
                         app.repaint = true;
                     }
+                    bindings::CURSOR => {
+                        app.cursor = match app.cursor {
+                            Some(_) => None,
+                            None => Some(Cursor::new(
+                                app.zoom.start() + app.zoom.length() / 2f64,
+                                0.5f64,
+                            )),
+                        };
+                        app.status = None;
+                        app.repaint = true;
+                    }
+                    bindings::CURSOR_LEFT => {
+                        move_cursor(&mut app, -1f64, 0f64);
+                        app.repaint = true;
+                    }
+                    bindings::CURSOR_RIGHT => {
+                        move_cursor(&mut app, 1f64, 0f64);
+                        app.repaint = true;
+                    }
+                    bindings::CURSOR_UP => {
+                        move_cursor(&mut app, 0f64, 1f64);
+                        app.repaint = true;
+                    }
+                    bindings::CURSOR_DOWN => {
+                        move_cursor(&mut app, 0f64, -1f64);
+                        app.repaint = true;
+                    }
+                    bindings::SELECTION_IN => {
+                        let position = app.cursor.map_or(app.zoom.start(), |c| c.position());
+                        app.selection = Some(match app.selection {
+                            Some(selection) => selection.with_start(position),
+                            None => Selection::new(position, 1f64),
+                        });
+                        app.repaint = true;
+                    }
+                    bindings::SELECTION_OUT => {
+                        let position = app
+                            .cursor
+                            .map_or(app.zoom.start() + app.zoom.length(), |c| c.position());
+                        app.selection = Some(match app.selection {
+                            Some(selection) => selection.with_end(position),
+                            None => Selection::new(0f64, position),
+                        });
+                        app.repaint = true;
+                    }
+                    bindings::ZOOM_SELECTION => {
+                        if let Some(selection) = app.selection {
+                            app.zoom.zoom_on(selection.start(), selection.end());
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::CLEAR_SELECTION => {
+                        app.selection = None;
+                        app.repaint = true;
+                    }
+                    bindings::NEXT_TRUE_PEAK | bindings::PREVIOUS_TRUE_PEAK => {
+                        if let RendererType::Metadata(renderer) = &mut metadata_render {
+                            jump_to_true_peak(
+                                &mut app,
+                                renderer.true_peak_locations(),
+                                frames,
+                                samplerate,
+                                input == bindings::NEXT_TRUE_PEAK,
+                            );
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::NEXT_CLIP | bindings::PREVIOUS_CLIP => {
+                        if let RendererType::Waveform(renderer) = &mut waveform {
+                            jump_to_clip(
+                                &mut app,
+                                renderer.clip_events(),
+                                frames,
+                                samplerate,
+                                input == bindings::NEXT_CLIP,
+                            );
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::STATISTICS => {
+                        app.statistics.set_visible(!app.statistics.is_visible());
+                        app.repaint = true;
+                    }
+                    bindings::SILENCES => {
+                        app.silences.set_visible(!app.silences.is_visible());
+                        app.repaint = true;
+                    }
+                    bindings::EXPORT_SELECTION => {
+                        app.status = Some(match app.selection {
+                            Some(selection) => {
+                                let (start, end) = selection.frames(frames);
+                                let path = default_audio_export_path(&args.path, "selection");
+                                // Copying a long selection takes a while, keep
+                                // handling the inputs meanwhile
+                                let (status_tx, status_rx) = channel();
+                                let input = args.path.clone();
+                                std::thread::spawn(move || {
+                                    let status = match export_audio_range(&input, start, end, &path)
+                                    {
+                                        Ok(_) => format!("Exported to {}", path.display()),
+                                        Err(err) => format!("Export failed : {}", err),
+                                    };
+                                    let _ = status_tx.send(status);
+                                });
+                                app.audio_export = Some(status_rx);
+                                "Exporting the selection...".to_string()
+                            }
+                            None => "Nothing selected".to_string(),
+                        });
+                        app.repaint = true;
+                    }
+                    bindings::EXPORT => {
+                        let channels: Vec<usize> = app
+                            .channels
+                            .activated()
+                            .iter()
+                            .map(|(idx, _)| *idx)
+                            .collect();
+                        let options = args.export_options();
+
+                        let result = match (app.tabs.index, &mut waveform, &mut spectral) {
+                            (WAVEFORM_TAB_IDX, RendererType::Waveform(renderer), _) => {
+                                let path =
+                                    default_export_path(&args.path, "waveform", options.format);
+                                Some(
+                                    renderer
+                                        .export(&channels, &app.zoom, &options, &path)
+                                        .map(|_| path),
+                                )
+                            }
+                            (SPECTRAL_TAB_IDX, _, RendererType::Spectral(renderer)) => {
+                                // Spectrograms are only exported as images
+                                let path = default_export_path(
+                                    &args.path,
+                                    "spectrogram",
+                                    ExportFormat::Png,
+                                );
+                                Some(
+                                    renderer
+                                        .export(&channels, &app.zoom, &options, &path)
+                                        .map(|_| path),
+                                )
+                            }
+                            _ => None,
+                        };
+
+                        if let Some(result) = result {
+                            app.status = Some(match result {
+                                Ok(path) => format!("Exported to {}", path.display()),
+                                Err(err) => format!("Export failed : {}", err),
+                            });
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::FREQUENCY_SCALE => {
+                        if let (SPECTRAL_TAB_IDX, RendererType::Spectral(renderer)) =
+                            (app.tabs.index, &mut spectral)
+                        {
+                            let scale = renderer.next_frequency_scale();
+                            app.status = Some(format!("Frequency scale : {}", scale.name()));
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::VERTICAL_ZOOM_IN
+                    | bindings::VERTICAL_ZOOM_OUT
+                    | bindings::MOVE_UP
+                    | bindings::MOVE_DOWN => match (app.tabs.index, &mut waveform, &mut spectral) {
+                        (WAVEFORM_TAB_IDX, RendererType::Waveform(renderer), _) => {
+                            // Moving up and down is only for the frequencies
+                            let top = match input {
+                                bindings::VERTICAL_ZOOM_IN => Some(renderer.vertical_zoom_in()),
+                                bindings::VERTICAL_ZOOM_OUT => Some(renderer.vertical_zoom_out()),
+                                _ => None,
+                            };
+                            if let Some(top) = top {
+                                app.status = Some(format!("Amplitude top : {:.0} dBFS", top));
+                                app.repaint = true;
+                            }
+                        }
+                        (SPECTRAL_TAB_IDX, _, RendererType::Spectral(renderer)) => {
+                            // Vertical moves and zooms are along the frequencies
+                            match input {
+                                bindings::VERTICAL_ZOOM_IN => renderer.frequency_zoom_in(),
+                                bindings::VERTICAL_ZOOM_OUT => renderer.frequency_zoom_out(),
+                                bindings::MOVE_UP => renderer.move_frequency_up(),
+                                _ => renderer.move_frequency_down(),
+                            }
+                            if let Some((min_hz, max_hz)) = renderer.frequency_range() {
+                                app.status = Some(format!(
+                                    "Frequencies : {} - {} Hz",
+                                    format_frequency(min_hz),
+                                    format_frequency(max_hz)
+                                ));
+                            }
+                            app.repaint = true;
+                        }
+                        _ => {}
+                    },
+                    bindings::AMPLITUDE_SCALE | bindings::AUTO_FIT => {
+                        if let (WAVEFORM_TAB_IDX, RendererType::Waveform(renderer)) =
+                            (app.tabs.index, &mut waveform)
+                        {
+                            app.status = Some(match input {
+                                bindings::AMPLITUDE_SCALE => format!(
+                                    "Amplitude scale : {}",
+                                    renderer.next_amplitude_scale().name()
+                                ),
+                                _ => match renderer.toggle_auto_fit() {
+                                    true => "Amplitude fitted to the view".to_string(),
+                                    false => "Amplitude fit disabled".to_string(),
+                                },
+                            });
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::COLORMAP => {
+                        if let (SPECTRAL_TAB_IDX, RendererType::Spectral(renderer)) =
+                            (app.tabs.index, &mut spectral)
+                        {
+                            let colormap = renderer.next_colormap();
+                            app.status = Some(format!("Colormap : {}", colormap.name()));
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::DB_FLOOR_DOWN
+                    | bindings::DB_FLOOR_UP
+                    | bindings::DB_CEILING_DOWN
+                    | bindings::DB_CEILING_UP => {
+                        if let (SPECTRAL_TAB_IDX, RendererType::Spectral(renderer)) =
+                            (app.tabs.index, &mut spectral)
+                        {
+                            let range = match input {
+                                bindings::DB_FLOOR_DOWN => renderer.shift_db_floor(-DB_STEP),
+                                bindings::DB_FLOOR_UP => renderer.shift_db_floor(DB_STEP),
+                                bindings::DB_CEILING_DOWN => renderer.shift_db_ceiling(-DB_STEP),
+                                _ => renderer.shift_db_ceiling(DB_STEP),
+                            };
+                            app.status =
+                                Some(format!("dB range : {} / {}", range.floor, range.ceiling));
+                            app.repaint = true;
+                        }
+                    }
+                    bindings::FFT_WINDOW_SIZE_UP
+                    | bindings::FFT_WINDOW_SIZE_DOWN
+                    | bindings::FFT_OVERLAP
+                    | bindings::FFT_WINDOW_TYPE
+                    | bindings::FFT_PADDING_TYPE => {
+                        if let (SPECTRAL_TAB_IDX, RendererType::Spectral(renderer)) =
+                            (app.tabs.index, &mut spectral)
+                        {
+                            let mut parameters = renderer.parameters();
+                            match input {
+                                bindings::FFT_WINDOW_SIZE_UP => parameters.next_window_size(),
+                                bindings::FFT_WINDOW_SIZE_DOWN => parameters.previous_window_size(),
+                                bindings::FFT_OVERLAP => parameters.next_overlap_rate(),
+                                bindings::FFT_WINDOW_TYPE => {
+                                    parameters.window_type = parameters.window_type.next()
+                                }
+                                _ => {
+                                    parameters.side_padding_type =
+                                        parameters.side_padding_type.next()
+                                }
+                            }
+                            renderer.recompute(parameters);
+                            app.status = Some(format!("Computing : {}", parameters.summary()));
+                            app.repaint = true;
+                        }
+                    }
                     bindings::HELP => {
                         app.helper.set_visible(!app.helper.is_visible());
                         app.repaint = true;
                     }
                     _ => {}
                 },
+                Event::Mouse(mouse) => {
+                    handle_mouse(&mut app, mouse);
+                }
                 Event::Tick => {
                     break;
                 }
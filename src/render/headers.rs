@@ -38,9 +38,15 @@ use tui::widgets::{Block, Borders, Paragraph};
 //     }
 // }
 
+/// Number of channels toggled by the number keys, per page
+pub const CHANNELS_PER_PAGE: usize = 9;
+/// Drawn between the names of the channels
+const SEPARATOR: &str = " | ";
+
 pub struct ChannelsTabs {
     titles: Vec<String>,
     activated: BTreeSet<usize>,
+    page: usize,
 }
 
 impl<'a> ChannelsTabs {
@@ -54,26 +60,43 @@ impl<'a> ChannelsTabs {
         Self {
             titles: Self::get_channels_titles(count),
             activated: set,
+            page: 0,
         }
     }
 
     pub fn render<B: Backend>(&self, frame: &mut Frame<'_, B>, area: Rect) {
         // Styles
-        let separator = Span::raw(" | ");
+        let separator = Span::raw(SEPARATOR);
         let selected_style = Style::default()
             .add_modifier(Modifier::BOLD)
             .fg(Color::Green);
         let not_selected_style = Style::default().fg(Color::Gray);
 
-        // Title block
+        // Title block, with the channels of the page when there are several
+        let page_range = self.page_range();
+        let title = if self.page_count() > 1 {
+            format!(
+                "Channels {}-{} of {}",
+                page_range.start + 1,
+                page_range.end,
+                self.count()
+            )
+        } else {
+            "Channels".to_string()
+        };
         let block = Block::default()
             .borders(Borders::RIGHT | Borders::TOP | Borders::BOTTOM)
-            .title("Channels")
+            .title(title)
             .title_alignment(Alignment::Right);
 
-        // Create styled channels names
+        // Create styled channels names, only the ones that fit
+        let states = self.states();
         let mut span_vec: Vec<Span> = vec![];
-        for (activated, name) in self.states() {
+        for (activated, name) in self
+            .names_layout(area)
+            .into_iter()
+            .map(|(channel, _)| states[channel])
+        {
             let span = if activated {
                 Span::styled(name, selected_style)
             } else {
@@ -98,6 +121,77 @@ impl<'a> ChannelsTabs {
         self.titles.len()
     }
 
+    /// Channels of the page whose names fit in `area`, along with the column
+    /// where each name is drawn. Names are on the line under the top border,
+    /// aligned to the right border
+    fn names_layout(&self, area: Rect) -> Vec<(usize, u16)> {
+        let inner_width = area.width.saturating_sub(1) as usize;
+        let name_width = |channel: usize| self.titles[channel].chars().count();
+
+        let mut channels = vec![];
+        let mut text_width = 0usize;
+        for channel in self.page_range() {
+            let separator_width = if channels.is_empty() {
+                0
+            } else {
+                SEPARATOR.len()
+            };
+            if text_width + separator_width + name_width(channel) > inner_width {
+                break;
+            }
+            text_width += separator_width + name_width(channel);
+            channels.push(channel);
+        }
+
+        let mut start = area.x as usize + inner_width - text_width;
+        channels
+            .into_iter()
+            .map(|channel| {
+                let x = start as u16;
+                start += name_width(channel) + SEPARATOR.len();
+                (channel, x)
+            })
+            .collect()
+    }
+
+    /// The channel whose name is drawn at the given position, when rendered
+    /// in `area`
+    pub fn channel_at(&self, area: Rect, x: u16, y: u16) -> Option<usize> {
+        if y != area.y + 1 {
+            return None;
+        }
+
+        self.names_layout(area)
+            .into_iter()
+            .find(|(channel, start)| {
+                x >= *start && x < *start + self.titles[*channel].chars().count() as u16
+            })
+            .map(|(channel, _)| channel)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.count().div_ceil(CHANNELS_PER_PAGE)
+    }
+
+    /// Channels of the current page
+    fn page_range(&self) -> std::ops::Range<usize> {
+        let start = self.page * CHANNELS_PER_PAGE;
+        start..usize::min(start + CHANNELS_PER_PAGE, self.count())
+    }
+
+    pub fn next_page(&mut self) {
+        self.page = (self.page + 1) % self.page_count();
+    }
+
+    pub fn previous_page(&mut self) {
+        self.page = (self.page + self.page_count() - 1) % self.page_count();
+    }
+
+    /// Name of a channel
+    pub fn title(&'a self, idx: usize) -> &'a str {
+        self.titles[idx].as_str()
+    }
+
     pub fn activated(&'a self) -> Vec<(usize, &'a str)> {
         self.activated
             .iter()
@@ -132,13 +226,44 @@ impl<'a> ChannelsTabs {
         }
     }
 
+    /// Toggle a channel of the current page
+    pub fn update_in_page(&mut self, page_idx: usize) {
+        if page_idx < CHANNELS_PER_PAGE {
+            self.update(self.page * CHANNELS_PER_PAGE + page_idx);
+        }
+    }
+
+    /// Activate only the channels given as 1-based ranges, like "1-4,9"
+    pub fn select(&mut self, selection: &str) -> Result<(), String> {
+        let mut activated = BTreeSet::new();
+
+        for range in selection.split(',').map(|range| range.trim()) {
+            let (first, last) = match range.split_once('-') {
+                Some((first, last)) => (first.trim(), last.trim()),
+                None => (range, range),
+            };
+            let parse = |value: &str| match value.parse::<usize>() {
+                Ok(channel) if channel >= 1 && channel <= self.count() => Ok(channel - 1),
+                _ => Err(format!("Invalid channel \"{}\"", value)),
+            };
+            let (first, last) = (parse(first)?, parse(last)?);
+            if first > last {
+                return Err(format!("Invalid range \"{}\"", range));
+            }
+            activated.extend(first..=last);
+        }
+
+        self.activated = activated;
+        Ok(())
+    }
+
     pub fn reset(&mut self) {
         for idx in 0..self.count() {
             self.activated.insert(idx);
         }
     }
 
-    fn get_channels_titles(count: usize) -> Vec<String> {
+    pub fn get_channels_titles(count: usize) -> Vec<String> {
         match count {
             0usize => panic!(),
             1_usize => vec!["Mono"].into_iter().map(|v| v.to_string()).collect(), // mono
@@ -163,7 +288,72 @@ impl<'a> ChannelsTabs {
                 .into_iter()
                 .map(|v| v.to_string())
                 .collect(), // 7.1
-            _ => (0..count).map(|idx| format!["Channel {:?}", idx]).collect(),
+            _ => (0..count).map(|idx| format!("Ch {}", idx + 1)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelsTabs;
+    use tui::layout::Rect;
+
+    #[test]
+    fn select() {
+        let mut tabs = ChannelsTabs::new(16);
+
+        tabs.select("1-4, 9,12-12").unwrap();
+        let activated: Vec<usize> = tabs.activated().iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(activated, vec![0, 1, 2, 3, 8, 11]);
+
+        for invalid in ["", "0", "17", "4-2", "a", "1-", "1,,2"] {
+            assert!(tabs.select(invalid).is_err());
         }
+        // Failed selections keep the previous one
+        assert_eq!(tabs.activated().len(), 6);
+    }
+
+    #[test]
+    fn channel_at() {
+        let tabs = ChannelsTabs::new(2);
+        let area = Rect::new(0, 0, 40, 3);
+        let names: String = tabs.titles.join(" | ");
+        let start = 39 - names.chars().count() as u16;
+
+        assert_eq!(tabs.channel_at(area, start, 1), Some(0));
+        assert_eq!(tabs.channel_at(area, 38, 1), Some(1));
+        assert_eq!(tabs.channel_at(area, start, 0), None);
+        assert_eq!(tabs.channel_at(area, start - 1, 1), None);
+        let separator = start + tabs.titles[0].chars().count() as u16 + 1;
+        assert_eq!(tabs.channel_at(area, separator, 1), None);
+    }
+
+    #[test]
+    fn pages() {
+        let mut tabs = ChannelsTabs::new(20);
+        assert_eq!(tabs.page_count(), 3);
+
+        tabs.previous_page();
+        assert_eq!(tabs.page_range(), 18..20);
+        tabs.update_in_page(1);
+        assert_eq!(tabs.activated().len(), 19);
+        // Out of the last page
+        tabs.update_in_page(5);
+        assert_eq!(tabs.activated().len(), 19);
+
+        tabs.next_page();
+        assert_eq!(tabs.page_range(), 0..9);
+    }
+
+    #[test]
+    fn channel_at_narrow() {
+        // Only "Ch 1 | Ch 2 | Ch 3" fits, at the right of the area
+        let tabs = ChannelsTabs::new(16);
+        let area = Rect::new(10, 0, 20, 3);
+
+        assert_eq!(tabs.channel_at(area, 11, 1), Some(0));
+        assert_eq!(tabs.channel_at(area, 28, 1), Some(2));
+        assert_eq!(tabs.channel_at(area, 10, 1), None);
+        assert_eq!(tabs.channel_at(area, 29, 1), None);
     }
 }
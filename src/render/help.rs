@@ -44,9 +44,64 @@ impl Renderer for HelperPopup {
                 ("Zoom out", bindings::ZOOM_OUT),
                 ("Move left", bindings::MOVE_LEFT),
                 ("Move right", bindings::MOVE_RIGHT),
+                ("Export view to an image", bindings::EXPORT),
+            ],
+            vec![
+                ("Show/hide the cursor", bindings::CURSOR),
+                ("Move cursor left", bindings::CURSOR_LEFT),
+                ("Move cursor right", bindings::CURSOR_RIGHT),
+                ("Move cursor up", bindings::CURSOR_UP),
+                ("Move cursor down", bindings::CURSOR_DOWN),
+            ],
+            vec![
+                ("Start the selection", bindings::SELECTION_IN),
+                ("End the selection", bindings::SELECTION_OUT),
+                ("Zoom to the selection", bindings::ZOOM_SELECTION),
+                ("Clear the selection", bindings::CLEAR_SELECTION),
+                ("Show/hide selection statistics", bindings::STATISTICS),
+                (
+                    "Export selection to an audio file",
+                    bindings::EXPORT_SELECTION,
+                ),
+            ],
+            vec![
+                ("Jump to the next true peak", bindings::NEXT_TRUE_PEAK),
+                (
+                    "Jump to the previous true peak",
+                    bindings::PREVIOUS_TRUE_PEAK,
+                ),
+                ("Jump to the next clip event", bindings::NEXT_CLIP),
+                ("Jump to the previous clip event", bindings::PREVIOUS_CLIP),
+                ("Show/hide the silences", bindings::SILENCES),
+            ],
+            vec![
+                ("Zoom in vertically", bindings::VERTICAL_ZOOM_IN),
+                ("Zoom out vertically", bindings::VERTICAL_ZOOM_OUT),
+                ("Move frequencies up", bindings::MOVE_UP),
+                ("Move frequencies down", bindings::MOVE_DOWN),
+                ("Switch amplitude scale", bindings::AMPLITUDE_SCALE),
+                ("Fit amplitude to the view", bindings::AUTO_FIT),
+            ],
+            vec![
+                ("Switch frequency scale", bindings::FREQUENCY_SCALE),
+                ("Switch colormap", bindings::COLORMAP),
+                ("Lower dB floor", bindings::DB_FLOOR_DOWN),
+                ("Raise dB floor", bindings::DB_FLOOR_UP),
+                ("Lower dB ceiling", bindings::DB_CEILING_DOWN),
+                ("Raise dB ceiling", bindings::DB_CEILING_UP),
+            ],
+            vec![
+                ("Increase FFT window size", bindings::FFT_WINDOW_SIZE_UP),
+                ("Decrease FFT window size", bindings::FFT_WINDOW_SIZE_DOWN),
+                ("Switch FFT overlap", bindings::FFT_OVERLAP),
+                ("Switch FFT window type", bindings::FFT_WINDOW_TYPE),
+                ("Switch FFT padding type", bindings::FFT_PADDING_TYPE),
             ],
             vec![
                 ("Reset channel selection", bindings::CHANNEL_RESET),
+                ("Select channels by range", bindings::CHANNEL_PROMPT),
+                ("Next channels page", bindings::NEXT_CHANNEL_PAGE),
+                ("Previous channels page", bindings::PREVIOUS_CHANNEL_PAGE),
                 ("Enable/disable channel 1", bindings::CHANNEL_SELECTOR_1),
                 ("Enable/disable channel 2", bindings::CHANNEL_SELECTOR_2),
                 ("Enable/disable channel 3", bindings::CHANNEL_SELECTOR_3),
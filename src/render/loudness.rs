@@ -0,0 +1,256 @@
+use super::renderer::{
+    compute_rulers_layout, draw_cursor, draw_selection, inner_area, HORIZONTAL_LABEL_SPACING,
+    VERTICAL_LABEL_SPACING,
+};
+use super::widgets::{Ruler, RulerOrientation};
+use super::{draw_progress, draw_text_info, Renderer, RenderingInfo};
+use crate::dsp::{AnalysisCache, AsyncDspData, AsyncDspDataState, Loudness, LoudnessParameters};
+use crate::utils::axis::{format_time, level_labels, time_labels};
+use crate::utils::{Cursor, Zoom};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::symbols::Marker;
+use tui::text::{Span, Spans};
+use tui::widgets::canvas::{Canvas, Line};
+use tui::widgets::{Block, Borders, Paragraph};
+use tui::Frame;
+
+/// Range of the loudness axis, in LUFS
+const LOUDNESS_FLOOR: f64 = -60f64;
+const LOUDNESS_TOP: f64 = 0f64;
+/// Height of the summary above the curves, borders included
+const SUMMARY_HEIGHT: u16 = 3;
+
+/// Points of a curve within the zoom window, as (relative position within the
+/// window, value) pairs. Each point stands for the end of its window. Beyond
+/// `max_points`, neighbouring points are merged, keeping the loudest
+fn visible_points(curve: &[f64], zoom: &Zoom, max_points: usize) -> Vec<(f64, f64)> {
+    let count = curve.len() as f64;
+    let first = (zoom.start() * count).floor() as usize;
+    let last = usize::min(
+        ((zoom.start() + zoom.length()) * count).ceil() as usize,
+        curve.len(),
+    );
+    if first >= last || max_points == 0 {
+        return vec![];
+    }
+
+    let to_x = |idx: f64| ((idx + 1f64) / count - zoom.start()) / zoom.length();
+    let merged = (last - first).div_ceil(max_points);
+    curve[first..last]
+        .chunks(merged)
+        .enumerate()
+        .map(|(chunk_idx, values)| {
+            let idx = (first + chunk_idx * merged) as f64 + (values.len() - 1) as f64 / 2f64;
+            let loudest = values.iter().fold(f64::NEG_INFINITY, |a, b| a.max(*b));
+            (to_x(idx), loudest.max(LOUDNESS_FLOOR))
+        })
+        .collect()
+}
+
+fn draw_curve(ctx: &mut tui::widgets::canvas::Context, points: &[(f64, f64)], color: Color) {
+    for pair in points.windows(2) {
+        ctx.draw(&Line {
+            x1: pair[0].0,
+            y1: pair[0].1,
+            x2: pair[1].0,
+            y2: pair[1].1,
+            color,
+        });
+    }
+}
+
+/// Loudness of the whole content following EBU R128, with the momentary and
+/// short-term loudness over time
+pub struct LoudnessRenderer {
+    async_renderer: AsyncDspData<Loudness, LoudnessParameters>,
+    duration: f64,
+    max_width_res: usize,
+}
+
+impl LoudnessRenderer {
+    pub fn new(path: &std::path::PathBuf, cache: Option<AnalysisCache>) -> LoudnessRenderer {
+        let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(path)
+            .expect("Could not open wave file");
+        let frames = snd.len().unwrap() as f64;
+        let duration = frames / snd.get_samplerate() as f64;
+        let loudness = AsyncDspData::new(path, LoudnessParameters, false, cache);
+
+        LoudnessRenderer {
+            max_width_res: usize::max(1, (duration / Loudness::STEP_DURATION) as usize),
+            async_renderer: loudness,
+            duration,
+        }
+    }
+
+    /// Areas of the summary, the curves and their rulers
+    fn layout(&self, area: Rect) -> (Rect, Rect, Option<(Rect, Rect)>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(SUMMARY_HEIGHT), Constraint::Min(1)])
+            .split(area);
+
+        match compute_rulers_layout(chunks[1]) {
+            Some(rulers) => (
+                chunks[0],
+                rulers.channels,
+                Some((rulers.vertical, rulers.horizontal)),
+            ),
+            None => (chunks[0], chunks[1], None),
+        }
+    }
+}
+
+impl Renderer for LoudnessRenderer {
+    fn draw<B: Backend>(&mut self, frame: &mut Frame<'_, B>, info: &RenderingInfo, area: Rect) {
+        let block = Block::default().title("Loudness").borders(Borders::ALL);
+        match self.async_renderer.state() {
+            AsyncDspDataState::Normalizing
+            | AsyncDspDataState::Created
+            | AsyncDspDataState::Processing => {
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Measuring",
+                    self.async_renderer.progress(),
+                    self.async_renderer.eta(),
+                );
+                return;
+            }
+            AsyncDspDataState::Cancelled => {
+                draw_text_info(frame, area, block, "Cancelled");
+                return;
+            }
+            AsyncDspDataState::Failed(err) => {
+                draw_text_info(frame, area, block, &format!("Error : {}", err));
+                return;
+            }
+            _ => {}
+        }
+
+        let (summary_area, curves_area, rulers) = self.layout(area);
+        let duration = self.duration;
+        let loudness = self.async_renderer.data().unwrap();
+
+        // Summary
+        let name_style = Style::default().add_modifier(Modifier::BOLD);
+        let max = |curve: &[f64]| curve.iter().fold(f64::NEG_INFINITY, |a, b| a.max(*b));
+        let summary = Spans::from(vec![
+            Span::styled("Integrated", name_style),
+            Span::raw(format!(" : {:.1} LUFS | ", loudness.integrated())),
+            Span::styled("Range", name_style),
+            Span::raw(format!(" : {:.1} LU | ", loudness.range())),
+            Span::styled("True peak", name_style),
+            Span::raw(format!(" : {:.1} dBTP | ", loudness.true_peak())),
+            Span::styled("Max momentary", name_style),
+            Span::raw(format!(" : {:.1} LUFS | ", max(loudness.momentary()))),
+            Span::styled("Max short-term", name_style),
+            Span::raw(format!(" : {:.1} LUFS", max(loudness.short_term()))),
+        ]);
+        frame.render_widget(
+            Paragraph::new(summary).block(Block::default().borders(Borders::ALL)),
+            summary_area,
+        );
+
+        // Curves, merged down to the braille resolution
+        let max_points = usize::from(curves_area.width.saturating_sub(2)) * 2;
+        let momentary = visible_points(loudness.momentary(), info.zoom, max_points);
+        let short_term = visible_points(loudness.short_term(), info.zoom, max_points);
+        let integrated = loudness.integrated().max(LOUDNESS_FLOOR);
+        let canvas = Canvas::default()
+            .block(
+                Block::default()
+                    .title("Momentary / Short-term / Integrated")
+                    .borders(Borders::ALL),
+            )
+            .paint(|ctx| {
+                draw_curve(ctx, &momentary, Color::DarkGray);
+                draw_curve(ctx, &short_term, Color::White);
+                ctx.draw(&Line {
+                    x1: 0f64,
+                    y1: integrated,
+                    x2: 1f64,
+                    y2: integrated,
+                    color: Color::Yellow,
+                });
+            })
+            .marker(Marker::Braille)
+            .x_bounds([0f64, 1f64])
+            .y_bounds([LOUDNESS_FLOOR, LOUDNESS_TOP]);
+        frame.render_widget(canvas, curves_area);
+
+        let inner = inner_area(curves_area);
+        if let Some(selection) = info.selection {
+            draw_selection(frame, inner, info.zoom, selection);
+        }
+        if let Some(cursor) = info.cursor {
+            draw_cursor(frame, inner, info.zoom, cursor, false);
+        }
+
+        // Rulers, aligned with the inside of the curves block
+        if let Some((vertical, horizontal)) = rulers {
+            let vertical = Rect {
+                y: inner.y,
+                height: inner.height,
+                ..vertical
+            };
+            let labels = level_labels(
+                LOUDNESS_FLOOR,
+                LOUDNESS_TOP,
+                usize::from(vertical.height) / VERTICAL_LABEL_SPACING,
+            );
+            frame.render_widget(Ruler::new(RulerOrientation::Vertical, &labels), vertical);
+
+            let horizontal = Rect {
+                x: inner.x,
+                width: inner.width,
+                ..horizontal
+            };
+            let labels = time_labels(
+                info.zoom.start() * duration,
+                (info.zoom.start() + info.zoom.length()) * duration,
+                usize::from(horizontal.width) / HORIZONTAL_LABEL_SPACING,
+            );
+            frame.render_widget(
+                Ruler::new(RulerOrientation::Horizontal, &labels),
+                horizontal,
+            );
+        }
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        self.async_renderer.update_status()
+    }
+
+    fn max_width_resolution(&self) -> usize {
+        self.max_width_res
+    }
+
+    fn time_areas(&mut self, _: &RenderingInfo, area: Rect) -> Vec<Rect> {
+        let (_, curves_area, _) = self.layout(area);
+        vec![inner_area(curves_area)]
+    }
+
+    fn channel_areas(&mut self, info: &RenderingInfo, area: Rect) -> Vec<Rect> {
+        self.time_areas(info, area)
+    }
+
+    fn cursor_readout(&mut self, _: &RenderingInfo, cursor: &Cursor) -> Option<String> {
+        let loudness = self.async_renderer.data()?;
+        let count = loudness.momentary().len();
+        if count == 0 {
+            return None;
+        }
+        let idx = usize::min((cursor.position() * count as f64) as usize, count - 1);
+
+        Some(format!(
+            "{} | Momentary {:.1} LUFS | Short-term {:.1} LUFS",
+            format_time(cursor.position() * self.duration),
+            loudness.momentary()[idx],
+            loudness.short_term()[idx]
+        ))
+    }
+}
@@ -9,8 +9,13 @@ use tui::Frame;
 use tui::{backend::Backend, text::Spans};
 
 extern crate sndfile;
-use super::{Renderer, RenderingInfo};
+use super::{draw_progress, draw_text_info, ChannelsTabs, Renderer, RenderingInfo};
+use crate::dsp::{
+    AnalysisCache, AsyncDspData, AsyncDspDataState, TruePeak, TruePeakLocation, TruePeakParameters,
+};
 use crate::sndfile::TagType;
+use crate::utils::axis::format_time;
+use crate::utils::to_dbfs;
 
 fn format_to_string(fmt: MajorFormat) -> String {
     match fmt {
@@ -86,7 +91,7 @@ fn channel_layout_to_string(channels: usize) -> String {
         6 => "ambisonic 5.1",
         7 => "ambisonic 7",
         8 => "ambisonic 7.1",
-        _ => panic!("Unsupported channel layout"),
+        _ => return format!("{} channels", channels),
     }
     .to_string()
 }
@@ -114,7 +119,7 @@ fn compute_duration_string(samplerate: f64, frames: f64) -> String {
     )
 }
 
-struct Metadata {
+pub struct Metadata {
     // Format data
     samplerate: String,
     channel_layout: String,
@@ -137,7 +142,7 @@ struct Metadata {
 }
 
 impl Metadata {
-    fn new(path: &std::path::PathBuf) -> Self {
+    pub fn new(path: &std::path::PathBuf) -> Self {
         let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
             .from_path(path)
             .unwrap();
@@ -187,20 +192,149 @@ impl Metadata {
                 .unwrap_or_else(|| default_msg.to_string()),
         }
     }
+
+    /// Format properties of the file, as (name, value) pairs
+    pub fn properties(&self) -> [(&'static str, &str); 7] {
+        [
+            ("Format", &self.format),
+            ("Format subtype", &self.subtype),
+            ("Endianess", &self.endianess),
+            ("Samplerate", &self.samplerate),
+            ("Channel layout", &self.channel_layout),
+            ("Frames", &self.frames),
+            ("Duration", &self.duration),
+        ]
+    }
+
+    /// Tags of the file, as (name, value) pairs
+    pub fn tags(&self) -> [(&'static str, &str); 10] {
+        [
+            ("Title", &self.title),
+            ("Copyright", &self.copyright),
+            ("Encoder", &self.software),
+            ("Artist", &self.artist),
+            ("Comment", &self.comment),
+            ("Date", &self.date),
+            ("Album", &self.album),
+            ("License", &self.license),
+            ("Track number", &self.track_number),
+            ("Genre", &self.genre),
+        ]
+    }
 }
 
 pub struct MetadataRenderer {
     metadata: Metadata,
     redraw: bool,
+    titles: Vec<String>,
+    samplerate: f64,
+    true_peak: AsyncDspData<TruePeak, TruePeakParameters>,
 }
 
 impl MetadataRenderer {
-    pub fn new(path: &std::path::PathBuf) -> Self {
+    pub fn new(path: &std::path::PathBuf, cache: Option<AnalysisCache>) -> Self {
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(path)
+            .unwrap();
+
         MetadataRenderer {
             metadata: Metadata::new(path),
             redraw: true,
+            titles: ChannelsTabs::get_channels_titles(snd.get_channels()),
+            samplerate: snd.get_samplerate() as f64,
+            true_peak: AsyncDspData::new(path, TruePeakParameters, false, cache),
+        }
+    }
+
+    /// Locations of the highest true peaks, highest first. Empty until they
+    /// are measured
+    pub fn true_peak_locations(&mut self) -> &[TruePeakLocation] {
+        if self.true_peak.update_status() {
+            self.redraw = true;
+        }
+        match self.true_peak.data() {
+            Some(true_peak) => true_peak.locations(),
+            None => &[],
         }
     }
+
+    fn draw_true_peak<B: Backend>(&mut self, frame: &mut Frame<'_, B>, area: Rect) {
+        let block = Block::default().title("True peak").borders(Borders::ALL);
+        match self.true_peak.state() {
+            AsyncDspDataState::Normalizing
+            | AsyncDspDataState::Created
+            | AsyncDspDataState::Processing => {
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Measuring",
+                    self.true_peak.progress(),
+                    self.true_peak.eta(),
+                );
+                return;
+            }
+            AsyncDspDataState::Cancelled => {
+                draw_text_info(frame, area, block, "Cancelled");
+                return;
+            }
+            AsyncDspDataState::Failed(err) => {
+                draw_text_info(frame, area, block, &format!("Error : {}", err));
+                return;
+            }
+            AsyncDspDataState::Finished => {}
+        }
+
+        let name_style = Style::default().add_modifier(Modifier::BOLD);
+        let titles = &self.titles;
+        let samplerate = self.samplerate;
+        let true_peak = self.true_peak.data().unwrap();
+
+        let peaks_spans: Vec<Spans> = titles
+            .iter()
+            .enumerate()
+            .map(|(ch_idx, name)| {
+                Spans::from(vec![
+                    Span::styled(name.as_str(), name_style),
+                    Span::raw(format!(" : {:.2} dBTP", to_dbfs(true_peak.channel(ch_idx)))),
+                ])
+            })
+            .collect();
+        let locations_spans: Vec<Spans> = true_peak
+            .locations()
+            .iter()
+            .enumerate()
+            .map(|(rank, location)| {
+                Spans::from(vec![
+                    Span::styled(format!("#{}", rank + 1), name_style),
+                    Span::raw(format!(
+                        " : {:.2} dBTP | {} | {}",
+                        to_dbfs(location.peak),
+                        titles[location.channel],
+                        format_time(location.frame as f64 / samplerate)
+                    )),
+                ])
+            })
+            .collect();
+
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let peaks_paragraph = Paragraph::new(peaks_spans)
+            .block(block)
+            .alignment(Alignment::Left);
+        frame.render_widget(peaks_paragraph, layout[0]);
+
+        let locations_paragraph = Paragraph::new(locations_spans)
+            .block(
+                Block::default()
+                    .title("Highest true peaks")
+                    .borders(Borders::ALL),
+            )
+            .alignment(Alignment::Left);
+        frame.render_widget(locations_paragraph, layout[1]);
+    }
 }
 
 impl Renderer for MetadataRenderer {
@@ -208,35 +342,15 @@ impl Renderer for MetadataRenderer {
         let name_style = Style::default().add_modifier(Modifier::BOLD);
         let value_style = Style::default();
 
-        // properties
-        let properties = [
-            ("Format", &self.metadata.format),
-            ("Format subtype", &self.metadata.subtype),
-            ("Endianess", &self.metadata.endianess),
-            ("Samplerate", &self.metadata.samplerate),
-            ("Channel layout", &self.metadata.channel_layout),
-            ("Frames", &self.metadata.frames),
-            ("Duration", &self.metadata.duration),
-        ];
-
-        // tags
-        let tags = vec![
-            ("Title", &self.metadata.title),
-            ("Copyright", &self.metadata.copyright),
-            ("Encoder", &self.metadata.software),
-            ("Artist", &self.metadata.artist),
-            ("Comment", &self.metadata.comment),
-            ("Date", &self.metadata.date),
-            ("Album", &self.metadata.album),
-            ("License", &self.metadata.license),
-            ("Track number", &self.metadata.track_number),
-            ("Genre", &self.metadata.genre),
-            ("Date", &self.metadata.date),
-        ];
+        // The true peaks list one line per channel or location
+        let true_peak_lines = usize::max(self.titles.len(), self.true_peak_locations().len());
+        let properties = self.metadata.properties();
+        let tags = self.metadata.tags();
 
         // Layouts
         let constraints = vec![
             Constraint::Length(u16::try_from(properties.len()).unwrap() + 2u16),
+            Constraint::Length(u16::try_from(true_peak_lines).unwrap_or(u16::MAX) + 2u16),
             Constraint::Min(u16::try_from(tags.len()).unwrap() + 2u16),
         ];
         let layout = Layout::default()
@@ -275,12 +389,14 @@ impl Renderer for MetadataRenderer {
         let tags_paragraph = Paragraph::new(tags_spans)
             .block(Block::default().title("Tags").borders(Borders::ALL))
             .alignment(Alignment::Left);
-        frame.render_widget(tags_paragraph, layout[1]);
+        frame.render_widget(tags_paragraph, layout[2]);
+
+        self.draw_true_peak(frame, layout[1]);
 
         self.redraw = false
     }
 
     fn needs_redraw(&mut self) -> bool {
-        self.redraw
+        self.true_peak.update_status() || self.redraw
     }
 }
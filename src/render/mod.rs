@@ -1,5 +1,8 @@
+mod loudness;
 mod renderer;
+mod silence;
 mod spectral;
+mod statistics;
 mod waveform;
 // mod ascii;
 mod headers;
@@ -7,19 +10,24 @@ mod help;
 mod metadata;
 mod widgets;
 
+use crate::utils::Cursor;
 pub use headers::ChannelsTabs;
 pub use help::HelperPopup;
-pub use metadata::MetadataRenderer;
+pub use loudness::LoudnessRenderer;
+pub use metadata::{Metadata, MetadataRenderer};
 pub use renderer::{Renderer, RenderingInfo};
+pub use silence::SilencePanel;
 pub use spectral::SpectralRenderer;
+pub use statistics::StatisticsPopup;
 use tui::{backend::Backend, layout::Rect, Frame};
 pub use waveform::WaveformRenderer;
 
-use renderer::draw_text_info;
+use renderer::{draw_progress, draw_text_info};
 
 pub enum RendererType<'a> {
-    Waveform(WaveformRenderer),
-    Spectral(SpectralRenderer<'a>),
+    Waveform(Box<WaveformRenderer>),
+    Spectral(Box<SpectralRenderer<'a>>),
+    Loudness(Box<LoudnessRenderer>),
     Metadata(Box<MetadataRenderer>),
 }
 
@@ -28,6 +36,7 @@ impl Renderer for RendererType<'_> {
         match self {
             RendererType::Waveform(renderer) => renderer.draw(frame, info, area),
             RendererType::Spectral(renderer) => renderer.draw(frame, info, area),
+            RendererType::Loudness(renderer) => renderer.draw(frame, info, area),
             RendererType::Metadata(renderer) => renderer.draw(frame, info, area),
         }
     }
@@ -36,6 +45,7 @@ impl Renderer for RendererType<'_> {
         match self {
             RendererType::Waveform(renderer) => renderer.needs_redraw(),
             RendererType::Spectral(renderer) => renderer.needs_redraw(),
+            RendererType::Loudness(renderer) => renderer.needs_redraw(),
             RendererType::Metadata(renderer) => renderer.needs_redraw(),
         }
     }
@@ -44,7 +54,35 @@ impl Renderer for RendererType<'_> {
         match self {
             RendererType::Waveform(renderer) => renderer.max_width_resolution(),
             RendererType::Spectral(renderer) => renderer.max_width_resolution(),
+            RendererType::Loudness(renderer) => renderer.max_width_resolution(),
             RendererType::Metadata(renderer) => renderer.max_width_resolution(),
         }
     }
+
+    fn time_areas(&mut self, info: &RenderingInfo, area: Rect) -> Vec<Rect> {
+        match self {
+            RendererType::Waveform(renderer) => renderer.time_areas(info, area),
+            RendererType::Spectral(renderer) => renderer.time_areas(info, area),
+            RendererType::Loudness(renderer) => renderer.time_areas(info, area),
+            RendererType::Metadata(renderer) => renderer.time_areas(info, area),
+        }
+    }
+
+    fn channel_areas(&mut self, info: &RenderingInfo, area: Rect) -> Vec<Rect> {
+        match self {
+            RendererType::Waveform(renderer) => renderer.channel_areas(info, area),
+            RendererType::Spectral(renderer) => renderer.channel_areas(info, area),
+            RendererType::Loudness(renderer) => renderer.channel_areas(info, area),
+            RendererType::Metadata(renderer) => renderer.channel_areas(info, area),
+        }
+    }
+
+    fn cursor_readout(&mut self, info: &RenderingInfo, cursor: &Cursor) -> Option<String> {
+        match self {
+            RendererType::Waveform(renderer) => renderer.cursor_readout(info, cursor),
+            RendererType::Spectral(renderer) => renderer.cursor_readout(info, cursor),
+            RendererType::Loudness(renderer) => renderer.cursor_readout(info, cursor),
+            RendererType::Metadata(renderer) => renderer.cursor_readout(info, cursor),
+        }
+    }
 }
@@ -1,15 +1,35 @@
 use std::convert::TryFrom;
+use std::time::Duration;
 use tui::backend::Backend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
 use tui::text::Spans;
-use tui::widgets::{Block, Borders, Paragraph};
+use tui::widgets::{Block, Borders, Gauge, Paragraph};
 use tui::Frame;
 
-use crate::utils::Zoom;
+use super::widgets::{Ruler, RulerOrientation, Shade};
+use crate::dsp::Silence;
+use crate::utils::axis::{format_time, time_labels};
+use crate::utils::{Cursor, Selection, Zoom};
+
+/// Width of the rulers on the left of each channel, in characters
+const VERTICAL_RULER_WIDTH: u16 = 7;
+/// Height of the time ruler under the channels, in characters
+const HORIZONTAL_RULER_HEIGHT: u16 = 2;
+/// Minimum spacing between two labels of the time ruler, in characters
+pub(super) const HORIZONTAL_LABEL_SPACING: usize = 12;
+/// Minimum spacing between two labels of a vertical ruler, in characters
+pub(super) const VERTICAL_LABEL_SPACING: usize = 3;
+/// Minimum height of a channel, borders included, before channels get spread
+/// over several columns
+const MIN_CHANNEL_HEIGHT: u16 = 6;
 
 pub struct RenderingInfo<'a> {
     pub activated_channels: Vec<(usize, &'a str)>,
     pub zoom: &'a Zoom,
+    pub cursor: Option<&'a Cursor>,
+    pub selection: Option<&'a Selection>,
+    pub silence: Option<&'a Silence>,
 }
 
 pub trait Renderer {
@@ -19,9 +39,28 @@ pub trait Renderer {
     fn max_width_resolution(&self) -> usize {
         usize::MAX
     }
+
+    /// Areas spanning the zoom window along their width, as laid out by `draw`
+    /// in `area`. Used to map pointer positions onto the content
+    fn time_areas(&mut self, _info: &RenderingInfo, _area: Rect) -> Vec<Rect> {
+        vec![]
+    }
+
+    /// Inside of each activated channel, as laid out by `draw` in `area`
+    fn channel_areas(&mut self, _info: &RenderingInfo, _area: Rect) -> Vec<Rect> {
+        vec![]
+    }
+
+    /// Values of the content under the cursor, once known
+    fn cursor_readout(&mut self, _info: &RenderingInfo, _cursor: &Cursor) -> Option<String> {
+        None
+    }
 }
 
 pub trait ChannelRenderer: Renderer {
+    /// Whether the height of the cursor points at something, e.g. a frequency
+    const VERTICAL_CURSOR: bool = false;
+
     fn draw_single_channel<B: Backend>(
         &mut self,
         frame: &mut Frame<'_, B>,
@@ -33,15 +72,256 @@ pub trait ChannelRenderer: Renderer {
 
     fn needs_redraw(&mut self) -> bool;
     fn max_width_resolution(&self) -> usize;
+
+    /// Labels of the vertical axis of a channel, as (relative position, text)
+    /// pairs. None until there is something to label
+    fn vertical_ruler(&mut self, _channel: usize, _max_count: usize) -> Option<Vec<(f64, String)>> {
+        None
+    }
+
+    /// Duration of the displayed content in seconds, once known
+    fn duration(&mut self) -> Option<f64> {
+        None
+    }
+
+    /// Values of the given channels, as (index, name) pairs, under the cursor
+    fn read_cursor(&mut self, _channels: &[(usize, &str)], _cursor: &Cursor) -> Option<String> {
+        None
+    }
+}
+
+/// Inside of a channel block
+pub(super) fn inner_area(area: Rect) -> Rect {
+    Rect {
+        x: area.x + 1,
+        y: area.y + 1,
+        width: area.width.saturating_sub(2),
+        height: area.height.saturating_sub(2),
+    }
+}
+
+/// Row of the cursor within the inside of a channel
+fn cursor_row(area: Rect, cursor: &Cursor) -> u16 {
+    let from_bottom = ((cursor.height() * area.height as f64) as u16).min(area.height - 1);
+    area.y + area.height - 1 - from_bottom
+}
+
+/// Shade the columns of the silent regions of a channel inside its area
+pub(super) fn draw_silences<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    area: Rect,
+    zoom: &Zoom,
+    silence: &Silence,
+    channel: usize,
+) {
+    if area.width == 0 || silence.frames() == 0 {
+        return;
+    }
+
+    let frames = silence.frames() as f64;
+    let width = area.width as f64;
+    for region in silence
+        .regions()
+        .iter()
+        .filter(|region| region.channel == channel)
+    {
+        let start = (region.start as f64 / frames - zoom.start()) / zoom.length();
+        let end = (region.end() as f64 / frames - zoom.start()) / zoom.length();
+        if end <= 0f64 || start >= 1f64 {
+            continue;
+        }
+
+        let first = (start.max(0f64) * width) as u16;
+        let last = ((end.min(1f64) * width).ceil() as u16).clamp(first + 1, area.width);
+        let columns = Rect {
+            x: area.x + first,
+            width: last - first,
+            ..area
+        };
+        frame.render_widget(Shade, columns);
+    }
+}
+
+/// Highlight the columns of the bounds of the selection inside a channel
+pub(super) fn draw_selection<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    area: Rect,
+    zoom: &Zoom,
+    selection: &Selection,
+) {
+    let style = Block::default().style(Style::default().bg(Color::Blue));
+    for bound in [selection.start(), selection.end()] {
+        let ratio = (bound - zoom.start()) / zoom.length();
+        if !(0f64..1f64).contains(&ratio) || area.width == 0 {
+            continue;
+        }
+
+        let column = Rect {
+            x: area.x + (ratio * area.width as f64) as u16,
+            width: 1,
+            ..area
+        };
+        frame.render_widget(style.clone(), column);
+    }
+}
+
+/// Highlight the column of the cursor inside a channel, leaving a gap on its
+/// row when it has a height
+pub(super) fn draw_cursor<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    area: Rect,
+    zoom: &Zoom,
+    cursor: &Cursor,
+    vertical: bool,
+) {
+    let ratio = (cursor.position() - zoom.start()) / zoom.length();
+    if !(0f64..1f64).contains(&ratio) || area.width == 0 || area.height == 0 {
+        return;
+    }
+
+    let column = Rect {
+        x: area.x + (ratio * area.width as f64) as u16,
+        width: 1,
+        ..area
+    };
+    let style = Block::default().style(Style::default().add_modifier(Modifier::REVERSED));
+    if vertical {
+        let row = cursor_row(area, cursor);
+        let above = Rect {
+            height: row - area.y,
+            ..column
+        };
+        let below = Rect {
+            y: row + 1,
+            height: area.y + area.height - row - 1,
+            ..column
+        };
+        frame.render_widget(style.clone(), above);
+        frame.render_widget(style, below);
+    } else {
+        frame.render_widget(style, column);
+    }
+}
+
+/// Areas of the rulers, around the channels area
+pub(super) struct RulersLayout {
+    pub channels: Rect,
+    pub vertical: Rect,
+    pub horizontal: Rect,
+}
+
+pub(super) fn compute_rulers_layout(area: Rect) -> Option<RulersLayout> {
+    if area.width <= 4 * VERTICAL_RULER_WIDTH || area.height <= 4 * HORIZONTAL_RULER_HEIGHT {
+        return None;
+    }
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(VERTICAL_RULER_WIDTH), Constraint::Min(1)])
+        .split(area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(HORIZONTAL_RULER_HEIGHT),
+        ])
+        .split(columns[1]);
+
+    Some(RulersLayout {
+        channels: rows[0],
+        vertical: Rect {
+            height: rows[0].height,
+            ..columns[0]
+        },
+        horizontal: rows[1],
+    })
 }
 
 impl<T: ChannelRenderer> Renderer for T {
     fn draw<B: Backend>(&mut self, frame: &mut Frame<'_, B>, info: &RenderingInfo, area: Rect) {
-        let layout = compute_channels_layout(area, info.activated_channels.len());
+        // Rulers are only drawn once there is something to label
+        let rulers = match self.duration() {
+            Some(duration) => compute_rulers_layout(area).map(|layout| (duration, layout)),
+            None => None,
+        };
+        let channels_area = match &rulers {
+            Some((_, layout)) => layout.channels,
+            None => area,
+        };
+
+        let (layout, columns) =
+            compute_channels_layout(channels_area, info.activated_channels.len());
 
         for (activated_idx, (ch_idx, title)) in info.activated_channels.iter().enumerate() {
+            // Nothing fits inside the borders
+            if layout[activated_idx].width < 3 || layout[activated_idx].height < 3 {
+                continue;
+            }
+
             let block = Block::default().title(*title).borders(Borders::ALL);
             self.draw_single_channel(frame, *ch_idx, layout[activated_idx], block, info.zoom);
+            if let Some(silence) = info.silence {
+                draw_silences(
+                    frame,
+                    inner_area(layout[activated_idx]),
+                    info.zoom,
+                    silence,
+                    *ch_idx,
+                );
+            }
+            if let Some(selection) = info.selection {
+                draw_selection(
+                    frame,
+                    inner_area(layout[activated_idx]),
+                    info.zoom,
+                    selection,
+                );
+            }
+            if let Some(cursor) = info.cursor {
+                draw_cursor(
+                    frame,
+                    inner_area(layout[activated_idx]),
+                    info.zoom,
+                    cursor,
+                    T::VERTICAL_CURSOR,
+                );
+            }
+
+            if let Some((_, rulers_layout)) = &rulers {
+                // Aligned with the inside of the channel block
+                let ruler_area = Rect {
+                    y: layout[activated_idx].y + 1,
+                    height: layout[activated_idx].height.saturating_sub(2),
+                    ..rulers_layout.vertical
+                };
+                let max_count = usize::from(ruler_area.height) / VERTICAL_LABEL_SPACING;
+                if let Some(labels) = self.vertical_ruler(*ch_idx, max_count) {
+                    frame
+                        .render_widget(Ruler::new(RulerOrientation::Vertical, &labels), ruler_area);
+                }
+            }
+        }
+
+        if let Some((duration, rulers_layout)) = &rulers {
+            // One time ruler under each column, aligned with the inside of the
+            // channel blocks
+            for column in columns.iter() {
+                let ruler_area = Rect {
+                    x: column.x + 1,
+                    width: column.width.saturating_sub(2),
+                    ..rulers_layout.horizontal
+                };
+                let max_count = usize::from(ruler_area.width) / HORIZONTAL_LABEL_SPACING;
+                let labels = time_labels(
+                    info.zoom.start() * duration,
+                    (info.zoom.start() + info.zoom.length()) * duration,
+                    max_count,
+                );
+                frame.render_widget(
+                    Ruler::new(RulerOrientation::Horizontal, &labels),
+                    ruler_area,
+                );
+            }
         }
     }
 
@@ -52,6 +332,42 @@ impl<T: ChannelRenderer> Renderer for T {
     fn max_width_resolution(&self) -> usize {
         ChannelRenderer::max_width_resolution(self)
     }
+
+    fn time_areas(&mut self, info: &RenderingInfo, area: Rect) -> Vec<Rect> {
+        let (_, columns) = channels_layout(self, info, area);
+
+        // Inside of the channel blocks
+        columns
+            .iter()
+            .map(|column| Rect {
+                x: column.x + 1,
+                width: column.width.saturating_sub(2),
+                ..*column
+            })
+            .collect()
+    }
+
+    fn channel_areas(&mut self, info: &RenderingInfo, area: Rect) -> Vec<Rect> {
+        let (layout, _) = channels_layout(self, info, area);
+        layout.into_iter().map(inner_area).collect()
+    }
+
+    fn cursor_readout(&mut self, info: &RenderingInfo, cursor: &Cursor) -> Option<String> {
+        self.read_cursor(&info.activated_channels, cursor)
+    }
+}
+
+/// Channels and columns layout of a channel renderer, as drawn in `area`
+fn channels_layout<T: ChannelRenderer>(
+    renderer: &mut T,
+    info: &RenderingInfo,
+    area: Rect,
+) -> (Vec<Rect>, Vec<Rect>) {
+    let channels_area = match renderer.duration() {
+        Some(_) => compute_rulers_layout(area).map_or(area, |layout| layout.channels),
+        None => area,
+    };
+    compute_channels_layout(channels_area, info.activated_channels.len())
 }
 
 pub fn draw_text_info<B: Backend>(
@@ -76,11 +392,101 @@ pub fn draw_text_info<B: Backend>(
     frame.render_widget(paragraph, area);
 }
 
-fn compute_channels_layout(area: Rect, num_channels: usize) -> Vec<Rect> {
-    let constraints =
-        vec![Constraint::Ratio(1u32, u32::try_from(num_channels).unwrap()); num_channels];
-    Layout::default()
+/// Draw a one line progress gauge in the middle of the area, with the
+/// estimated time left when known
+pub fn draw_progress<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    area: Rect,
+    block: Block<'_>,
+    text: &str,
+    ratio: f64,
+    eta: Option<Duration>,
+) {
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+    if inner_area.height == 0 || inner_area.width < 4 {
+        return;
+    }
+
+    let label = match eta {
+        Some(eta) => format!(
+            "{} {:.0}% - {} left",
+            text,
+            ratio * 100f64,
+            format_time(eta.as_secs_f64().ceil())
+        ),
+        None => format!("{} {:.0}%", text, ratio * 100f64),
+    };
+    let gauge_area = Rect {
+        x: inner_area.x + inner_area.width / 8,
+        y: inner_area.y + (inner_area.height - 1) / 2,
+        width: inner_area.width - inner_area.width / 4,
+        height: 1,
+    };
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(Color::Gray).bg(Color::DarkGray))
+        .ratio(ratio.clamp(0f64, 1f64))
+        .label(label);
+
+    frame.render_widget(gauge, gauge_area);
+}
+
+/// Split the area into one rect per channel, stacked vertically. When they
+/// would be too thin to be legible, channels are laid out row by row on a grid,
+/// and the areas of the columns are returned too
+fn compute_channels_layout(area: Rect, num_channels: usize) -> (Vec<Rect>, Vec<Rect>) {
+    let max_rows = usize::max(1, usize::from(area.height / MIN_CHANNEL_HEIGHT));
+    let num_columns = usize::max(1, num_channels.div_ceil(max_rows));
+    let num_rows = num_channels.div_ceil(num_columns);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints::<&[Constraint]>(
+            vec![Constraint::Ratio(1u32, u32::try_from(num_columns).unwrap()); num_columns]
+                .as_ref(),
+        )
+        .split(area);
+    let rows = Layout::default()
         .direction(Direction::Vertical)
-        .constraints::<&[Constraint]>(constraints.as_ref())
-        .split(area)
+        .constraints::<&[Constraint]>(
+            vec![Constraint::Ratio(1u32, u32::try_from(num_rows).unwrap()); num_rows].as_ref(),
+        )
+        .split(area);
+
+    let channels = (0..num_channels)
+        .map(|idx| {
+            let (row, column) = (rows[idx / num_columns], columns[idx % num_columns]);
+            Rect {
+                x: column.x,
+                width: column.width,
+                ..row
+            }
+        })
+        .collect();
+
+    (channels, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_channels_layout, MIN_CHANNEL_HEIGHT};
+    use tui::layout::Rect;
+
+    #[test]
+    fn channels_layout() {
+        let area = Rect::new(0, 0, 200, 60);
+
+        for num_channels in [1usize, 2, 8, 16, 32, 64] {
+            let (layout, columns) = compute_channels_layout(area, num_channels);
+
+            assert_eq!(layout.len(), num_channels);
+            assert!(layout.iter().all(|rect| area.union(*rect) == area));
+            if num_channels * usize::from(MIN_CHANNEL_HEIGHT) > usize::from(area.height) {
+                assert!(columns.len() > 1);
+                assert!(layout.iter().all(|rect| rect.height >= MIN_CHANNEL_HEIGHT));
+            } else {
+                assert_eq!(columns.len(), 1);
+            }
+        }
+    }
 }
@@ -0,0 +1,140 @@
+use super::{draw_progress, draw_text_info, Renderer, RenderingInfo};
+use crate::dsp::{AnalysisCache, AsyncDspData, AsyncDspDataState, Silence, SilenceParameters};
+use crate::utils::axis::format_time;
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+/// Silent regions of the activated channels, from the start of the view
+pub struct SilencePanel {
+    visible: bool,
+    repaint: bool,
+    samplerate: f64,
+    async_silence: AsyncDspData<Silence, SilenceParameters>,
+}
+
+impl SilencePanel {
+    pub fn new(
+        path: &std::path::PathBuf,
+        parameters: SilenceParameters,
+        cache: Option<AnalysisCache>,
+    ) -> Self {
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(path)
+            .expect("Could not open wave file");
+
+        SilencePanel {
+            visible: false,
+            repaint: true,
+            samplerate: snd.get_samplerate() as f64,
+            async_silence: AsyncDspData::new(path, parameters, false, cache),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, enable: bool) {
+        if self.visible != enable {
+            self.repaint = true;
+        }
+        self.visible = enable;
+    }
+
+    /// Silent regions, once detected
+    pub fn silence(&self) -> Option<&Silence> {
+        self.async_silence.data.as_ref()
+    }
+}
+
+impl Renderer for SilencePanel {
+    fn needs_redraw(&mut self) -> bool {
+        // The regions are shaded in the channels even when hidden
+        let updated = self.async_silence.update_status();
+        updated || (self.visible && self.repaint)
+    }
+
+    fn draw<B: Backend>(&mut self, frame: &mut Frame<'_, B>, info: &RenderingInfo, area: Rect) {
+        self.repaint = false;
+        let block = Block::default().title("Silences").borders(Borders::ALL);
+
+        match self.async_silence.state() {
+            AsyncDspDataState::Created
+            | AsyncDspDataState::Normalizing
+            | AsyncDspDataState::Processing => {
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Detecting",
+                    self.async_silence.progress(),
+                    self.async_silence.eta(),
+                );
+                return;
+            }
+            AsyncDspDataState::Cancelled => {
+                draw_text_info(frame, area, block, "Cancelled");
+                return;
+            }
+            AsyncDspDataState::Failed(err) => {
+                draw_text_info(frame, area, block, &format!("Error : {}", err));
+                return;
+            }
+            AsyncDspDataState::Finished => {}
+        }
+
+        let samplerate = self.samplerate;
+        let silence = self.async_silence.data().unwrap();
+        let regions: Vec<_> = silence
+            .regions()
+            .iter()
+            .filter(|region| {
+                info.activated_channels
+                    .iter()
+                    .any(|(ch_idx, _)| *ch_idx == region.channel)
+            })
+            .collect();
+        let block = block.title(format!("Silences ({})", regions.len()));
+        if regions.is_empty() {
+            draw_text_info(frame, area, block, "No silence");
+            return;
+        }
+
+        // Regions ending before the view are skipped
+        let view_start = (info.zoom.start() * silence.frames() as f64) as usize;
+        let kind_style = Style::default().add_modifier(Modifier::BOLD);
+        let spans: Vec<Spans> = regions
+            .iter()
+            .filter(|region| region.end() > view_start)
+            .map(|region| {
+                let name = info
+                    .activated_channels
+                    .iter()
+                    .find(|(ch_idx, _)| *ch_idx == region.channel)
+                    .map_or("", |(_, name)| *name);
+                Spans::from(vec![
+                    Span::styled(region.kind.name(), kind_style),
+                    Span::raw(format!(
+                        " | {} | {} - {} ({})",
+                        name,
+                        format_time(region.start as f64 / samplerate),
+                        format_time(region.end() as f64 / samplerate),
+                        format_time(region.length as f64 / samplerate)
+                    )),
+                ])
+            })
+            .collect();
+
+        let paragraph = Paragraph::new(spans)
+            .block(block)
+            .alignment(Alignment::Left);
+
+        frame.render_widget(paragraph, area);
+    }
+}
@@ -1,55 +1,216 @@
 use super::widgets;
-use super::{draw_text_info, renderer::ChannelRenderer};
+use super::{draw_progress, draw_text_info, renderer::ChannelRenderer};
 use core::panic;
 extern crate sndfile;
-use crate::utils::Zoom;
+use crate::utils::axis::format_time;
+use crate::utils::{Cursor, Zoom};
 use fr::Image;
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryInto;
 use tui::backend::Backend;
 use tui::layout::Rect;
 use tui::widgets::Block;
 use tui::Frame;
 
-use crate::dsp::{AsyncDspData, AsyncDspDataState, Spectrogram, SpectrogramParameters};
+use crate::dsp::{
+    AnalysisCache, AsyncDspData, AsyncDspDataState, Colormap, DbRange, FrequencyScale, Spectrogram,
+    SpectrogramParameters,
+};
+use crate::export::{export_spectrogram, ExportOptions, SpectrogramStyle};
+use std::io;
+use std::path::Path;
 
 use std::num::NonZeroU32;
 
 use fast_image_resize as fr;
 
+/// Fewest bins the frequency zoom can narrow down to
+const MIN_VISIBLE_BINS: usize = 8;
+
+fn min_frequency_zoom(num_bins: usize) -> f64 {
+    MIN_VISIBLE_BINS as f64 / num_bins as f64
+}
+
 pub struct SpectralRenderer<'a> {
     channels: usize,
     async_renderer: AsyncDspData<Spectrogram, SpectrogramParameters>,
+    // Job started with new parameters, replacing the current one when finished
+    pending_renderer: Option<AsyncDspData<Spectrogram, SpectrogramParameters>>,
+    // Whether the latest recomputation is still running
+    recomputing: bool,
+    // Outcome of the latest recomputation, until taken
+    recompute_status: Option<String>,
+    path: std::path::PathBuf,
+    normalize: bool,
+    cache: Option<AnalysisCache>,
+    parameters: SpectrogramParameters,
+    frames: u64,
     resizer: fr::Resizer,
     canva_img: Option<Image<'a>>,
     max_width_resolution: usize,
+    frequency_scale: FrequencyScale,
+    // Range of bins shown, along the vertical axis
+    frequency_zoom: Zoom,
+    scaled_buffer: Vec<u32>,
+    colormaps: Vec<Colormap>,
+    colormap_idx: usize,
+    db_range: DbRange,
+    canva_colors: Vec<u8>,
 }
 
 impl<'a> SpectralRenderer<'a> {
     pub fn new(
         path: &std::path::PathBuf,
         parameters: SpectrogramParameters,
+        colormap: &Colormap,
         normalize: bool,
+        cache: Option<AnalysisCache>,
     ) -> Self {
         let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
             .from_path(path)
             .expect("Could not open wave file");
 
         let channels = snd.get_channels();
-        let max_res = snd.len().unwrap()
-            / (parameters.window_size as f64 * (1f64 - parameters.overlap_rate)) as u64;
+        let frames = snd.len().unwrap();
+        let frequency_scale = parameters.frequency_scale;
+        let db_range = DbRange {
+            floor: parameters.db_threshold,
+            ceiling: 0f64,
+        };
+        let (colormaps, colormap_idx) = colormap.cycle();
 
         SpectralRenderer {
             channels,
-            async_renderer: AsyncDspData::new(path, parameters, normalize),
+            async_renderer: AsyncDspData::new(path, parameters, normalize, cache.clone()),
+            pending_renderer: None,
+            recomputing: false,
+            recompute_status: None,
+            path: path.clone(),
+            normalize,
+            cache,
+            parameters,
+            frames,
             // resizer: fr::Resizer::new(fr::ResizeAlg::Nearest),
             resizer: fr::Resizer::new(fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3)),
             canva_img: None,
-            max_width_resolution: usize::try_from(max_res).unwrap(),
+            max_width_resolution: parameters.num_bands(frames),
+            frequency_scale,
+            frequency_zoom: Zoom::new(min_frequency_zoom(parameters.window_size / 2)).unwrap(),
+            scaled_buffer: vec![],
+            colormaps,
+            colormap_idx,
+            db_range,
+            canva_colors: vec![],
+        }
+    }
+
+    /// FFT parameters of the latest computation requested
+    pub fn parameters(&self) -> SpectrogramParameters {
+        self.parameters
+    }
+
+    /// Start computing the spectrogram with new parameters. The current one
+    /// stays displayed until the new one is finished, any other computation in
+    /// progress is cancelled
+    pub fn recompute(&mut self, parameters: SpectrogramParameters) {
+        self.parameters = parameters;
+        self.recomputing = true;
+        self.recompute_status = None;
+        let job = AsyncDspData::new(&self.path, parameters, self.normalize, self.cache.clone());
+
+        if self.async_renderer.state() == AsyncDspDataState::Finished {
+            // Replacing the previous pending job drops, hence cancels, it
+            self.pending_renderer = Some(job);
+        } else {
+            // Nothing to keep displayed
+            self.async_renderer.cancel();
+            self.async_renderer = job;
+            self.pending_renderer = None;
+            self.max_width_resolution = parameters.num_bands(self.frames);
+        }
+    }
+
+    /// Outcome of the latest recomputation once it is over, returned only once
+    pub fn take_recompute_status(&mut self) -> Option<String> {
+        self.recompute_status.take()
+    }
+
+    /// Switch to the next colormap, and return it
+    pub fn next_colormap(&mut self) -> &Colormap {
+        self.colormap_idx = (self.colormap_idx + 1) % self.colormaps.len();
+        &self.colormaps[self.colormap_idx]
+    }
+
+    /// Switch to the next frequency scale, and return it
+    pub fn next_frequency_scale(&mut self) -> FrequencyScale {
+        self.frequency_scale = self.frequency_scale.next();
+        self.frequency_scale
+    }
+
+    /// Narrow the range of frequencies shown around its center
+    pub fn frequency_zoom_in(&mut self) {
+        self.frequency_zoom.zoom_in();
+    }
+
+    /// Widen the range of frequencies shown, up to the whole spectrum
+    pub fn frequency_zoom_out(&mut self) {
+        self.frequency_zoom.zoom_out();
+    }
+
+    /// Move the range of frequencies shown toward the high frequencies
+    pub fn move_frequency_up(&mut self) {
+        self.frequency_zoom.move_right();
+    }
+
+    /// Move the range of frequencies shown toward the low frequencies
+    pub fn move_frequency_down(&mut self) {
+        self.frequency_zoom.move_left();
+    }
+
+    /// Lowest and highest frequencies shown, once computed
+    pub fn frequency_range(&mut self) -> Option<(f64, f64)> {
+        let frequency_zoom = &self.frequency_zoom;
+        self.async_renderer
+            .data()
+            .map(|data| data.frequency_range(frequency_zoom))
+    }
+
+    /// Move the floor of the colored dB range, and return the new range
+    pub fn shift_db_floor(&mut self, delta: f64) -> DbRange {
+        self.db_range.shift_floor(delta);
+        self.db_range
+    }
+
+    /// Move the ceiling of the colored dB range, and return the new range
+    pub fn shift_db_ceiling(&mut self, delta: f64) -> DbRange {
+        self.db_range.shift_ceiling(delta);
+        self.db_range
+    }
+
+    /// Export the current view of the given channels to a png file
+    pub fn export(
+        &mut self,
+        channels: &[usize],
+        zoom: &Zoom,
+        options: &ExportOptions,
+        path: &Path,
+    ) -> io::Result<()> {
+        let style = SpectrogramStyle {
+            scale: self.frequency_scale,
+            frequency_zoom: &self.frequency_zoom,
+            colormap: &self.colormaps[self.colormap_idx],
+            db_range: self.db_range,
+        };
+
+        match self.async_renderer.data() {
+            Some(data_ref) => export_spectrogram(data_ref, channels, zoom, &style, options, path),
+            None => Err(io::Error::other("Spectrogram is not computed yet")),
         }
     }
 }
 
 impl<'a> ChannelRenderer for SpectralRenderer<'a> {
+    const VERTICAL_CURSOR: bool = true;
+
     fn draw_single_channel<B: Backend>(
         &mut self,
         frame: &mut Frame<'_, B>,
@@ -60,16 +221,33 @@ impl<'a> ChannelRenderer for SpectralRenderer<'a> {
     ) {
         match self.async_renderer.state() {
             AsyncDspDataState::Normalizing => {
-                draw_text_info(frame, area, block, "Normalizing...");
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Normalizing",
+                    self.async_renderer.progress(),
+                    self.async_renderer.eta(),
+                );
                 return;
             }
             AsyncDspDataState::Created | AsyncDspDataState::Processing => {
-                draw_text_info(frame, area, block, "Loading...");
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Loading",
+                    self.async_renderer.progress(),
+                    self.async_renderer.eta(),
+                );
+                return;
+            }
+            AsyncDspDataState::Cancelled => {
+                draw_text_info(frame, area, block, "Cancelled");
                 return;
             }
-            AsyncDspDataState::Failed => {
-                // Should crash soon
-                draw_text_info(frame, area, block, "Error");
+            AsyncDspDataState::Failed(err) => {
+                draw_text_info(frame, area, block, &format!("Error : {}", err));
                 return;
             }
             _ => {}
@@ -86,15 +264,23 @@ impl<'a> ChannelRenderer for SpectralRenderer<'a> {
             None => panic!(),
         };
 
-        // Create source image from spectrogram
-        let num_bins = data_ref.num_bins();
+        // The number of bins changes with the FFT parameters
+        self.frequency_zoom
+            .update_zoom_max(min_frequency_zoom(data_ref.num_bins()));
 
-        let (data_slice, num_bands) = data_ref.data(channel, zoom);
-        let src_image = fr::Image::from_slice_u8(
+        // Create source image from spectrogram
+        let (data_slice, num_bands, num_bins) = data_ref.scaled_data(
+            channel,
+            zoom,
+            &self.frequency_zoom,
+            self.frequency_scale,
+            &mut self.scaled_buffer,
+        );
+        let src_image = fr::Image::from_slice_u32(
             NonZeroU32::new(num_bins.try_into().unwrap()).unwrap(),
             NonZeroU32::new(num_bands.try_into().unwrap()).unwrap(),
             data_slice,
-            fr::PixelType::U8x3,
+            fr::PixelType::F32,
         )
         .unwrap();
 
@@ -107,7 +293,7 @@ impl<'a> ChannelRenderer for SpectralRenderer<'a> {
         self.canva_img = Some(fr::Image::new(
             NonZeroU32::new(resize_dst_width.try_into().unwrap()).unwrap(),
             NonZeroU32::new(resize_dst_height.try_into().unwrap()).unwrap(),
-            fr::PixelType::U8x3,
+            fr::PixelType::F32,
         ));
 
         let canva_img_ref = self.canva_img.as_mut().unwrap();
@@ -118,18 +304,95 @@ impl<'a> ChannelRenderer for SpectralRenderer<'a> {
             .resize(&src_image.view(), &mut dst_view)
             .unwrap();
 
+        // Colorize the resized magnitudes only, it is much cheaper than the
+        // whole spectrogram
+        self.colormaps[self.colormap_idx].colorize(
+            canva_img_ref.buffer(),
+            &self.db_range,
+            &mut self.canva_colors,
+        );
+
         // Build Image widget
-        let img_widget = widgets::Image::new(canva_img_ref.buffer()).block(block);
+        let img_widget = widgets::Image::new(&self.canva_colors).block(block);
 
         frame.render_widget(img_widget, area);
     }
 
     fn needs_redraw(&mut self) -> bool {
-        self.async_renderer.update_status()
+        let mut redraw = self.async_renderer.update_status();
+
+        if let Some(pending) = self.pending_renderer.as_mut() {
+            pending.update_status();
+            match pending.state() {
+                AsyncDspDataState::Finished => {
+                    self.async_renderer = self.pending_renderer.take().unwrap();
+                    self.max_width_resolution = self.parameters.num_bands(self.frames);
+                    redraw = true;
+                }
+                // The current image stays, the error only goes to the status
+                AsyncDspDataState::Failed(err) => {
+                    self.pending_renderer = None;
+                    self.recomputing = false;
+                    self.recompute_status = Some(format!("Computation failed : {}", err));
+                }
+                _ => {}
+            }
+        }
+
+        if self.recomputing && self.pending_renderer.is_none() {
+            let status = match self.async_renderer.state() {
+                AsyncDspDataState::Finished => {
+                    Some(format!("Computed : {}", self.parameters.summary()))
+                }
+                AsyncDspDataState::Failed(err) => Some(format!("Computation failed : {}", err)),
+                AsyncDspDataState::Cancelled => Some("Computation cancelled".to_string()),
+                _ => None,
+            };
+            if status.is_some() {
+                self.recomputing = false;
+                self.recompute_status = status;
+            }
+        }
+
+        redraw
     }
 
     fn max_width_resolution(&self) -> usize {
         // nasty, should rely on the same variables as the time window generator
         self.max_width_resolution
     }
+
+    fn vertical_ruler(&mut self, _: usize, max_count: usize) -> Option<Vec<(f64, String)>> {
+        let scale = self.frequency_scale;
+        self.frequency_range()
+            .map(|(min_hz, max_hz)| scale.labels(min_hz, max_hz, max_count))
+    }
+
+    fn duration(&mut self) -> Option<f64> {
+        self.async_renderer.data().map(|data| data.duration())
+    }
+
+    fn read_cursor(&mut self, channels: &[(usize, &str)], cursor: &Cursor) -> Option<String> {
+        let scale = self.frequency_scale;
+        let frequency_zoom = &self.frequency_zoom;
+        let data_ref = self.async_renderer.data()?;
+        let (min_hz, max_hz) = data_ref.frequency_range(frequency_zoom);
+        let hz = scale.frequency_at(cursor.height(), min_hz, max_hz);
+        let values: Vec<String> = channels
+            .iter()
+            .map(
+                |(channel, name)| match data_ref.magnitude_at(*channel, cursor.position(), hz) {
+                    Some(magnitude) => format!("{} {:.1} dB", name, magnitude),
+                    None => format!("{} -", name),
+                },
+            )
+            .collect();
+
+        Some(format!(
+            "{} | {:.0} Hz | {}",
+            format_time(cursor.position() * data_ref.duration()),
+            hz,
+            values.join(", ")
+        ))
+    }
 }
@@ -0,0 +1,179 @@
+use super::{draw_progress, draw_text_info, Renderer, RenderingInfo};
+use crate::dsp::{
+    AnalysisCache, AsyncDspData, AsyncDspDataState, Statistics, StatisticsParameters,
+};
+use crate::utils::axis::format_time;
+use crate::utils::{to_dbfs, Selection};
+use std::path::PathBuf;
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+/// Levels of each channel over the selection
+pub struct StatisticsPopup {
+    visible: bool,
+    repaint: bool,
+    path: PathBuf,
+    frames: usize,
+    samplerate: usize,
+    // Level counted as clipped, in dBFS
+    clip_threshold: f64,
+    // Selection measured, along with its measure
+    selection: Option<Selection>,
+    async_statistics: Option<AsyncDspData<Statistics, StatisticsParameters>>,
+    cache: Option<AnalysisCache>,
+}
+
+impl StatisticsPopup {
+    pub fn new(
+        path: &std::path::PathBuf,
+        clip_threshold: f64,
+        cache: Option<AnalysisCache>,
+    ) -> Self {
+        let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(path)
+            .expect("Could not open wave file");
+
+        StatisticsPopup {
+            visible: false,
+            repaint: true,
+            path: path.clone(),
+            frames: snd.len().unwrap() as usize,
+            samplerate: snd.get_samplerate(),
+            clip_threshold,
+            selection: None,
+            async_statistics: None,
+            cache,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, enable: bool) {
+        if self.visible != enable {
+            self.repaint = true;
+        }
+        self.visible = enable;
+    }
+
+    /// Measure a new selection, while visible. Any measure in progress is
+    /// cancelled
+    pub fn update(&mut self, selection: Option<Selection>) {
+        if !self.visible || self.selection == selection {
+            return;
+        }
+
+        self.selection = selection;
+        self.async_statistics = selection.map(|selection| {
+            let (start, end) = selection.frames(self.frames);
+            let parameters = StatisticsParameters {
+                start,
+                end,
+                clip_threshold: self.clip_threshold,
+            };
+            AsyncDspData::new(&self.path, parameters, false, self.cache.clone())
+        });
+        self.repaint = true;
+    }
+
+    fn title(&self) -> String {
+        match self.selection {
+            Some(selection) => {
+                let (start, end) = selection.frames(self.frames);
+                let samplerate = self.samplerate as f64;
+                format!(
+                    "Selection : {} - {} ({})",
+                    format_time(start as f64 / samplerate),
+                    format_time(end as f64 / samplerate),
+                    format_time((end - start) as f64 / samplerate)
+                )
+            }
+            None => "Selection".to_string(),
+        }
+    }
+}
+
+impl Renderer for StatisticsPopup {
+    fn needs_redraw(&mut self) -> bool {
+        let updated = match self.async_statistics.as_mut() {
+            Some(statistics) => statistics.update_status(),
+            None => false,
+        };
+        self.visible && (updated || self.repaint)
+    }
+
+    fn draw<B: Backend>(&mut self, frame: &mut Frame<'_, B>, info: &RenderingInfo, area: Rect) {
+        self.repaint = false;
+        let block = Block::default().title(self.title()).borders(Borders::ALL);
+
+        let async_statistics = match self.async_statistics.as_mut() {
+            Some(statistics) => statistics,
+            None => {
+                draw_text_info(frame, area, block, "Nothing selected");
+                return;
+            }
+        };
+
+        match async_statistics.state() {
+            AsyncDspDataState::Created
+            | AsyncDspDataState::Normalizing
+            | AsyncDspDataState::Processing => {
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Measuring",
+                    async_statistics.progress(),
+                    async_statistics.eta(),
+                );
+                return;
+            }
+            AsyncDspDataState::Cancelled => {
+                draw_text_info(frame, area, block, "Cancelled");
+                return;
+            }
+            AsyncDspDataState::Failed(err) => {
+                draw_text_info(frame, area, block, &format!("Error : {}", err));
+                return;
+            }
+            AsyncDspDataState::Finished => {}
+        }
+
+        let statistics = async_statistics.data().unwrap();
+        let name_style = Style::default().add_modifier(Modifier::BOLD);
+        let spans: Vec<Spans> = info
+            .activated_channels
+            .iter()
+            .map(|(ch_idx, name)| {
+                let channel = statistics.channel(*ch_idx);
+                let crest = channel
+                    .crest_factor()
+                    .map_or("n/a".to_string(), |crest| format!("{:.1} dB", crest));
+                Spans::from(vec![
+                    Span::styled(*name, name_style),
+                    Span::raw(format!(
+                        " : peak {:.1} dBFS | RMS {:.1} dBFS | crest {} | DC {:+.5} | clipped {}",
+                        to_dbfs(channel.peak),
+                        to_dbfs(channel.rms),
+                        crest,
+                        channel.dc_offset,
+                        channel.clipped
+                    )),
+                ])
+            })
+            .collect();
+
+        let paragraph = Paragraph::new(spans)
+            .block(block)
+            .alignment(Alignment::Left);
+
+        frame.render_widget(paragraph, area);
+    }
+}
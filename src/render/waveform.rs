@@ -1,15 +1,25 @@
-use super::{draw_text_info, renderer::ChannelRenderer};
+use super::{draw_progress, draw_text_info, renderer::ChannelRenderer};
 use core::panic;
 extern crate sndfile;
-use crate::utils::Zoom;
+use crate::utils::{i32_to_dbfs, to_dbfs, AmplitudeMapping, AmplitudeScale, Cursor, Zoom};
 use std::convert::TryFrom;
 use tui::backend::Backend;
 use tui::layout::Rect;
+use tui::style::{Color, Style};
 use tui::symbols::Marker;
-use tui::widgets::canvas::{Canvas, Context, Line};
-use tui::{style::Color, widgets::Block, Frame};
+use tui::text::Span;
+use tui::widgets::canvas::{Canvas, Context, Line, Points};
+use tui::widgets::Paragraph;
+use tui::{widgets::Block, Frame};
 
-use crate::dsp::{AsyncDspData, AsyncDspDataState, Waveform, WaveformParameters, WaveformPoint};
+use crate::dsp::{
+    AnalysisCache, AsyncDspData, AsyncDspDataState, ClipEvent, Clipping, ClippingParameters,
+    Waveform, WaveformParameters, WaveformPoint,
+};
+use crate::export::{export_waveform, ExportOptions};
+use crate::utils::axis::format_time;
+use std::io;
+use std::path::Path;
 
 // fn draw_outlined_shape(ctx: &mut Context, n_int: &Vec<i32>, p_int: &Vec<i32>) {
 //     let mut previous_idx = 0usize;
@@ -52,18 +62,48 @@ use crate::dsp::{AsyncDspData, AsyncDspDataState, Waveform, WaveformParameters,
 //     }
 // }
 
-fn draw_shape(ctx: &mut Context, points: &[WaveformPoint<i32>]) {
+/// Smallest top amplitude, relative to full scale, reachable by zooming in or
+/// fitting (-120 dBFS)
+const MIN_TOP: f64 = 1e-6;
+/// Amplitude ratio of each vertical zoom step (~6 dB)
+const VERTICAL_ZOOM_FACTOR: f64 = 2f64;
+
+/// Color of the parts of the waveform holding clipped samples
+const CLIP_COLOR: Color = Color::Red;
+/// Color of the mean line, and of the DC offset flag
+const DC_COLOR: Color = Color::Yellow;
+
+fn draw_shape(
+    ctx: &mut Context,
+    points: &[WaveformPoint<i32>],
+    clipped: &[bool],
+    mapping: &AmplitudeMapping,
+) {
     let mut prev_peak_up = 0f64;
     let mut prev_peak_down = 0f64;
+    let mut prev_mean = 0f64;
+    let color = |idx: usize| match clipped.get(idx) {
+        Some(true) => CLIP_COLOR,
+        _ => Color::White,
+    };
 
     for (idx, points) in points.iter().enumerate() {
-        // Draw inner RMS shape
+        let peak_up = mapping.map(points.peak_max as f64);
+        let peak_down = mapping.map(points.peak_min as f64);
+        let rms = mapping.map(points.rms as f64);
+        let mean = mapping.map(points.mean as f64);
+
+        // Draw inner RMS shape, the whole height of the clipped points
+        let (y1, y2) = match clipped.get(idx) {
+            Some(true) => (peak_down, peak_up),
+            _ => (-rms, rms),
+        };
         ctx.draw(&Line {
             x1: idx as f64,
             x2: idx as f64,
-            y1: -(points.rms as f64),
-            y2: points.rms as f64,
-            color: Color::White,
+            y1,
+            y2,
+            color: color(idx),
         });
 
         if idx != 0usize {
@@ -72,43 +112,210 @@ fn draw_shape(ctx: &mut Context, points: &[WaveformPoint<i32>]) {
                 x1: idx as f64 - 1f64,
                 x2: idx as f64,
                 y1: prev_peak_up,
-                y2: points.peak_max as f64,
-                color: Color::White,
+                y2: peak_up,
+                color: color(idx),
             });
 
             ctx.draw(&Line {
                 x1: idx as f64 - 1f64,
                 x2: idx as f64,
                 y1: prev_peak_down,
-                y2: points.peak_min as f64,
-                color: Color::White,
+                y2: peak_down,
+                color: color(idx),
+            });
+
+            // Draw the mean over the rest, to show the DC offset drifting
+            ctx.draw(&Line {
+                x1: idx as f64 - 1f64,
+                x2: idx as f64,
+                y1: prev_mean,
+                y2: mean,
+                color: DC_COLOR,
             });
         }
 
-        prev_peak_down = points.peak_min as f64;
-        prev_peak_up = points.peak_max as f64;
+        prev_peak_down = peak_down;
+        prev_peak_up = peak_up;
+        prev_mean = mean;
+    }
+}
+
+/// How far the waveform can be zoomed in, as the number of terminal columns a
+/// single sample can span
+const MAX_COLUMNS_PER_SAMPLE: usize = 4;
+
+/// Minimum spacing between samples, in canvas points, to draw them as stems
+const STEM_MIN_SPACING: f64 = 3f64;
+
+/// Draw samples as stems topped with dots, joined by lines. Clipped samples
+/// and the lines reaching them are highlighted
+///
+/// * 'offset' - Position of the first sample, in canvas points
+/// * 'spacing' - Distance between two samples, in canvas points
+fn draw_samples(
+    ctx: &mut Context,
+    samples: &[i32],
+    clipped: &[bool],
+    offset: f64,
+    spacing: f64,
+    mapping: &AmplitudeMapping,
+) {
+    let coords: Vec<(f64, f64)> = samples
+        .iter()
+        .enumerate()
+        .map(|(idx, sample)| (offset + idx as f64 * spacing, mapping.map(*sample as f64)))
+        .collect();
+    let is_clipped = |idx: usize| clipped.get(idx).copied().unwrap_or(false);
+
+    for (idx, pair) in coords.windows(2).enumerate() {
+        ctx.draw(&Line {
+            x1: pair[0].0,
+            y1: pair[0].1,
+            x2: pair[1].0,
+            y2: pair[1].1,
+            color: if is_clipped(idx) || is_clipped(idx + 1) {
+                CLIP_COLOR
+            } else {
+                Color::White
+            },
+        });
     }
+
+    if spacing >= STEM_MIN_SPACING {
+        for (x, y) in coords.iter() {
+            ctx.draw(&Line {
+                x1: *x,
+                y1: 0f64,
+                x2: *x,
+                y2: *y,
+                color: Color::Gray,
+            });
+        }
+        ctx.draw(&Points {
+            coords: &coords,
+            color: Color::White,
+        });
+    }
+
+    // Over the rest, even when the samples are too close for stems
+    let clipped_coords: Vec<(f64, f64)> = coords
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| is_clipped(*idx))
+        .map(|(_, coords)| *coords)
+        .collect();
+    ctx.draw(&Points {
+        coords: &clipped_coords,
+        color: CLIP_COLOR,
+    });
 }
 
 pub struct WaveformRenderer {
     channels: usize,
     async_renderer: AsyncDspData<Waveform, WaveformParameters>,
+    clipping: AsyncDspData<Clipping, ClippingParameters>,
     max_width_res: usize,
+    amplitude_scale: AmplitudeScale,
+    // Top amplitude of the vertical zoom, relative to full scale
+    top: f64,
+    // Fit the top to the peak of the visible window instead
+    auto_fit: bool,
+    // Top used by the latest drawing of each channel, for the rulers
+    drawn_tops: Vec<f64>,
+    // DC offset, relative to full scale, from which channels are flagged
+    dc_threshold: f64,
 }
 
 impl WaveformRenderer {
-    pub fn new(path: &std::path::PathBuf, normalize: bool) -> WaveformRenderer {
+    pub fn new(
+        path: &std::path::PathBuf,
+        normalize: bool,
+        clipping: ClippingParameters,
+        dc_threshold: f64,
+        cache: Option<AnalysisCache>,
+    ) -> WaveformRenderer {
         let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
             .from_path(path)
             .expect("Could not open wave file");
 
         let channels = snd.get_channels();
-        let max_res = usize::try_from(snd.len().unwrap()).unwrap();
+        let max_res = usize::try_from(snd.len().unwrap()).unwrap() * MAX_COLUMNS_PER_SAMPLE;
 
         WaveformRenderer {
             channels,
-            async_renderer: AsyncDspData::new(path, WaveformParameters, normalize),
+            async_renderer: AsyncDspData::new(path, WaveformParameters, normalize, cache.clone()),
+            clipping: AsyncDspData::new(path, clipping, false, cache),
             max_width_res: max_res,
+            amplitude_scale: AmplitudeScale::Linear,
+            top: 1f64,
+            auto_fit: false,
+            drawn_tops: vec![1f64; channels],
+            dc_threshold,
+        }
+    }
+
+    /// Switch to the next amplitude scale, and return it
+    pub fn next_amplitude_scale(&mut self) -> AmplitudeScale {
+        self.amplitude_scale = self.amplitude_scale.next();
+        self.amplitude_scale
+    }
+
+    /// Magnify the waveform vertically, leaving the auto-fit mode. Returns the
+    /// new top amplitude in dBFS
+    pub fn vertical_zoom_in(&mut self) -> f64 {
+        self.auto_fit = false;
+        self.top = (self.top / VERTICAL_ZOOM_FACTOR).max(MIN_TOP);
+        to_dbfs(self.top)
+    }
+
+    /// Shrink the waveform vertically, down to full scale, leaving the
+    /// auto-fit mode. Returns the new top amplitude in dBFS
+    pub fn vertical_zoom_out(&mut self) -> f64 {
+        self.auto_fit = false;
+        self.top = (self.top * VERTICAL_ZOOM_FACTOR).min(1f64);
+        to_dbfs(self.top)
+    }
+
+    /// Toggle the scaling to the peak of the visible window, and return
+    /// whether it is enabled
+    pub fn toggle_auto_fit(&mut self) -> bool {
+        self.auto_fit = !self.auto_fit;
+        self.auto_fit
+    }
+
+    /// Clip events of all the channels, sorted by start. Empty until they are
+    /// detected
+    pub fn clip_events(&mut self) -> &[ClipEvent] {
+        self.clipping.update_status();
+        match self.clipping.data() {
+            Some(clipping) => clipping.events(),
+            None => &[],
+        }
+    }
+
+    fn mapping(&self, channel: usize) -> AmplitudeMapping {
+        AmplitudeMapping {
+            scale: self.amplitude_scale,
+            top: self.drawn_tops[channel],
+        }
+    }
+
+    /// Export the current view of the given channels to a png or svg file
+    pub fn export(
+        &mut self,
+        channels: &[usize],
+        zoom: &Zoom,
+        options: &ExportOptions,
+        path: &Path,
+    ) -> io::Result<()> {
+        // As scaled by the latest drawing
+        let mappings: Vec<AmplitudeMapping> = channels
+            .iter()
+            .map(|channel| self.mapping(*channel))
+            .collect();
+        match self.async_renderer.data() {
+            Some(data_ref) => export_waveform(data_ref, channels, &mappings, zoom, options, path),
+            None => Err(io::Error::other("Waveform is not computed yet")),
         }
     }
 }
@@ -124,16 +331,33 @@ impl ChannelRenderer for WaveformRenderer {
     ) {
         match self.async_renderer.state() {
             AsyncDspDataState::Normalizing => {
-                draw_text_info(frame, area, block, "Normalizing...");
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Normalizing",
+                    self.async_renderer.progress(),
+                    self.async_renderer.eta(),
+                );
                 return;
             }
             AsyncDspDataState::Created | AsyncDspDataState::Processing => {
-                draw_text_info(frame, area, block, "Loading...");
+                draw_progress(
+                    frame,
+                    area,
+                    block,
+                    "Loading",
+                    self.async_renderer.progress(),
+                    self.async_renderer.eta(),
+                );
+                return;
+            }
+            AsyncDspDataState::Cancelled => {
+                draw_text_info(frame, area, block, "Cancelled");
                 return;
             }
-            AsyncDspDataState::Failed => {
-                // Should crash soon
-                draw_text_info(frame, area, block, "Error");
+            AsyncDspDataState::Failed(err) => {
+                draw_text_info(frame, area, block, &format!("Error : {}", err));
                 return;
             }
             _ => {}
@@ -145,28 +369,123 @@ impl ChannelRenderer for WaveformRenderer {
 
         // Prepare
         let data_ref = self.async_renderer.data().unwrap();
+        let dc_offset = data_ref.dc_offset(channel);
         let canva_width_int = area.width as usize - 2;
         let estimated_witdh_res = canva_width_int * 2; // Braille res is 2 per char
 
-        // Compute local min & max for each block
-        let points = data_ref.compute_points(channel, estimated_witdh_res, zoom);
+        // Draw each sample when there are fewer than the resolution, local min &
+        // max for each block otherwise
+        let visible_frames = data_ref.visible_frames(zoom);
+        let (samples, points) = if visible_frames <= estimated_witdh_res as f64 {
+            (Some(data_ref.compute_samples(channel, zoom)), vec![])
+        } else {
+            let points = data_ref.compute_points(channel, estimated_witdh_res, zoom);
+            (None, points)
+        };
+
+        // Samples or points holding clipped samples
+        let (first_frame, block_size, count) = match &samples {
+            Some((_, samples)) => (data_ref.first_visible_frame(zoom), 1, samples.len()),
+            None => {
+                let (start, block_size) = data_ref.points_layout(estimated_witdh_res, zoom);
+                (start, block_size, points.len())
+            }
+        };
+        let clipped = match self.clipping.data() {
+            Some(clipping) => clipping.clipped_blocks(channel, first_frame, block_size, count),
+            None => vec![],
+        };
+
+        // Vertical scaling
+        self.drawn_tops[channel] = if self.auto_fit {
+            let peak = match &samples {
+                Some((_, samples)) => samples
+                    .iter()
+                    .map(|sample| (*sample as f64).abs())
+                    .fold(0f64, f64::max),
+                None => points
+                    .iter()
+                    .map(|point| f64::max(point.peak_max as f64, -(point.peak_min as f64)))
+                    .fold(0f64, f64::max),
+            };
+            (peak / i32::MAX as f64).clamp(MIN_TOP, 1f64)
+        } else {
+            self.top
+        };
+        let mapping = self.mapping(channel);
+
+        let paint: Box<dyn Fn(&mut Context)> = match samples {
+            Some((offset, samples)) => {
+                let spacing = estimated_witdh_res as f64 / visible_frames;
+                Box::new(move |ctx| {
+                    draw_samples(ctx, &samples, &clipped, offset * spacing, spacing, &mapping)
+                })
+            }
+            None => Box::new(move |ctx| draw_shape(ctx, &points, &clipped, &mapping)),
+        };
 
         // Draw the canva
         let canva = Canvas::default()
             .block(block)
-            .paint(|ctx| draw_shape(ctx, &points))
+            .paint(|ctx| paint(ctx))
             .marker(Marker::Braille)
             .x_bounds([-1., estimated_witdh_res as f64 + 1f64])
-            .y_bounds([i32::MIN as f64, i32::MAX as f64]);
+            .y_bounds([-1f64, 1f64]);
 
-        frame.render_widget(canva, area)
+        frame.render_widget(canva, area);
+
+        // Flag a DC offset over the threshold on the top border
+        if dc_offset.abs() >= self.dc_threshold {
+            let flag = format!(" DC offset {:+.4} ", dc_offset);
+            let width = u16::min(flag.len() as u16, area.width.saturating_sub(2));
+            let flag_area = Rect {
+                x: area.x + area.width - 1 - width,
+                y: area.y,
+                width,
+                height: 1,
+            };
+            frame.render_widget(
+                Paragraph::new(Span::styled(flag, Style::default().fg(DC_COLOR))),
+                flag_area,
+            );
+        }
     }
 
     fn needs_redraw(&mut self) -> bool {
-        self.async_renderer.update_status()
+        self.async_renderer.update_status() | self.clipping.update_status()
     }
 
     fn max_width_resolution(&self) -> usize {
         self.max_width_res
     }
+
+    fn vertical_ruler(&mut self, channel: usize, max_count: usize) -> Option<Vec<(f64, String)>> {
+        let mapping = self.mapping(channel);
+        self.async_renderer
+            .data()
+            .map(|_| mapping.labels(max_count))
+    }
+
+    fn duration(&mut self) -> Option<f64> {
+        self.async_renderer.data().map(|data| data.duration())
+    }
+
+    fn read_cursor(&mut self, channels: &[(usize, &str)], cursor: &Cursor) -> Option<String> {
+        let data_ref = self.async_renderer.data()?;
+        let frame = data_ref.frame_at(cursor.position());
+        let values: Vec<String> = channels
+            .iter()
+            .map(|(channel, name)| match data_ref.sample(*channel, frame) {
+                Some(sample) => format!("{} {:.1} dBFS", name, i32_to_dbfs(sample)),
+                None => format!("{} -", name),
+            })
+            .collect();
+
+        Some(format!(
+            "{} | Sample {} | {}",
+            format_time(data_ref.frame_time(frame)),
+            frame,
+            values.join(", ")
+        ))
+    }
 }
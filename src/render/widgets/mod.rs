@@ -1,3 +1,7 @@
 mod image;
+mod ruler;
+mod shade;
 
 pub use image::*;
+pub use ruler::*;
+pub use shade::*;
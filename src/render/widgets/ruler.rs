@@ -0,0 +1,87 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::widgets::Widget;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulerOrientation {
+    /// Labels on the left of the area, from the bottom (0) to the top (1)
+    Vertical,
+    /// Labels under the area, from the left (0) to the right (1)
+    Horizontal,
+}
+
+/// Axis labels placed at relative positions along an area
+pub struct Ruler<'a> {
+    orientation: RulerOrientation,
+    labels: &'a [(f64, String)],
+    style: Style,
+}
+
+impl<'a> Ruler<'a> {
+    pub fn new(orientation: RulerOrientation, labels: &'a [(f64, String)]) -> Ruler<'a> {
+        Ruler {
+            orientation,
+            labels,
+            style: Style::default().fg(Color::Gray),
+        }
+    }
+
+    fn render_vertical(self, area: Rect, buf: &mut Buffer) {
+        let max_label_width = usize::from(area.width.saturating_sub(1));
+
+        for (position, label) in self.labels {
+            let offset = (position * f64::from(area.height - 1)).round() as u16;
+            let y = area.bottom() - 1 - offset.min(area.height - 1);
+
+            // Right aligned label, followed by the tick
+            let label: String = label.chars().take(max_label_width).collect();
+            let x = area.right() - 1 - label.chars().count() as u16;
+            buf.set_string(x, y, &label, self.style);
+            buf.get_mut(area.right() - 1, y)
+                .set_char('┤')
+                .set_style(self.style);
+        }
+    }
+
+    fn render_horizontal(self, area: Rect, buf: &mut Buffer) {
+        let mut free_from = area.left();
+
+        for (position, label) in self.labels {
+            let offset = (position * f64::from(area.width - 1)).round() as u16;
+            let x = area.left() + offset.min(area.width - 1);
+            buf.get_mut(x, area.top())
+                .set_char('┬')
+                .set_style(self.style);
+
+            if area.height < 2 {
+                continue;
+            }
+
+            // Centered under the tick, skipped if it would overlap the previous one
+            let width = label.chars().count() as u16;
+            let label_x = x
+                .saturating_sub(width / 2)
+                .max(area.left())
+                .min(area.right().saturating_sub(width));
+            if label_x < free_from {
+                continue;
+            }
+            buf.set_string(label_x, area.top() + 1, label, self.style);
+            free_from = label_x + width + 1;
+        }
+    }
+}
+
+impl<'a> Widget for Ruler<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        match self.orientation {
+            RulerOrientation::Vertical => self.render_vertical(area, buf),
+            RulerOrientation::Horizontal => self.render_horizontal(area, buf),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Color;
+use tui::widgets::Widget;
+
+/// Background of the shaded cells that had none
+const SHADE_BACKGROUND: Color = Color::DarkGray;
+
+fn darken(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(r / 2, g / 2, b / 2),
+        color => color,
+    }
+}
+
+/// Darkens what is already drawn in its area, keeping it readable
+pub struct Shade;
+
+impl Widget for Shade {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                let bg = match cell.bg {
+                    Color::Reset | Color::Black => SHADE_BACKGROUND,
+                    bg => darken(bg),
+                };
+                let fg = darken(cell.fg);
+                cell.set_bg(bg).set_fg(fg);
+            }
+        }
+    }
+}
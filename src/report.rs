@@ -0,0 +1,400 @@
+use serde_json::{json, Map, Value};
+use std::fmt::{Display, Write};
+
+use crate::dsp::{
+    CancelToken, ClipEvent, ClippingParameters, DspErr, Overview, ProgressReporter,
+    SilenceParameters, SilentRegion, TruePeakLocation,
+};
+use crate::render::{ChannelsTabs, Metadata};
+use crate::utils::axis::format_time;
+use crate::utils::to_dbfs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReportFormatParseError;
+
+impl Display for ReportFormatParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid report format")
+    }
+}
+
+const TEXT: &str = "text";
+const JSON: &str = "json";
+
+impl ReportFormat {
+    pub fn parse(name: &str) -> Result<Self, ReportFormatParseError> {
+        if name == TEXT {
+            Ok(Self::Text)
+        } else if name == JSON {
+            Ok(Self::Json)
+        } else {
+            Err(ReportFormatParseError)
+        }
+    }
+
+    pub fn possible_values() -> &'static [&'static str] {
+        &[TEXT, JSON]
+    }
+
+    pub fn default() -> &'static str {
+        TEXT
+    }
+}
+
+struct ChannelReport {
+    name: String,
+    peak: f64,
+    rms: f64,
+    true_peak: f64,
+    clip_events: usize,
+    dc_offset: f64,
+    dc_flagged: bool,
+}
+
+/// The facts displayed by the Metadata tab, along with a few levels measured
+/// on the signal, gathered without any terminal interface
+pub struct Report {
+    path: String,
+    metadata: Metadata,
+    norm: f64,
+    samplerate: f64,
+    channels: Vec<ChannelReport>,
+    true_peaks: Vec<TruePeakLocation>,
+    clip_events: Vec<ClipEvent>,
+    silences: Vec<SilentRegion>,
+}
+
+/// Turn a property name into a snake case key for machine-readable formats
+fn to_key(name: &str) -> String {
+    name.to_lowercase().replace(' ', "_")
+}
+
+impl Report {
+    /// Measure a file, flagging the channels with a DC offset of at least
+    /// `dc_threshold`, relative to full scale
+    pub fn new(
+        path: &std::path::PathBuf,
+        clipping: ClippingParameters,
+        silence: SilenceParameters,
+        dc_threshold: f64,
+    ) -> Result<Self, DspErr> {
+        let snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(path)
+            .map_err(|_| DspErr::new("Could not open audio file"))?;
+        let samplerate = snd.get_samplerate() as f64;
+        let titles = ChannelsTabs::get_channels_titles(snd.get_channels());
+        let overview = Overview::new(
+            snd,
+            clipping,
+            silence,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )?;
+
+        let channels = titles
+            .into_iter()
+            .enumerate()
+            .map(|(ch_idx, name)| {
+                let levels = overview.levels.channel(ch_idx);
+                ChannelReport {
+                    name,
+                    peak: levels.peak,
+                    rms: levels.rms,
+                    true_peak: overview.true_peak.channel(ch_idx),
+                    clip_events: overview.clipping.count(ch_idx),
+                    dc_offset: levels.dc_offset,
+                    dc_flagged: levels.dc_offset.abs() >= dc_threshold,
+                }
+            })
+            .collect();
+
+        Ok(Report {
+            path: path.display().to_string(),
+            metadata: Metadata::new(path),
+            norm: overview.norm,
+            samplerate,
+            channels,
+            true_peaks: overview.true_peak.locations().to_vec(),
+            clip_events: overview.clipping.events().to_vec(),
+            silences: overview.silence.regions().to_vec(),
+        })
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => self.to_json(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        let _ = writeln!(text, "File : {}", self.path);
+
+        let _ = writeln!(text, "\nProperties");
+        for (name, value) in self.metadata.properties() {
+            let _ = writeln!(text, "  {} : {}", name, value);
+        }
+
+        let _ = writeln!(text, "\nTags");
+        for (name, value) in self.metadata.tags() {
+            let _ = writeln!(text, "  {} : {}", name, value);
+        }
+
+        let _ = writeln!(
+            text,
+            "\nNormalization peak : {:.6} ({:.2} dBFS)",
+            self.norm,
+            to_dbfs(self.norm)
+        );
+
+        let _ = writeln!(text, "\nChannels");
+        for (ch_idx, channel) in self.channels.iter().enumerate() {
+            let _ = writeln!(
+                text,
+                "  [{}] {} : peak {:.2} dBFS / rms {:.2} dBFS / true peak {:.2} dBTP / {} clip events / DC offset {:+.6}{}",
+                ch_idx,
+                channel.name,
+                to_dbfs(channel.peak),
+                to_dbfs(channel.rms),
+                to_dbfs(channel.true_peak),
+                channel.clip_events,
+                channel.dc_offset,
+                if channel.dc_flagged { " (over threshold)" } else { "" }
+            );
+        }
+
+        let _ = writeln!(text, "\nHighest true peaks");
+        for (rank, location) in self.true_peaks.iter().enumerate() {
+            let _ = writeln!(
+                text,
+                "  #{} : {:.2} dBTP on {} at {} (frame {})",
+                rank + 1,
+                to_dbfs(location.peak),
+                self.channels[location.channel].name,
+                format_time(location.frame as f64 / self.samplerate),
+                location.frame
+            );
+        }
+
+        let _ = writeln!(text, "\nClip events");
+        for event in self.clip_events.iter() {
+            let _ = writeln!(
+                text,
+                "  {} samples on {} at {} (frame {})",
+                event.length,
+                self.channels[event.channel].name,
+                format_time(event.start as f64 / self.samplerate),
+                event.start
+            );
+        }
+
+        let _ = writeln!(text, "\nSilences");
+        for region in self.silences.iter() {
+            let _ = writeln!(
+                text,
+                "  {} on {} : {} - {} ({}, frames {} - {})",
+                region.kind.name(),
+                self.channels[region.channel].name,
+                format_time(region.start as f64 / self.samplerate),
+                format_time(region.end() as f64 / self.samplerate),
+                format_time(region.length as f64 / self.samplerate),
+                region.start,
+                region.end()
+            );
+        }
+
+        text
+    }
+
+    fn to_json(&self) -> String {
+        let to_map = |pairs: &[(&str, &str)]| -> Map<String, Value> {
+            pairs
+                .iter()
+                .map(|(name, value)| (to_key(name), json!(value)))
+                .collect()
+        };
+
+        let channels: Vec<Value> = self
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(ch_idx, channel)| {
+                let mut channel_json = json!({
+                    "index": ch_idx,
+                    "name": channel.name,
+                    "peak": channel.peak,
+                    "peak_dbfs": to_dbfs(channel.peak),
+                    "rms": channel.rms,
+                    "rms_dbfs": to_dbfs(channel.rms),
+                    "true_peak": channel.true_peak,
+                    "true_peak_dbtp": to_dbfs(channel.true_peak),
+                    "clip_events": channel.clip_events,
+                    "dc_offset": channel.dc_offset,
+                    "dc_offset_flagged": channel.dc_flagged,
+                });
+                // No level for a null offset, rather than an infinite one
+                if channel.dc_offset != 0f64 {
+                    channel_json["dc_offset_dbfs"] = json!(to_dbfs(channel.dc_offset.abs()));
+                }
+                channel_json
+            })
+            .collect();
+
+        let true_peaks: Vec<Value> = self
+            .true_peaks
+            .iter()
+            .map(|location| {
+                json!({
+                    "channel": location.channel,
+                    "frame": location.frame,
+                    "time": location.frame as f64 / self.samplerate,
+                    "true_peak": location.peak,
+                    "true_peak_dbtp": to_dbfs(location.peak),
+                })
+            })
+            .collect();
+
+        let clip_events: Vec<Value> = self
+            .clip_events
+            .iter()
+            .map(|event| {
+                json!({
+                    "channel": event.channel,
+                    "frame": event.start,
+                    "time": event.start as f64 / self.samplerate,
+                    "length": event.length,
+                })
+            })
+            .collect();
+
+        let silences: Vec<Value> = self
+            .silences
+            .iter()
+            .map(|region| {
+                json!({
+                    "channel": region.channel,
+                    "kind": region.kind.name(),
+                    "frame": region.start,
+                    "time": region.start as f64 / self.samplerate,
+                    "length": region.length,
+                    "duration": region.length as f64 / self.samplerate,
+                })
+            })
+            .collect();
+
+        let report = json!({
+            "path": self.path,
+            "properties": to_map(&self.metadata.properties()),
+            "tags": to_map(&self.metadata.tags()),
+            "normalization_peak": self.norm,
+            "channels": channels,
+            "highest_true_peaks": true_peaks,
+            "clip_events": clip_events,
+            "silences": silences,
+        });
+
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Report, ReportFormat};
+    use crate::dsp::{
+        compute_norm, CancelToken, ClippingParameters, ProgressReporter, SilenceParameters,
+    };
+    use std::path::{Path, PathBuf};
+
+    fn get_test_files_location() -> PathBuf {
+        Path::new(&env!("CARGO_MANIFEST_DIR").to_string())
+            .join("tests")
+            .join("files")
+    }
+
+    #[test]
+    fn parse_all() {
+        ReportFormat::parse(ReportFormat::default()).unwrap();
+        for value in ReportFormat::possible_values() {
+            ReportFormat::parse(value).unwrap();
+        }
+    }
+
+    #[test]
+    fn json_is_valid() {
+        let clipping = ClippingParameters {
+            threshold: -0.01,
+            min_run: 3,
+        };
+        let silence = SilenceParameters {
+            threshold: -60f64,
+            min_duration: 0.1,
+        };
+        let report = Report::new(
+            &get_test_files_location().join("rock_1s.wav"),
+            clipping,
+            silence,
+            0.01f64,
+        )
+        .unwrap();
+        let value: serde_json::Value =
+            serde_json::from_str(&report.render(ReportFormat::Json)).unwrap();
+
+        assert_eq!(
+            value["channels"].as_array().unwrap().len(),
+            report.channels.len()
+        );
+        assert!(value["normalization_peak"].as_f64().unwrap() > 0f64);
+        for channel in value["channels"].as_array().unwrap() {
+            assert!(channel["true_peak"].as_f64().unwrap() >= channel["peak"].as_f64().unwrap());
+            assert!(
+                channel["dc_offset"].as_f64().unwrap().abs() <= channel["rms"].as_f64().unwrap()
+            );
+            // Either a finite level or none
+            if let Some(level) = channel.get("dc_offset_dbfs") {
+                assert!(level.as_f64().unwrap().is_finite());
+            }
+        }
+        assert!(!value["highest_true_peaks"].as_array().unwrap().is_empty());
+        assert_eq!(
+            value["clip_events"].as_array().unwrap().len(),
+            report.channels.iter().map(|c| c.clip_events).sum::<usize>()
+        );
+        assert_eq!(
+            value["silences"].as_array().unwrap().len(),
+            report.silences.len()
+        );
+    }
+
+    #[test]
+    fn norm() {
+        let path = get_test_files_location().join("rock_1s.wav");
+        let clipping = ClippingParameters {
+            threshold: -0.01,
+            min_run: 3,
+        };
+        let silence = SilenceParameters {
+            threshold: -60f64,
+            min_duration: 0.1,
+        };
+        let report = Report::new(&path, clipping, silence, 0.01f64).unwrap();
+
+        let mut snd = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+            .from_path(&path)
+            .unwrap();
+        let norm = compute_norm(
+            &mut snd,
+            &CancelToken::default(),
+            &mut ProgressReporter::default(),
+        )
+        .unwrap();
+        assert_eq!(report.norm, norm);
+    }
+}
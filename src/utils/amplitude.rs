@@ -0,0 +1,72 @@
+use super::axis::{amplitude_labels, db_amplitude_labels};
+use super::to_dbfs;
+
+/// Vertical scale of the waveform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmplitudeScale {
+    Linear,
+    Decibel,
+}
+
+impl AmplitudeScale {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AmplitudeScale::Linear => "linear",
+            AmplitudeScale::Decibel => "dBFS",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            AmplitudeScale::Linear => AmplitudeScale::Decibel,
+            AmplitudeScale::Decibel => AmplitudeScale::Linear,
+        }
+    }
+}
+
+/// Levels shown under the top of the dBFS scale
+const DB_SPAN: f64 = 60f64;
+
+/// Maps sample values into [-1; 1] coordinates, the top amplitude, relative
+/// to full scale, reaching the edges
+#[derive(Debug, Clone, Copy)]
+pub struct AmplitudeMapping {
+    pub scale: AmplitudeScale,
+    pub top: f64,
+}
+
+impl AmplitudeMapping {
+    /// Linear scale, full scale reaching the edges
+    pub fn full_scale() -> Self {
+        AmplitudeMapping {
+            scale: AmplitudeScale::Linear,
+            top: 1f64,
+        }
+    }
+
+    pub fn map(&self, value: f64) -> f64 {
+        let amplitude = value / i32::MAX as f64;
+
+        match self.scale {
+            AmplitudeScale::Linear => (amplitude / self.top).clamp(-1f64, 1f64),
+            AmplitudeScale::Decibel => {
+                // Mirrored around the center, where the floor is. Silence maps
+                // to -inf, hence to the center too
+                let floor = to_dbfs(self.top) - DB_SPAN;
+                let position = ((to_dbfs(amplitude.abs()) - floor) / DB_SPAN).clamp(0f64, 1f64);
+                position.copysign(amplitude)
+            }
+        }
+    }
+
+    /// Labels of the vertical axis, as (relative position, text) pairs
+    pub fn labels(&self, max_count: usize) -> Vec<(f64, String)> {
+        match self.scale {
+            AmplitudeScale::Linear => amplitude_labels(self.top, max_count),
+            AmplitudeScale::Decibel => {
+                let top = to_dbfs(self.top);
+                db_amplitude_labels(top - DB_SPAN, top, max_count)
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+/// Compute evenly spaced "round" values (1, 2 or 5 times a power of ten)
+/// contained within [start; end], with at most `max_count` values
+pub fn ticks(start: f64, end: f64, max_count: usize) -> Vec<f64> {
+    let range = end - start;
+    if max_count == 0 || range <= 0f64 || !range.is_finite() {
+        return vec![];
+    }
+
+    let raw_step = range / max_count as f64;
+    let magnitude = 10f64.powf(raw_step.log10().floor());
+    let step = [1f64, 2f64, 5f64, 10f64]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= raw_step)
+        .unwrap_or(10f64 * magnitude);
+
+    // Multiply instead of accumulating to avoid drifting away from round values
+    let first = (start / step).ceil() as i64;
+    (first..)
+        .map(|idx| idx as f64 * step)
+        .take_while(|value| *value <= end)
+        .collect()
+}
+
+/// Labels of a time axis spanning [start; end] seconds, as (relative position,
+/// text) pairs
+pub fn time_labels(start: f64, end: f64, max_count: usize) -> Vec<(f64, String)> {
+    ticks(start, end, max_count)
+        .into_iter()
+        .map(|time| ((time - start) / (end - start), format_time(time)))
+        .collect()
+}
+
+/// Labels of a linear amplitude axis spanning [-top; top], as (relative
+/// position, text) pairs
+pub fn amplitude_labels(top: f64, max_count: usize) -> Vec<(f64, String)> {
+    // Enough decimals to tell small values apart
+    let decimals = (1 - top.log10().floor() as i64).max(1) as usize;
+
+    ticks(-top, top, max_count)
+        .into_iter()
+        .map(|amp| ((amp + top) / (2f64 * top), format!("{:.*}", decimals, amp)))
+        .collect()
+}
+
+/// Labels of a dBFS amplitude axis, mirrored around the center where the
+/// floor is, with the top at both ends, as (relative position, text) pairs
+pub fn db_amplitude_labels(floor: f64, top: f64, max_count: usize) -> Vec<(f64, String)> {
+    let mut labels = vec![];
+    for db in ticks(floor, top, max_count / 2) {
+        let position = (db - floor) / (top - floor) / 2f64;
+        let text = format!("{:.0}", db);
+
+        labels.push((0.5f64 - position, text.clone()));
+        if position > 0f64 {
+            labels.push((0.5f64 + position, text));
+        }
+    }
+
+    labels
+}
+
+/// Labels of a linear axis of levels in dB spanning [floor; top], as
+/// (relative position, text) pairs
+pub fn level_labels(floor: f64, top: f64, max_count: usize) -> Vec<(f64, String)> {
+    ticks(floor, top, max_count)
+        .into_iter()
+        .map(|db| ((db - floor) / (top - floor), format!("{:.0}", db)))
+        .collect()
+}
+
+/// Format a frequency for an axis label : 500, 1.5k, 20k
+pub fn format_frequency(hz: f64) -> String {
+    if hz >= 1000f64 {
+        let khz = hz / 1000f64;
+        if khz.fract().abs() < 1e-6 {
+            format!("{:.0}k", khz)
+        } else {
+            format!("{:.1}k", khz)
+        }
+    } else {
+        format!("{:.0}", hz)
+    }
+}
+
+/// Format a timestamp for an axis label : 0.25s, 12s, 1:05, 1:02:05.5
+/// Sub-second digits are kept up to the microsecond, so labels stay distinct
+/// when zoomed in
+pub fn format_time(seconds: f64) -> String {
+    let rounded = (seconds * 1e6).round() / 1e6;
+    let whole = rounded.trunc() as u64;
+    let fract = format!("{:.6}", rounded.fract());
+    let fract = fract.trim_start_matches('0').trim_end_matches('0');
+    let fract = if fract == "." { "" } else { fract };
+
+    if whole < 60 {
+        format!("{}{}s", whole, fract)
+    } else {
+        let (h, m, s) = (whole / 3600, (whole / 60) % 60, whole % 60);
+        if h > 0 {
+            format!("{}:{:02}:{:02}{}", h, m, s, fract)
+        } else {
+            format!("{}:{:02}{}", m, s, fract)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_contained() {
+        let ranges = [(0f64, 1f64), (0f64, 22050f64), (12.3f64, 15.9f64)];
+
+        for (start, end) in ranges {
+            for max_count in 1..20 {
+                let values = ticks(start, end, max_count);
+
+                assert!(!values.is_empty());
+                assert!(values.len() <= max_count + 1);
+                for value in values {
+                    assert!(value >= start && value <= end);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ticks_empty_range() {
+        assert!(ticks(1f64, 1f64, 10).is_empty());
+        assert!(ticks(0f64, 1f64, 0).is_empty());
+    }
+
+    #[test]
+    fn amplitude_axes() {
+        let labels = amplitude_labels(1f64, 4);
+        assert_eq!(labels.first().unwrap(), &(0f64, "-1.0".to_string()));
+        assert_eq!(labels.last().unwrap(), &(1f64, "1.0".to_string()));
+
+        let labels = amplitude_labels(0.05f64, 4);
+        assert!(labels.iter().any(|(_, text)| text == "0.050"));
+        for (position, _) in labels {
+            assert!((0f64..=1f64).contains(&position));
+        }
+
+        let labels = level_labels(-60f64, 0f64, 6);
+        assert_eq!(labels.first().unwrap(), &(0f64, "-60".to_string()));
+        assert_eq!(labels.last().unwrap(), &(1f64, "0".to_string()));
+
+        let labels = db_amplitude_labels(-60f64, 0f64, 8);
+        assert!(labels.contains(&(0f64, "0".to_string())));
+        assert!(labels.contains(&(1f64, "0".to_string())));
+        assert_eq!(
+            labels
+                .iter()
+                .filter(|(position, _)| *position == 0.5f64)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(format_frequency(500f64), "500");
+        assert_eq!(format_frequency(1500f64), "1.5k");
+        assert_eq!(format_frequency(20000f64), "20k");
+        assert_eq!(format_time(2f64), "2s");
+        assert_eq!(format_time(0.5f64), "0.5s");
+        assert_eq!(format_time(65f64), "1:05");
+        assert_eq!(format_time(3725f64), "1:02:05");
+        assert_eq!(format_time(0.000125f64), "0.000125s");
+        assert_eq!(format_time(65.5f64), "1:05.5");
+    }
+}
@@ -13,11 +13,48 @@ pub const CHANNEL_SELECTOR_7: Key = Key::Char('7');
 pub const CHANNEL_SELECTOR_8: Key = Key::Char('8');
 pub const CHANNEL_SELECTOR_9: Key = Key::Char('9');
 pub const CHANNEL_RESET: Key = Key::Esc;
+pub const NEXT_CHANNEL_PAGE: Key = Key::Char('>');
+pub const PREVIOUS_CHANNEL_PAGE: Key = Key::Char('<');
+pub const CHANNEL_PROMPT: Key = Key::Char('r');
 pub const HELP: Key = Key::Char(' ');
 pub const ZOOM_IN: Key = Key::Char('k');
 pub const ZOOM_OUT: Key = Key::Char('j');
+pub const VERTICAL_ZOOM_IN: Key = Key::Char('K');
+pub const VERTICAL_ZOOM_OUT: Key = Key::Char('J');
+pub const MOVE_UP: Key = Key::Up;
+pub const MOVE_DOWN: Key = Key::Down;
+pub const AMPLITUDE_SCALE: Key = Key::Char('d');
+pub const AUTO_FIT: Key = Key::Char('a');
 pub const MOVE_LEFT: Key = Key::Char('h');
 pub const MOVE_RIGHT: Key = Key::Char('l');
+pub const CURSOR: Key = Key::Char('x');
+pub const CURSOR_LEFT: Key = Key::Alt('h');
+pub const CURSOR_RIGHT: Key = Key::Alt('l');
+pub const CURSOR_UP: Key = Key::Alt('k');
+pub const CURSOR_DOWN: Key = Key::Alt('j');
+pub const SELECTION_IN: Key = Key::Char('I');
+pub const SELECTION_OUT: Key = Key::Char('O');
+pub const ZOOM_SELECTION: Key = Key::Char('z');
+pub const CLEAR_SELECTION: Key = Key::Char('u');
+pub const NEXT_TRUE_PEAK: Key = Key::Char('n');
+pub const PREVIOUS_TRUE_PEAK: Key = Key::Char('N');
+pub const NEXT_CLIP: Key = Key::Char(')');
+pub const PREVIOUS_CLIP: Key = Key::Char('(');
+pub const STATISTICS: Key = Key::Char('s');
+pub const SILENCES: Key = Key::Char('g');
+pub const EXPORT_SELECTION: Key = Key::Char('E');
+pub const EXPORT: Key = Key::Char('e');
+pub const FREQUENCY_SCALE: Key = Key::Char('f');
+pub const COLORMAP: Key = Key::Char('c');
+pub const DB_FLOOR_DOWN: Key = Key::Char('[');
+pub const DB_FLOOR_UP: Key = Key::Char(']');
+pub const DB_CEILING_DOWN: Key = Key::Char('{');
+pub const DB_CEILING_UP: Key = Key::Char('}');
+pub const FFT_WINDOW_SIZE_UP: Key = Key::Char('w');
+pub const FFT_WINDOW_SIZE_DOWN: Key = Key::Char('W');
+pub const FFT_OVERLAP: Key = Key::Char('o');
+pub const FFT_WINDOW_TYPE: Key = Key::Char('t');
+pub const FFT_PADDING_TYPE: Key = Key::Char('p');
 
 // pub fn binding_iterat
 
@@ -51,6 +88,7 @@ pub fn key_to_string(key: &Key) -> String {
         Key::Alt(value) => format!("alt-{}", value),
         Key::Ctrl(value) => format!("ctl-{}", value),
         Key::Esc => String::from("Esc"),
+        Key::Backspace => String::from("Backspace"),
         _ => panic!(),
     }
 }
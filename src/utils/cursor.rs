@@ -0,0 +1,57 @@
+/// Point of the content pointed at, to read the values there
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    position: f64,
+    height: f64,
+}
+
+impl Cursor {
+    /// Builds a new cursor
+    /// # Arguments
+    ///
+    /// * 'position' - Relative position in time btw 0 and 1, like the zoom
+    /// * 'height' - Relative height within the channels, 0 is the bottom
+    pub fn new(position: f64, height: f64) -> Self {
+        Cursor {
+            position: position.clamp(0f64, 1f64),
+            height: height.clamp(0f64, 1f64),
+        }
+    }
+
+    /// Get the relative position in time of the cursor btw 0 and 1
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    /// Get the relative height of the cursor btw 0 (bottom) and 1 (top)
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// Move the cursor, staying within the content
+    pub fn move_by(&mut self, position: f64, height: f64) {
+        *self = Cursor::new(self.position + position, self.height + height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cursor;
+
+    #[test]
+    fn check_cursor_bounds() {
+        let mut cursor = Cursor::new(0.5f64, 2f64);
+        assert_eq!(cursor.height(), 1f64);
+
+        cursor.move_by(0.25f64, -0.5f64);
+        assert_eq!(cursor.position(), 0.75f64);
+        assert_eq!(cursor.height(), 0.5f64);
+
+        cursor.move_by(1f64, -1f64);
+        assert_eq!(cursor.position(), 1f64);
+        assert_eq!(cursor.height(), 0f64);
+
+        cursor.move_by(-2f64, 0f64);
+        assert_eq!(cursor.position(), 0f64);
+    }
+}
@@ -3,15 +3,16 @@ use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
+use termion::event::{Event as TermEvent, Key, MouseEvent};
 use termion::input::TermRead;
 
 pub enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
     Tick,
 }
 
-/// A small event handler that wrap termion input and tick events. Each event
+/// A small event handler that wrap termion input, mouse and tick events. Each event
 /// type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
@@ -41,8 +42,13 @@ impl Events {
             let tx = tx.clone();
             thread::spawn(move || {
                 let stdin = io::stdin();
-                for key in stdin.keys().flatten() {
-                    if let Err(err) = tx.send(Event::Input(key)) {
+                for event in stdin.events().flatten() {
+                    let event = match event {
+                        TermEvent::Key(key) => Event::Input(key),
+                        TermEvent::Mouse(mouse) => Event::Mouse(mouse),
+                        TermEvent::Unsupported(_) => continue,
+                    };
+                    if let Err(err) = tx.send(event) {
                         eprintln!("{}", err);
                         return;
                     }
@@ -1,5 +1,12 @@
+mod amplitude;
+pub use amplitude::{AmplitudeMapping, AmplitudeScale};
+pub mod axis;
 pub mod bindings;
+mod cursor;
+pub use cursor::Cursor;
 pub mod filled_rectangle;
+mod selection;
+pub use selection::Selection;
 mod zoom;
 pub use zoom::*;
 pub mod event;
@@ -32,6 +39,21 @@ pub fn deinterleave<T: NumAssign + Copy>(channels: usize, src: &[T], dst: &mut [
         });
 }
 
+/// Convert an i32 sample amplitude to dB relative to full scale
+pub fn i32_to_dbfs(value: i32) -> f64 {
+    to_dbfs((value as f64).abs() / i32::MAX as f64)
+}
+
+/// Convert an amplitude relative to full scale to dB
+pub fn to_dbfs(amplitude: f64) -> f64 {
+    20f64 * amplitude.log10()
+}
+
+/// Convert a level in dB to an amplitude relative to full scale
+pub fn from_dbfs(level: f64) -> f64 {
+    10f64.powf(level / 20f64)
+}
+
 /* === Useful structs that could be helpful when debugging ===
     Commented to remove clippy warning
 */
@@ -0,0 +1,67 @@
+/// Range of the content marked by the user, shared by all the tabs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    start: f64,
+    end: f64,
+}
+
+impl Selection {
+    /// Builds a new selection between two relative positions btw 0 and 1, in
+    /// any order
+    pub fn new(first: f64, second: f64) -> Self {
+        let (first, second) = (first.clamp(0f64, 1f64), second.clamp(0f64, 1f64));
+        Selection {
+            start: f64::min(first, second),
+            end: f64::max(first, second),
+        }
+    }
+
+    /// Get the relative starting point of the selection btw 0 and 1
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    /// Get the relative ending point of the selection btw 0 and 1
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    /// Move the start of the selection, keeping its end
+    pub fn with_start(&self, start: f64) -> Self {
+        Selection::new(start, self.end)
+    }
+
+    /// Move the end of the selection, keeping its start
+    pub fn with_end(&self, end: f64) -> Self {
+        Selection::new(self.start, end)
+    }
+
+    /// Frames covered out of `frames`, as [start; end[
+    pub fn frames(&self, frames: usize) -> (usize, usize) {
+        let to_frame = |position: f64| usize::min((position * frames as f64) as usize, frames);
+        (to_frame(self.start), to_frame(self.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Selection;
+
+    #[test]
+    fn check_selection() {
+        let selection = Selection::new(0.75f64, 0.25f64);
+        assert_eq!(selection.start(), 0.25f64);
+        assert_eq!(selection.end(), 0.75f64);
+        assert_eq!(selection.frames(1000), (250, 750));
+
+        // Bounds swap when crossing each other
+        let selection = selection.with_start(1f64);
+        assert_eq!(selection.start(), 0.75f64);
+        assert_eq!(selection.end(), 1f64);
+        assert_eq!(selection.frames(1000), (750, 1000));
+
+        let selection = selection.with_end(-1f64);
+        assert_eq!(selection.start(), 0f64);
+        assert_eq!(selection.end(), 0.75f64);
+    }
+}
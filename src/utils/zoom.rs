@@ -94,6 +94,43 @@ impl Zoom {
         self.start = center - self.length / 2f64;
     }
 
+    /// Keep the window within the content, with its current length
+    fn clamp_start(&mut self) {
+        self.start = self.start.clamp(0f64, 1f64 - self.length);
+    }
+
+    /// Zoom in, keeping the given relative position of the content under the
+    /// same place of the window
+    pub fn zoom_in_at(&mut self, position: f64) {
+        let ratio = (position - self.start) / self.length;
+        self.zoom_in();
+        self.start = position - ratio * self.length;
+        self.clamp_start();
+    }
+
+    /// Zoom out, keeping the given relative position of the content under the
+    /// same place of the window
+    pub fn zoom_out_at(&mut self, position: f64) {
+        let ratio = (position - self.start) / self.length;
+        self.zoom_out();
+        self.start = position - ratio * self.length;
+        self.clamp_start();
+    }
+
+    /// Move the window to be centered on the given relative position, as much
+    /// as possible
+    pub fn center_on(&mut self, position: f64) {
+        self.start = position - self.length / 2f64;
+        self.clamp_start();
+    }
+
+    /// Zoom on the content between two relative positions, as much as allowed
+    pub fn zoom_on(&mut self, start: f64, end: f64) {
+        let (start, end) = (start.min(end).max(0f64), start.max(end).min(1f64));
+        self.length = (end - start).clamp(self.min, 1f64);
+        self.center_on((start + end) / 2f64);
+    }
+
     pub fn move_left(&mut self) {
         let offset = self.length / 10f64;
 
@@ -277,6 +314,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_zoom_at() {
+        let mut z = Zoom::new(0.001f64).unwrap();
+
+        // The position stays at the same place of the window
+        z.zoom_in_at(0.25f64);
+        assert!(((0.25f64 - z.start()) / z.length() - 0.25f64).abs() < 1e-9);
+        z.zoom_in_at(0.3f64);
+        let ratio = (0.3f64 - z.start()) / z.length();
+        z.zoom_out_at(0.3f64);
+        assert!(((0.3f64 - z.start()) / z.length() - ratio).abs() < 1e-9);
+
+        for _ in 0..100 {
+            z.zoom_out_at(0.99f64);
+            assert!(z.start() >= 0f64);
+            assert!(z.start() + z.length() <= 1f64 + 1e-9);
+        }
+        assert_eq!(z.length(), 1f64);
+    }
+
+    #[test]
+    fn check_zoom_on() {
+        let mut z = Zoom::new(0.01f64).unwrap();
+
+        z.zoom_on(0.6f64, 0.2f64);
+        assert!((z.start() - 0.2f64).abs() < 1e-9);
+        assert!((z.length() - 0.4f64).abs() < 1e-9);
+
+        // Limited by the maximum zoom
+        z.zoom_on(0.5f64, 0.5f64);
+        assert_eq!(z.length(), 0.01f64);
+        assert!((z.start() - 0.495f64).abs() < 1e-9);
+
+        z.center_on(1f64);
+        assert!((z.start() - 0.99f64).abs() < 1e-9);
+        z.center_on(-1f64);
+        assert_eq!(z.start(), 0f64);
+    }
+
     #[test]
     fn check_fuzz_all() {
         let mut rng = rand::thread_rng();